@@ -1,8 +1,20 @@
 //! Build script for chaser-gt
 //!
-//! Generates C header file when the `ffi` feature is enabled.
+//! Generates C header file when the `ffi` feature is enabled, sets up
+//! N-API linking when the `node` feature is enabled, and compiles the gRPC
+//! proto when the `grpc` feature is enabled.
 
 fn main() {
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    // Use the vendored protoc so builds don't need a system install
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_prost_build::compile_protos("proto/chaser_gt.proto").unwrap();
+    }
+
     // Only generate headers when ffi feature is enabled
     #[cfg(feature = "ffi")]
     {
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Input is `key\nencrypted`
+fuzz_target!(|data: &str| {
+    let (key, encrypted) = data.split_once('\n').unwrap_or((data, ""));
+    chaser_gt::fuzzing::decrypt_table(encrypted, key);
+});
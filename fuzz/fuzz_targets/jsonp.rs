@@ -0,0 +1,7 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|response: &str| {
+    chaser_gt::fuzzing::jsonp(response, "geetest_1700000000000");
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Input is `mapping\nlot_number`
+fuzz_target!(|data: &str| {
+    let (mapping, lot_number) = data.split_once('\n').unwrap_or((data, ""));
+    chaser_gt::fuzzing::mapping(mapping, lot_number);
+});
@@ -0,0 +1,267 @@
+//! Synthetic page behavior for invisible (AI) captchas.
+//!
+//! Invisible captchas are scored on what the page observed before the solve:
+//! pointer movement, focus changes, tab visibility and how long the user
+//! stayed on the page. This module generates a coherent bundle of those
+//! signals for a desktop browser viewport.
+//!
+//! [`EventCounts`] tallies the input events behind an answer, which the
+//! script reports in the payload's `em` next to the environment flags.
+
+use rand::Rng;
+use serde_json::{json, Map, Value};
+
+use crate::trajectory::Trajectory;
+
+/// Default desktop viewport, matching the Chrome profile used for requests.
+pub const DEFAULT_VIEWPORT: (u32, u32) = (1920, 1080);
+
+/// A pointer sample in page coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerEvent {
+    pub x: u32,
+    pub y: u32,
+    /// Milliseconds since page load
+    pub t: u32,
+}
+
+/// Kind of focus/visibility transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageEvent {
+    Focus,
+    Blur,
+    Visible,
+    Hidden,
+}
+
+impl PageEvent {
+    /// Returns the short code used in the payload.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PageEvent::Focus => "f",
+            PageEvent::Blur => "b",
+            PageEvent::Visible => "v",
+            PageEvent::Hidden => "h",
+        }
+    }
+}
+
+/// Behavioral signals collected on the page before an invisible solve.
+#[derive(Debug, Clone)]
+pub struct BehaviorBundle {
+    /// Pointer movement samples
+    pub pointer: Vec<PointerEvent>,
+    /// Focus and visibility transitions with their timestamps
+    pub page_events: Vec<(PageEvent, u32)>,
+    /// Time spent on the page before the captcha fired (ms)
+    pub dwell_ms: u32,
+}
+
+impl BehaviorBundle {
+    /// Generate a bundle for the default desktop viewport.
+    pub fn generate() -> Self {
+        Self::generate_for_viewport(DEFAULT_VIEWPORT)
+    }
+
+    /// Generate a bundle for a specific viewport size.
+    ///
+    /// The pointer wanders between a handful of points of interest with
+    /// eased segments, the page starts visible and focused, and occasionally
+    /// loses focus briefly as if the user switched tabs.
+    pub fn generate_for_viewport(viewport: (u32, u32)) -> Self {
+        let mut rng = crate::entropy::rng();
+        let (width, height) = (viewport.0.max(1), viewport.1.max(1));
+
+        let dwell_ms = rng.gen_range(2500..9000);
+        let mut page_events = vec![(PageEvent::Visible, 0), (PageEvent::Focus, 0)];
+
+        // Pointer enters the page a little after load
+        let mut t = rng.gen_range(150..700);
+        let mut pos = (
+            rng.gen_range(width / 4..width * 3 / 4),
+            rng.gen_range(height / 4..height * 3 / 4),
+        );
+        let mut pointer = vec![PointerEvent {
+            x: pos.0,
+            y: pos.1,
+            t,
+        }];
+
+        for _ in 0..rng.gen_range(3..7) {
+            let target = (rng.gen_range(0..width), rng.gen_range(0..height));
+            let steps = rng.gen_range(8..20);
+
+            for step in 1..=steps {
+                let progress = step as f64 / steps as f64;
+                // Ease-in-out so segments start and end slowly
+                let eased = progress * progress * (3.0 - 2.0 * progress);
+                let x = pos.0 as f64 + (target.0 as f64 - pos.0 as f64) * eased;
+                let y = pos.1 as f64 + (target.1 as f64 - pos.1 as f64) * eased;
+                t += rng.gen_range(8..24);
+                if t >= dwell_ms {
+                    break;
+                }
+                pointer.push(PointerEvent {
+                    x: (x.round() as u32).min(width - 1),
+                    y: (y.round() as u32).min(height - 1),
+                    t,
+                });
+            }
+
+            pos = target;
+            // Pause between movements (reading)
+            t += rng.gen_range(200..900);
+            if t >= dwell_ms {
+                break;
+            }
+        }
+
+        // Occasionally the user tabs away and comes back
+        if rng.gen_bool(0.25) {
+            let away = rng.gen_range(dwell_ms / 4..dwell_ms / 2);
+            let back = away + rng.gen_range(400..2000).min(dwell_ms - away - 1);
+            page_events.push((PageEvent::Blur, away));
+            page_events.push((PageEvent::Hidden, away + 2));
+            page_events.push((PageEvent::Visible, back));
+            page_events.push((PageEvent::Focus, back + 3));
+        }
+
+        Self {
+            pointer,
+            page_events,
+            dwell_ms,
+        }
+    }
+
+    /// Serialize the bundle into its payload representation.
+    pub fn to_value(&self) -> Value {
+        let pointer: Vec<[u32; 3]> = self.pointer.iter().map(|p| [p.x, p.y, p.t]).collect();
+        let focus: Vec<Value> = self
+            .page_events
+            .iter()
+            .filter(|(e, _)| matches!(e, PageEvent::Focus | PageEvent::Blur))
+            .map(|(e, t)| json!([e.as_str(), t]))
+            .collect();
+        let visibility: Vec<Value> = self
+            .page_events
+            .iter()
+            .filter(|(e, _)| matches!(e, PageEvent::Visible | PageEvent::Hidden))
+            .map(|(e, t)| json!([e.as_str(), t]))
+            .collect();
+
+        json!({
+            "mm": pointer,
+            "fc": focus,
+            "vs": visibility,
+            "dw": self.dwell_ms,
+        })
+    }
+}
+
+/// Input events the page script counted, reported in `em`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventCounts {
+    /// Pointer moves
+    pub moves: u32,
+    /// Pointer presses
+    pub downs: u32,
+    /// Pointer releases
+    pub ups: u32,
+    /// Presses released in place
+    pub clicks: u32,
+    pub scrolls: u32,
+}
+
+impl EventCounts {
+    /// Events of a drag along `track`.
+    pub fn from_trajectory(track: &Trajectory) -> Self {
+        // The first two samples are the press offset and the press itself
+        let moves = track.points().len().saturating_sub(2) as u32;
+        Self {
+            moves,
+            downs: 1,
+            ups: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Events of the pointer samples in `bundle`.
+    pub fn from_bundle(bundle: &BehaviorBundle) -> Self {
+        Self {
+            moves: bundle.pointer.len() as u32,
+            ..Self::default()
+        }
+    }
+
+    /// Events of clicking `targets` points in turn, moving to each first.
+    pub fn clicks(targets: u32) -> Self {
+        let mut rng = crate::entropy::rng();
+        Self {
+            moves: (0..targets).map(|_| rng.gen_range(10..30)).sum(),
+            downs: targets,
+            ups: targets,
+            clicks: targets,
+            scrolls: 0,
+        }
+    }
+
+    /// Set the counts in the payload's `em` object.
+    pub fn write_to(&self, em: &mut Map<String, Value>) {
+        for (key, count) in [
+            ("mm", self.moves),
+            ("md", self.downs),
+            ("mu", self.ups),
+            ("cl", self.clicks),
+            ("sc", self.scrolls),
+        ] {
+            em.insert(key.to_string(), json!(count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_stays_in_viewport() {
+        let bundle = BehaviorBundle::generate_for_viewport((800, 600));
+
+        assert!(!bundle.pointer.is_empty());
+        for p in &bundle.pointer {
+            assert!(p.x < 800 && p.y < 600);
+            assert!(p.t < bundle.dwell_ms);
+        }
+    }
+
+    #[test]
+    fn test_bundle_serialization() {
+        let bundle = BehaviorBundle::generate();
+        let value = bundle.to_value();
+
+        assert!(value["mm"].is_array());
+        assert_eq!(value["dw"], bundle.dwell_ms);
+        // Page always starts visible and focused
+        assert_eq!(value["vs"][0][0], "v");
+        assert_eq!(value["fc"][0][0], "f");
+    }
+
+    #[test]
+    fn test_event_counts() {
+        let track = Trajectory::slide(120.0);
+        let drag = EventCounts::from_trajectory(&track);
+        assert_eq!(drag.moves as usize, track.points().len() - 2);
+        assert_eq!((drag.downs, drag.ups, drag.clicks), (1, 1, 0));
+
+        let clicks = EventCounts::clicks(3);
+        assert_eq!((clicks.downs, clicks.clicks), (3, 3));
+        assert!(clicks.moves >= 30);
+
+        let mut em = Map::new();
+        em.insert("wd".into(), json!(1));
+        clicks.write_to(&mut em);
+        assert_eq!(em["cl"], 3);
+        assert_eq!(em["sc"], 0);
+        assert_eq!(em["wd"], 1);
+    }
+}
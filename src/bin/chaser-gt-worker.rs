@@ -0,0 +1,203 @@
+//! Redis queue worker for chaser-gt.
+//!
+//! Configured through environment variables:
+//!
+//! - `CHASER_GT_REDIS_URL`: Redis URL (default `redis://127.0.0.1/`)
+//! - `CHASER_GT_QUEUE`: interactive job list (default `chaser-gt:jobs`)
+//! - `CHASER_GT_BATCH_QUEUE`: batch job list (default `chaser-gt:jobs:batch`)
+//! - `CHASER_GT_RESULTS`: result list (default `chaser-gt:results`)
+//! - `CHASER_GT_DEAD_LETTER`: dead-letter list (default `chaser-gt:dead`)
+//! - `CHASER_GT_WORKER_ID`: stable worker ID (default `$HOSTNAME`)
+//! - `CHASER_GT_MAX_CONCURRENCY`: concurrent solves (default 16)
+//! - `CHASER_GT_INTERACTIVE_SHARE` / `CHASER_GT_BATCH_SHARE`: fraction of the
+//!   concurrent solves each priority may use (default 1.0 / 0.5)
+//! - `CHASER_GT_CONFIG`: TOML file overriding `max_concurrency`,
+//!   `interactive_share` and `batch_share`; re-read on SIGHUP or change
+//! - `CHASER_GT_MAX_ATTEMPTS`: attempts before dead-lettering (default 3)
+//! - `CHASER_GT_CACHE_DIR`: constants cache directory
+//! - `CHASER_GT_STRICT_CONSTANTS`: `1` to fail instead of starting with
+//!   outdated cached constants when refreshing them fails
+//! - `CHASER_GT_STRICT_RESPONSES`: `1` to fail solves on Geetest responses
+//!   with unknown fields, logging the raw JSON (for canary instances)
+//! - `CHASER_GT_SOLVE_DEADLINE`: seconds one solve may take, continue
+//!   rounds included (unbounded by default)
+//! - `CHASER_GT_CONSTANTS_REDIS_URL`: Redis shared with other instances for
+//!   constants, so only one refreshes them (`redis-cache` feature)
+//! - `CHASER_GT_PROXY_SESSION_TEMPLATE`: appended to the proxy user name with
+//!   `{id}` replaced per solve (e.g. `-session-{id}`), so rotating gateways
+//!   keep one exit IP for the whole solve
+//! - `CHASER_GT_IMAGE_CACHE_MB` / `CHASER_GT_IMAGE_CACHE_DIR`: enable the
+//!   captcha image cache with this memory budget (default 64) and disk
+//!   directory (memory only if unset)
+//! - `CHASER_GT_CIRCUIT_COOL_DOWN` / `CHASER_GT_CIRCUIT_FAILURE_RATE`: enable
+//!   the per-captcha-ID circuit breaker with this cool-down in seconds
+//!   (default 60) and failure rate that opens it (default 0.8)
+//! - `CHASER_GT_ICON_THREADS` / `CHASER_GT_ICON_INTER_THREADS`: ONNX Runtime
+//!   threads per icon inference within and across operators (`icon`
+//!   feature; default one per core / sequential)
+//! - `CHASER_GT_ICON_OPT_LEVEL`: icon model graph optimization level, 0 to 3
+//!   (default 3)
+//! - `CHASER_GT_ICON_ARENA`: `0` to return icon inference memory to the
+//!   system instead of keeping it in an arena
+//! - `CHASER_GT_ICON_POOL`: icon models loaded for concurrent solves
+//!   (default 2)
+//! - `CHASER_GT_WEBHOOK_SECRET`: HMAC key for signing job callbacks
+//! - `CHASER_GT_CALLBACK_HOSTS`: comma-separated hosts job callbacks may be
+//!   sent to (any public host if unset; callbacks must use https)
+//! - `CHASER_GT_DRAIN_TIMEOUT`: seconds running jobs get to finish on
+//!   SIGTERM (default 30)
+//! - `RUST_LOG`: log filter (default `info`)
+//! - `OTEL_EXPORTER_OTLP_ENDPOINT`: OTLP collector; enables trace and metric
+//!   export (`otel` feature)
+
+use std::env;
+use std::time::Duration;
+
+use chaser_gt::worker::{self, WorkerConfig};
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Keeps OTLP export running until exit
+    #[cfg(feature = "otel")]
+    let _otel = match env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Some(_) => Some(chaser_gt::otel::init_subscriber(&Default::default())?),
+        None => {
+            init_logging();
+            None
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    init_logging();
+
+    let mut config = WorkerConfig::default();
+    if let Ok(url) = env::var("CHASER_GT_REDIS_URL") {
+        config.redis_url = url;
+    }
+    if let Ok(queue) = env::var("CHASER_GT_QUEUE") {
+        config.queue = queue;
+    }
+    if let Ok(queue) = env::var("CHASER_GT_BATCH_QUEUE") {
+        config.batch_queue = queue;
+    }
+    if let Ok(results) = env::var("CHASER_GT_RESULTS") {
+        config.results = results;
+    }
+    if let Ok(dead_letter) = env::var("CHASER_GT_DEAD_LETTER") {
+        config.dead_letter = dead_letter;
+    }
+    if let Ok(id) = env::var("CHASER_GT_WORKER_ID") {
+        config.worker_id = id;
+    }
+    if let Ok(max) = env::var("CHASER_GT_MAX_CONCURRENCY") {
+        config.max_concurrency = max.parse()?;
+    }
+    if let Ok(share) = env::var("CHASER_GT_INTERACTIVE_SHARE") {
+        config.priority_shares.interactive = share.parse()?;
+    }
+    if let Ok(share) = env::var("CHASER_GT_BATCH_SHARE") {
+        config.priority_shares.batch = share.parse()?;
+    }
+    if let Ok(path) = env::var("CHASER_GT_CONFIG") {
+        config.config_file = Some(path.into());
+    }
+    if let Ok(max) = env::var("CHASER_GT_MAX_ATTEMPTS") {
+        config.max_attempts = max.parse()?;
+    }
+    if let Ok(dir) = env::var("CHASER_GT_CACHE_DIR") {
+        config.cache_dir = Some(dir.into());
+    }
+    if let Ok(strict) = env::var("CHASER_GT_STRICT_CONSTANTS") {
+        config.strict_constants = matches!(strict.as_str(), "1" | "true");
+    }
+    if let Ok(strict) = env::var("CHASER_GT_STRICT_RESPONSES") {
+        config.strict_responses = matches!(strict.as_str(), "1" | "true");
+    }
+    if let Ok(secs) = env::var("CHASER_GT_SOLVE_DEADLINE") {
+        config.solve_deadline = Some(Duration::from_secs(secs.parse()?));
+    }
+    #[cfg(feature = "redis-cache")]
+    if let Ok(url) = env::var("CHASER_GT_CONSTANTS_REDIS_URL") {
+        config.constants_redis_url = Some(url);
+    }
+    if let Ok(template) = env::var("CHASER_GT_PROXY_SESSION_TEMPLATE") {
+        config.proxy_session_template = Some(template);
+    }
+    if let Ok(mb) = env::var("CHASER_GT_IMAGE_CACHE_MB") {
+        config
+            .image_cache
+            .get_or_insert_with(Default::default)
+            .memory_bytes = mb.parse::<usize>()? * 1024 * 1024;
+    }
+    if let Ok(dir) = env::var("CHASER_GT_IMAGE_CACHE_DIR") {
+        config
+            .image_cache
+            .get_or_insert_with(Default::default)
+            .disk_dir = Some(dir.into());
+    }
+    if let Ok(secs) = env::var("CHASER_GT_CIRCUIT_COOL_DOWN") {
+        config
+            .circuit_breaker
+            .get_or_insert_with(Default::default)
+            .cool_down = Duration::from_secs(secs.parse()?);
+    }
+    if let Ok(rate) = env::var("CHASER_GT_CIRCUIT_FAILURE_RATE") {
+        config
+            .circuit_breaker
+            .get_or_insert_with(Default::default)
+            .max_failure_rate = rate.parse()?;
+    }
+    #[cfg(feature = "icon")]
+    {
+        if let Ok(threads) = env::var("CHASER_GT_ICON_THREADS") {
+            config
+                .icon
+                .get_or_insert_with(Default::default)
+                .intra_threads = Some(threads.parse()?);
+        }
+        if let Ok(threads) = env::var("CHASER_GT_ICON_INTER_THREADS") {
+            config
+                .icon
+                .get_or_insert_with(Default::default)
+                .inter_threads = Some(threads.parse()?);
+        }
+        if let Ok(level) = env::var("CHASER_GT_ICON_OPT_LEVEL") {
+            config
+                .icon
+                .get_or_insert_with(Default::default)
+                .optimization_level = level.parse()?;
+        }
+        if let Ok(arena) = env::var("CHASER_GT_ICON_ARENA") {
+            config
+                .icon
+                .get_or_insert_with(Default::default)
+                .arena_allocator = !matches!(arena.as_str(), "0" | "false");
+        }
+        if let Ok(size) = env::var("CHASER_GT_ICON_POOL") {
+            config.icon.get_or_insert_with(Default::default).pool_size = size.parse()?;
+        }
+    }
+    if let Ok(secret) = env::var("CHASER_GT_WEBHOOK_SECRET") {
+        config.webhook_secret = Some(secret);
+    }
+    if let Ok(hosts) = env::var("CHASER_GT_CALLBACK_HOSTS") {
+        config.callback_hosts = hosts
+            .split(',')
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    if let Ok(secs) = env::var("CHASER_GT_DRAIN_TIMEOUT") {
+        config.drain_timeout = Duration::from_secs(secs.parse()?);
+    }
+
+    worker::run(config).await?;
+    Ok(())
+}
+
+fn init_logging() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+}
@@ -0,0 +1,5 @@
+//! Generates Kotlin/Swift wrappers for the `uniffi` feature.
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,131 @@
+//! Synchronous API, for CLI tools and codebases without an async runtime.
+//!
+//! Solves run on a multi-threaded runtime the library creates on first use
+//! and shares between all blocking clients.
+//!
+//! ```ignore
+//! use chaser_gt::blocking::BlockingGeeked;
+//! use chaser_gt::{Geeked, RiskType};
+//!
+//! fn main() -> chaser_gt::Result<()> {
+//!     let solver = BlockingGeeked::new(Geeked::builder("captcha_id", RiskType::Slide))?;
+//!     let seccode = solver.solve()?;
+//!     println!("{}", seccode.pass_token);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The blocking calls panic when made from within an async runtime; use the
+//! async API there.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::runtime::Runtime;
+
+use crate::client::{Geeked, GeekedBuilder};
+use crate::error::Result;
+use crate::health::HealthReport;
+use crate::models::{CaptchaSession, SecCode};
+
+/// Runtime shared by all blocking calls, created on first use.
+static RUNTIME: Mutex<Option<Arc<Runtime>>> = Mutex::new(None);
+
+/// Get the shared runtime, creating it if needed.
+fn shared_runtime() -> Result<Arc<Runtime>> {
+    let mut guard = RUNTIME.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(runtime) = guard.as_ref() {
+        return Ok(runtime.clone());
+    }
+
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("chaser-gt-blocking")
+            .build()?,
+    );
+    *guard = Some(runtime.clone());
+    Ok(runtime)
+}
+
+impl Geeked {
+    /// Blocking version of [`solve`](Self::solve).
+    pub fn solve_blocking(&self) -> Result<SecCode> {
+        shared_runtime()?.block_on(self.solve())
+    }
+}
+
+impl GeekedBuilder {
+    /// Blocking version of [`build`](Self::build).
+    pub fn build_blocking(self) -> Result<BlockingGeeked> {
+        BlockingGeeked::new(self)
+    }
+}
+
+/// [`Geeked`] with blocking methods; cheap to clone like [`Geeked`].
+#[derive(Clone)]
+pub struct BlockingGeeked {
+    inner: Geeked,
+    runtime: Arc<Runtime>,
+}
+
+impl BlockingGeeked {
+    /// Build the client from `builder`.
+    pub fn new(builder: GeekedBuilder) -> Result<Self> {
+        let runtime = shared_runtime()?;
+        let inner = runtime.block_on(builder.build())?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Solve a captcha; see [`Geeked::solve`].
+    pub fn solve(&self) -> Result<SecCode> {
+        self.runtime.block_on(self.inner.solve())
+    }
+
+    /// Load a challenge without solving it; see [`Geeked::load_session`].
+    pub fn load_session(&self) -> Result<CaptchaSession> {
+        self.runtime.block_on(self.inner.load_session())
+    }
+
+    /// Check Geetest connectivity; see [`Geeked::health_check`].
+    pub fn health_check(&self, solve: bool) -> HealthReport {
+        self.runtime.block_on(self.inner.health_check(solve))
+    }
+
+    /// The async client, e.g. for its accessors.
+    pub fn inner(&self) -> &Geeked {
+        &self.inner
+    }
+
+    /// Unwrap the async client.
+    pub fn into_inner(self) -> Geeked {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::GeekedError;
+    use crate::models::{Constants, RiskType};
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn test_blocking_solve() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .fixture("https://gcaptcha4.geetest.com/load", 502, "Bad Gateway")
+                .fixture("https://gcaptcha4.geevisit.com/load", 502, "Bad Gateway"),
+        );
+        let solver = Geeked::builder("abc", RiskType::Slide)
+            .constants(Arc::new(Constants::default()))
+            .transport(transport.clone())
+            .max_retries(0)
+            .build_blocking()
+            .unwrap();
+        assert_eq!(solver.inner().captcha_id(), "abc");
+
+        let error = solver.solve().unwrap_err();
+        assert!(matches!(error, GeekedError::ServiceUnavailable(_)));
+        assert!(solver.into_inner().solve_blocking().is_err());
+    }
+}
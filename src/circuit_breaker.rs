@@ -0,0 +1,248 @@
+//! Per-captcha-ID circuit breaker.
+//!
+//! The breaker records how solves for each captcha ID end. When the recent
+//! failure rate of an ID exceeds the configured threshold, its circuit
+//! opens: solves for it fail at once with [`GeekedError::CircuitOpen`]
+//! instead of burning proxies against a captcha whose protocol changed.
+//! After the cool-down one probe solve is let through: success closes the
+//! circuit, failure opens it again.
+//!
+//! ```ignore
+//! use std::sync::Arc;
+//! use chaser_gt::{CircuitBreaker, Geeked, RiskType};
+//!
+//! let breaker = Arc::new(CircuitBreaker::new());
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .circuit_breaker(breaker.clone())
+//!     .build()
+//!     .await?;
+//! solver.solve().await?; // outcome is recorded against the captcha ID
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::error::{GeekedError, Result};
+use crate::models::SecCode;
+
+/// When a circuit opens, and for how long.
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Number of recent outcomes the failure rate is computed over
+    pub window: usize,
+    /// Outcomes needed before a circuit can open
+    pub min_samples: usize,
+    /// Open when this share of recent solves failed
+    pub max_failure_rate: f64,
+    /// How long an open circuit fails solves fast
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_samples: 10,
+            max_failure_rate: 0.8,
+            cool_down: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Whether a solve result says something about the captcha ID: `Some(true)`
+/// for a success, `Some(false)` for a verification or protocol failure, and
+/// `None` for network, proxy and local errors.
+fn succeeded(result: &Result<SecCode>) -> Option<bool> {
+    match result {
+        Ok(_) => Some(true),
+        Err(
+            GeekedError::VerificationFailed { .. }
+            | GeekedError::Geetest { .. }
+            | GeekedError::RiskEscalated { .. }
+            | GeekedError::InvalidResponse(_)
+            | GeekedError::Json(_),
+        ) => Some(false),
+        Err(_) => None,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    /// Recent outcomes, `true` for success
+    recent: VecDeque<bool>,
+    open_until: Option<Instant>,
+}
+
+impl Circuit {
+    /// Whether the next outcome is a probe after the circuit was open.
+    fn probing(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| until <= now)
+    }
+
+    fn open(&mut self, captcha_id: &str, config: &CircuitBreakerConfig, now: Instant) {
+        self.open_until = Some(now + config.cool_down);
+        self.recent.clear();
+        tracing::warn!(
+            "Opening the circuit for captcha {} for {:?}",
+            captcha_id,
+            config.cool_down
+        );
+    }
+
+    fn record(
+        &mut self,
+        captcha_id: &str,
+        success: bool,
+        config: &CircuitBreakerConfig,
+        now: Instant,
+    ) {
+        if self.probing(now) {
+            if success {
+                tracing::info!("Closing the circuit for captcha {}", captcha_id);
+                self.open_until = None;
+                self.recent.push_back(success);
+            } else {
+                self.open(captcha_id, config, now);
+            }
+            return;
+        }
+        // Solves started before the circuit opened
+        if self.open_until.is_some() {
+            return;
+        }
+
+        self.recent.push_back(success);
+        while self.recent.len() > config.window.max(1) {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= config.min_samples {
+            let failures = self.recent.iter().filter(|success| !**success).count();
+            if failures as f64 / self.recent.len() as f64 > config.max_failure_rate {
+                self.open(captcha_id, config, now);
+            }
+        }
+    }
+}
+
+/// Fails solves fast for captcha IDs that keep failing.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker with the default thresholds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a breaker with custom thresholds.
+    pub fn with_config(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Mutex::default(),
+        }
+    }
+
+    /// Fail with [`GeekedError::CircuitOpen`] if the circuit for
+    /// `captcha_id` is open.
+    pub fn check(&self, captcha_id: &str) -> Result<()> {
+        let circuits = self.circuits.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match circuits.get(captcha_id).and_then(|c| c.open_until) {
+            Some(until) if until > now => Err(GeekedError::CircuitOpen {
+                captcha_id: captcha_id.to_string(),
+                retry_after: until - now,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record how a solve for `captcha_id` ended.
+    pub fn record(&self, captcha_id: &str, result: &Result<SecCode>) {
+        let Some(success) = succeeded(result) else {
+            return;
+        };
+        let mut circuits = self.circuits.lock().unwrap_or_else(PoisonError::into_inner);
+        circuits.entry(captcha_id.to_string()).or_default().record(
+            captcha_id,
+            success,
+            &self.config,
+            Instant::now(),
+        );
+    }
+
+    /// Close the circuit for `captcha_id` and forget its recent failures,
+    /// e.g. after deploying a fix.
+    pub fn reset(&self, captcha_id: &str) {
+        let mut circuits = self.circuits.lock().unwrap_or_else(PoisonError::into_inner);
+        circuits.remove(captcha_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            window: 4,
+            min_samples: 4,
+            max_failure_rate: 0.5,
+            cool_down: Duration::from_secs(10),
+        }
+    }
+
+    fn failed() -> Result<SecCode> {
+        Err(GeekedError::VerificationFailed {
+            message: "fail".into(),
+        })
+    }
+
+    #[test]
+    fn test_failures_open_circuit() {
+        let breaker = CircuitBreaker::with_config(config());
+        for _ in 0..3 {
+            breaker.record("abc", &failed());
+        }
+        // Network errors say nothing about the captcha ID
+        breaker.record("abc", &Err(GeekedError::NoProxyAvailable("none".into())));
+        assert!(breaker.check("abc").is_ok());
+
+        breaker.record("abc", &failed());
+        assert!(matches!(
+            breaker.check("abc"),
+            Err(GeekedError::CircuitOpen { ref captcha_id, retry_after })
+                if captcha_id == "abc" && retry_after <= Duration::from_secs(10)
+        ));
+        assert!(breaker.check("other").is_ok());
+
+        breaker.reset("abc");
+        assert!(breaker.check("abc").is_ok());
+    }
+
+    #[test]
+    fn test_probe_after_cool_down() {
+        let config = config();
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        for _ in 0..4 {
+            circuit.record("abc", false, &config, now);
+        }
+        assert_eq!(circuit.open_until, Some(now + config.cool_down));
+
+        // Late outcomes of solves started before the circuit opened
+        circuit.record("abc", false, &config, now);
+        assert_eq!(circuit.open_until, Some(now + config.cool_down));
+
+        // Failed probe opens it again, a successful one closes it
+        let later = now + config.cool_down;
+        circuit.record("abc", false, &config, later);
+        assert_eq!(circuit.open_until, Some(later + config.cool_down));
+        circuit.record("abc", true, &config, later + config.cool_down);
+        assert_eq!(circuit.open_until, None);
+        assert_eq!(circuit.recent.len(), 1);
+    }
+}
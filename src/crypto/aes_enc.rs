@@ -3,6 +3,8 @@
 use aes::Aes128;
 use cbc::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
 
+use crate::error::{GeekedError, Result};
+
 type Aes128CbcEnc = cbc::Encryptor<Aes128>;
 
 /// Encrypt plaintext using AES-128-CBC with PKCS7 padding.
@@ -12,14 +14,15 @@ type Aes128CbcEnc = cbc::Encryptor<Aes128>;
 /// * `key` - 16-character key string
 ///
 /// # Returns
-/// Encrypted bytes
-pub fn encrypt_aes_cbc(plaintext: &str, key: &str) -> Vec<u8> {
-    let key_bytes = key.as_bytes();
+/// Encrypted bytes; an error if `key` is not 16 bytes long
+pub fn encrypt_aes_cbc(plaintext: &str, key: &str) -> Result<Vec<u8>> {
     // Geetest uses a static IV of all zeros (as string "0000000000000000")
     let iv = b"0000000000000000";
 
-    let cipher = Aes128CbcEnc::new(key_bytes.into(), iv.into());
-    cipher.encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes())
+    let cipher = Aes128CbcEnc::new_from_slices(key.as_bytes(), iv).map_err(|_| {
+        GeekedError::Encryption(format!("AES key must be 16 bytes, got {}", key.len()))
+    })?;
+    Ok(cipher.encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes()))
 }
 
 #[cfg(test)]
@@ -30,7 +33,7 @@ mod tests {
     fn test_aes_encryption() {
         let key = "56e508d726649e0d";
         let plaintext = "Hello world!";
-        let encrypted = encrypt_aes_cbc(plaintext, key);
+        let encrypted = encrypt_aes_cbc(plaintext, key).unwrap();
 
         // Should produce some output
         assert!(!encrypted.is_empty());
@@ -43,10 +46,18 @@ mod tests {
         let key = "56e508d726649e0d";
         let plaintext = "test message";
 
-        let enc1 = encrypt_aes_cbc(plaintext, key);
-        let enc2 = encrypt_aes_cbc(plaintext, key);
+        let enc1 = encrypt_aes_cbc(plaintext, key).unwrap();
+        let enc2 = encrypt_aes_cbc(plaintext, key).unwrap();
 
         // Same key + plaintext + IV should produce same output
         assert_eq!(enc1, enc2);
     }
+
+    #[test]
+    fn test_aes_invalid_key() {
+        assert!(matches!(
+            encrypt_aes_cbc("test", "short"),
+            Err(GeekedError::Encryption(_))
+        ));
+    }
 }
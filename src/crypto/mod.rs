@@ -11,7 +11,7 @@ pub use rsa_enc::encrypt_rsa;
 /// Generate a random 16-character hex string (like Python's rand_uid).
 pub fn rand_uid() -> String {
     use rand::Rng;
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::entropy::rng();
     let mut result = String::with_capacity(16);
     for _ in 0..4 {
         let val: u16 = rng.gen_range(0x1000..=0xFFFF);
@@ -30,8 +30,8 @@ pub fn encrypt_w(raw_input: &str, pt: &str) -> crate::error::Result<String> {
 
     match pt {
         "1" => {
-            let enc_key = encrypt_rsa(&random_uid);
-            let enc_input = encrypt_aes_cbc(raw_input, &random_uid);
+            let enc_key = encrypt_rsa(&random_uid)?;
+            let enc_input = encrypt_aes_cbc(raw_input, &random_uid)?;
             Ok(hex::encode(enc_input) + &enc_key)
         }
         "2" => Err(crate::error::GeekedError::Encryption(
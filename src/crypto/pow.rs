@@ -5,6 +5,7 @@ use sha1::Sha1;
 use sha2::Sha256;
 
 use super::rand_uid;
+use crate::error::{GeekedError, Result};
 
 /// Result of PoW computation.
 #[derive(Debug, Clone)]
@@ -27,7 +28,8 @@ pub struct PowResult {
 /// * `datetime` - Datetime string from server
 ///
 /// # Returns
-/// PoW message and signature
+/// PoW message and signature; an error for an unknown hash function, or
+/// more bits than its hashes have
 pub fn generate_pow(
     lot_number: &str,
     captcha_id: &str,
@@ -35,7 +37,26 @@ pub fn generate_pow(
     version: &str,
     bits: u32,
     datetime: &str,
-) -> PowResult {
+) -> Result<PowResult> {
+    let hash_bits = match hash_func {
+        "md5" => 128,
+        "sha1" => 160,
+        "sha256" => 256,
+        _ => {
+            return Err(GeekedError::Encryption(format!(
+                "Unsupported hash function: {}",
+                hash_func
+            )))
+        }
+    };
+    // No nonce could ever satisfy it
+    if bits > hash_bits {
+        return Err(GeekedError::Encryption(format!(
+            "PoW of {} bits exceeds {} hashes",
+            bits, hash_func
+        )));
+    }
+
     let bit_division = (bits / 4) as usize;
     let bit_remainder = bits % 4;
     let prefix = "0".repeat(bit_division);
@@ -60,19 +81,19 @@ pub fn generate_pow(
                 hasher.update(pow_msg.as_bytes());
                 hex::encode(hasher.finalize())
             }
-            "sha256" => {
+            // sha256, the last one accepted above
+            _ => {
                 let mut hasher = Sha256::new();
                 hasher.update(pow_msg.as_bytes());
                 hex::encode(hasher.finalize())
             }
-            _ => panic!("Unsupported hash function: {}", hash_func),
         };
 
         if verify_pow(&hash, &prefix, bit_remainder, bit_division) {
-            return PowResult {
+            return Ok(PowResult {
                 pow_msg,
                 pow_sign: hash,
-            };
+            });
         }
     }
 }
@@ -115,7 +136,8 @@ mod tests {
             "1",
             0,
             "2025-01-01T00:00:00+00:00",
-        );
+        )
+        .unwrap();
 
         assert!(!result.pow_msg.is_empty());
         assert!(!result.pow_sign.is_empty());
@@ -132,11 +154,19 @@ mod tests {
             "1",
             4,
             "2025-01-01T00:00:00+00:00",
-        );
+        )
+        .unwrap();
 
         assert!(result.pow_sign.starts_with('0'));
     }
 
+    #[test]
+    fn test_generate_pow_malformed() {
+        let pow = |hash_func, bits| generate_pow("lot", "id", hash_func, "1", bits, "now");
+        assert!(matches!(pow("sha512", 0), Err(GeekedError::Encryption(_))));
+        assert!(matches!(pow("md5", 129), Err(GeekedError::Encryption(_))));
+    }
+
     #[test]
     fn test_verify_pow() {
         // Test with exact prefix match
@@ -3,6 +3,8 @@
 use num_bigint_dig::BigUint;
 use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
 
+use crate::error::{GeekedError, Result};
+
 /// Geetest's RSA public key modulus (hex).
 const MODULUS_HEX: &str = "00C1E3934D1614465B33053E7F48EE4EC87B14B95EF88947713D25EECBFF7E74C7977D02DC1D9451F79DD5D1C10C29ACB6A9B4D6FB7D0A0279B6719E1772565F09AF627715919221AEF91899CAE08C0D686D748B20A3603BE2318CA6BC2B59706592A9219D0BF05C9F65023A21D2330807252AE0066D59CEEFA5F2748EA80BAB81";
 
@@ -15,19 +17,21 @@ const EXPONENT: u32 = 0x10001;
 /// * `message` - The message to encrypt (typically the random UID)
 ///
 /// # Returns
-/// Hex-encoded encrypted bytes
-pub fn encrypt_rsa(message: &str) -> String {
-    let n = BigUint::parse_bytes(MODULUS_HEX.as_bytes(), 16).expect("Failed to parse RSA modulus");
+/// Hex-encoded encrypted bytes; an error if `message` is too long for the key
+pub fn encrypt_rsa(message: &str) -> Result<String> {
+    let n = BigUint::parse_bytes(MODULUS_HEX.as_bytes(), 16)
+        .ok_or_else(|| GeekedError::Encryption("invalid RSA modulus".into()))?;
     let e = BigUint::from(EXPONENT);
 
-    let public_key = RsaPublicKey::new(n, e).expect("Failed to construct RSA public key");
+    let public_key = RsaPublicKey::new(n, e)
+        .map_err(|e| GeekedError::Encryption(format!("invalid RSA public key: {}", e)))?;
 
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::entropy::rng();
     let encrypted = public_key
         .encrypt(&mut rng, Pkcs1v15Encrypt, message.as_bytes())
-        .expect("RSA encryption failed");
+        .map_err(|e| GeekedError::Encryption(format!("RSA encryption failed: {}", e)))?;
 
-    hex::encode(encrypted)
+    Ok(hex::encode(encrypted))
 }
 
 #[cfg(test)]
@@ -37,7 +41,7 @@ mod tests {
     #[test]
     fn test_rsa_encryption_output_length() {
         let message = "56e508d726649e0d";
-        let encrypted = encrypt_rsa(message);
+        let encrypted = encrypt_rsa(message).unwrap();
 
         // RSA-1024 produces 128 bytes = 256 hex chars
         assert_eq!(encrypted.len(), 256);
@@ -47,8 +51,8 @@ mod tests {
     fn test_rsa_encryption_is_random() {
         let message = "testmessage12345";
 
-        let enc1 = encrypt_rsa(message);
-        let enc2 = encrypt_rsa(message);
+        let enc1 = encrypt_rsa(message).unwrap();
+        let enc2 = encrypt_rsa(message).unwrap();
 
         // PKCS1v1.5 uses random padding, so same message produces different output
         assert_ne!(enc1, enc2);
@@ -57,9 +61,18 @@ mod tests {
     #[test]
     fn test_rsa_encryption_hex_output() {
         let message = "test";
-        let encrypted = encrypt_rsa(message);
+        let encrypted = encrypt_rsa(message).unwrap();
 
         // Should be valid hex
         assert!(encrypted.chars().all(|c| c.is_ascii_hexdigit()));
     }
+
+    #[test]
+    fn test_rsa_message_too_long() {
+        // PKCS1v1.5 fits at most 117 bytes in a 1024-bit key
+        assert!(matches!(
+            encrypt_rsa(&"a".repeat(200)),
+            Err(GeekedError::Encryption(_))
+        ));
+    }
 }
@@ -0,0 +1,239 @@
+//! Labeled captcha samples for improving the solvers.
+//!
+//! A client built with
+//! [`GeekedBuilder::collect_dataset`](crate::GeekedBuilder::collect_dataset)
+//! saves every challenge it solves, with the downloaded images, the answer
+//! and Geetest's verdict on it:
+//!
+//! ```text
+//! <dir>/slide/<lot number>/bg.png
+//! <dir>/slide/<lot number>/slice.png
+//! <dir>/slide/<lot number>/sample.json
+//! ```
+//!
+//! `sample.json` is a [`Sample`], e.g.
+//!
+//! ```json
+//! {"captcha_id": "...", "risk_type": "slide", "lot_number": "...",
+//!  "recorded_at": "2026-10-16T09:30:00Z",
+//!  "images": {"bg": "bg.png", "slice": "slice.png"}, "ques": null,
+//!  "answer": {"type": "slide", "left": 120.5, "top": null},
+//!  "error": null, "verdict": "success"}
+//! ```
+//!
+//! Answers Geetest accepted are labels; rejected ones are hard examples.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::models::{LoadResponse, RiskType};
+use crate::sign::SolverResult;
+
+/// Name of the sidecar file in each sample directory.
+pub const SAMPLE_FILE: &str = "sample.json";
+
+/// What Geetest made of a sample's answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// Not submitted (yet), e.g. because solving failed
+    Pending,
+    /// Accepted
+    Success,
+    /// Rejected
+    Fail,
+    /// Accepted pending another round
+    Continue,
+    /// The challenge expired before it was verified
+    Expired,
+}
+
+/// One saved challenge, stored as `sample.json` next to its images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub captcha_id: String,
+    pub risk_type: RiskType,
+    pub lot_number: String,
+    pub recorded_at: DateTime<Utc>,
+    /// Image files in the sample directory by role (`bg`, `slice`, `imgs`,
+    /// `svg`, `prompt`)
+    pub images: BTreeMap<String, String>,
+    /// Question of the challenge, as served
+    pub ques: Option<Value>,
+    /// The solver's answer
+    pub answer: Option<SolverResult>,
+    /// Why solving failed
+    pub error: Option<String>,
+    pub verdict: Verdict,
+}
+
+impl Sample {
+    /// Read the sample stored in `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read(dir.as_ref().join(SAMPLE_FILE))?;
+        Ok(serde_json::from_slice(&text)?)
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join(SAMPLE_FILE), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Writes samples of the challenges a client solves.
+pub(crate) struct Dataset {
+    dir: PathBuf,
+    /// Images downloaded by running solves, by path
+    downloads: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl Dataset {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            downloads: Mutex::default(),
+        }
+    }
+
+    fn sample_dir(&self, risk_type: &RiskType, lot_number: &str) -> PathBuf {
+        self.dir.join(risk_type.as_str()).join(lot_number)
+    }
+
+    /// Keep `image` for the sample of the challenge that showed it.
+    pub(crate) fn downloaded(&self, path: &str, image: &[u8]) {
+        self.downloads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_string(), image.to_vec());
+    }
+
+    /// Save the images of `data` with the answer or error of solving it.
+    ///
+    /// Failures are logged; collecting must not fail the solve.
+    pub(crate) fn record(
+        &self,
+        captcha_id: &str,
+        risk_type: &RiskType,
+        data: &LoadResponse,
+        result: &Result<SolverResult>,
+    ) {
+        let roles = [
+            ("bg", &data.bg),
+            ("slice", &data.slice),
+            ("imgs", &data.imgs),
+            ("svg", &data.svg),
+            ("prompt", &data.prompt),
+        ];
+        let images: Vec<(&str, &str, Vec<u8>)> = {
+            let mut downloads = self
+                .downloads
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            roles
+                .into_iter()
+                .filter_map(|(role, path)| {
+                    let path = path.as_deref()?;
+                    Some((role, path, downloads.remove(path)?))
+                })
+                .collect()
+        };
+
+        let dir = self.sample_dir(risk_type, &data.lot_number);
+        let mut sample = Sample {
+            captcha_id: captcha_id.to_string(),
+            risk_type: risk_type.clone(),
+            lot_number: data.lot_number.clone(),
+            recorded_at: Utc::now(),
+            images: BTreeMap::new(),
+            ques: data.ques.clone(),
+            answer: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(ToString::to_string),
+            verdict: Verdict::Pending,
+        };
+        let saved = std::fs::create_dir_all(&dir)
+            .map_err(Into::into)
+            .and_then(|()| {
+                for (role, path, image) in &images {
+                    let file = match Path::new(path).extension() {
+                        Some(extension) => format!("{}.{}", role, extension.to_string_lossy()),
+                        None => role.to_string(),
+                    };
+                    std::fs::write(dir.join(&file), image)?;
+                    sample.images.insert(role.to_string(), file);
+                }
+                sample.save(&dir)
+            });
+        if let Err(e) = saved {
+            tracing::warn!("Cannot save sample to {}: {}", dir.display(), e);
+        }
+    }
+
+    /// Record Geetest's verdict on the answer for `lot_number`.
+    pub(crate) fn verdict(&self, risk_type: &RiskType, lot_number: &str, verdict: Verdict) {
+        let dir = self.sample_dir(risk_type, lot_number);
+        let saved = Sample::load(&dir).and_then(|mut sample| {
+            sample.verdict = verdict;
+            sample.save(&dir)
+        });
+        if let Err(e) = saved {
+            tracing::warn!("Cannot update sample in {}: {}", dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::GeekedError;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_sample() {
+        let dir = std::env::temp_dir().join(format!("chaser-gt-dataset-{}", uuid::Uuid::new_v4()));
+        let dataset = Dataset::new(dir.clone());
+        let data: LoadResponse = serde_json::from_value(json!({
+            "lot_number": "lot1", "payload": "p", "process_token": "t", "pt": "0",
+            "slice": "pictures/slice/1.png", "bg": "pictures/bg/1.webp"
+        }))
+        .unwrap();
+        dataset.downloaded("pictures/slice/1.png", b"slice");
+        dataset.downloaded("pictures/bg/1.webp", b"bg");
+
+        let answer = SolverResult::Slide {
+            left: 120.5,
+            top: None,
+            raw: [141.0, 30.0],
+        };
+        dataset.record("abc", &RiskType::Slide, &data, &Ok(answer.clone()));
+        dataset.verdict(&RiskType::Slide, "lot1", Verdict::Success);
+
+        let sample_dir = dir.join("slide").join("lot1");
+        let sample = Sample::load(&sample_dir).unwrap();
+        assert_eq!(sample.answer, Some(answer));
+        assert_eq!(sample.verdict, Verdict::Success);
+        assert_eq!(sample.images["bg"], "bg.webp");
+        assert_eq!(
+            std::fs::read(sample_dir.join("slice.png")).unwrap(),
+            b"slice"
+        );
+        assert!(dataset.downloads.lock().unwrap().is_empty());
+
+        let failed = Err(GeekedError::ImageProcessing("blank".into()));
+        let data = LoadResponse {
+            lot_number: "lot2".into(),
+            ..data
+        };
+        dataset.record("abc", &RiskType::Slide, &data, &failed);
+        let sample = Sample::load(dir.join("slide").join("lot2")).unwrap();
+        assert_eq!(sample.answer, None);
+        assert!(sample.error.unwrap().contains("blank"));
+        assert_eq!(sample.verdict, Verdict::Pending);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
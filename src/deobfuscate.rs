@@ -3,18 +3,29 @@
 //! This module automatically fetches and deobfuscates the latest Geetest
 //! JavaScript to extract the required constants (mapping, abo, device_id).
 //! Constants are cached locally and automatically refreshed when Geetest
-//! updates their script.
+//! updates their script. With the `redis-cache` feature the cache can be
+//! shared between instances (see [`RedisCache`]).
 
 use crate::error::{GeekedError, Result};
 use crate::models::{CachedConstants, Constants};
+use crate::transport::{Transport, TransportRequest};
 use chrono::Utc;
 use regex::Regex;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "redis-cache")]
+use crate::redis_cache::RedisCache;
 
 /// Deobfuscator for extracting Geetest constants.
 pub struct Deobfuscator {
     cache_path: PathBuf,
+    transport: Arc<dyn Transport>,
+    /// Fail instead of falling back to outdated constants
+    strict: bool,
+    #[cfg(feature = "redis-cache")]
+    shared: Option<RedisCache>,
 }
 
 impl Default for Deobfuscator {
@@ -30,43 +41,133 @@ impl Deobfuscator {
             .map(|dirs| dirs.cache_dir().to_path_buf())
             .unwrap_or_else(|| PathBuf::from(".cache"));
 
-        Self {
-            cache_path: cache_dir.join("constants.json"),
-        }
+        Self::with_cache_path(cache_dir.join("constants.json"))
     }
 
     /// Create a Deobfuscator with a custom cache path.
     pub fn with_cache_path(cache_path: PathBuf) -> Self {
-        Self { cache_path }
+        Self {
+            cache_path,
+            // rquest v5 has TLS fingerprinting built-in by default
+            transport: Arc::new(rquest::Client::new()),
+            strict: false,
+            #[cfg(feature = "redis-cache")]
+            shared: None,
+        }
+    }
+
+    /// Fetch the Geetest script through `transport`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Fail when the cached constants are outdated and refreshing them
+    /// fails, instead of using the outdated ones.
+    ///
+    /// Off by default: constants usually keep working for days after a
+    /// script update, so a failed refresh need not stop every solve.
+    pub fn with_strict_refresh(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Share constants with other instances through `cache`.
+    ///
+    /// The local cache is still written, and used whenever Redis is
+    /// unreachable.
+    #[cfg(feature = "redis-cache")]
+    pub fn with_redis_cache(mut self, cache: RedisCache) -> Self {
+        self.shared = Some(cache);
+        self
+    }
+
+    /// Path of the constants cache file.
+    pub fn cache_path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    /// Store externally obtained constants in the cache.
+    ///
+    /// The constants are used as long as their version matches the live
+    /// Geetest script, exactly like constants fetched by this crate.
+    pub fn import_constants(&self, constants: &CachedConstants) -> Result<()> {
+        // Reject mappings the signer could not use
+        crate::sign::LotParser::new(&constants.mapping)?;
+        self.save_cache(constants)
+    }
+
+    /// Constants currently in the cache, if any.
+    pub fn cached_constants(&self) -> Result<Option<CachedConstants>> {
+        self.load_cache()
+    }
+
+    /// Version of the live Geetest script.
+    pub async fn current_version(&self) -> Result<String> {
+        self.fetch_current_version().await
+    }
+
+    /// Fetch and deobfuscate constants even if the cache is current.
+    pub async fn refresh(&self) -> Result<CachedConstants> {
+        let constants = self.fetch_and_deobfuscate().await?;
+        self.save_cache(&constants)?;
+        Ok(constants)
     }
 
     /// Get constants, using cache if valid or fetching fresh ones.
     pub async fn get_constants(&self) -> Result<Constants> {
+        #[cfg(feature = "redis-cache")]
+        if let Some(shared) = &self.shared {
+            match shared.get_constants(self).await {
+                Ok(constants) => return Ok(constants),
+                Err(e) => tracing::warn!("Shared constants unavailable, using local cache: {}", e),
+            }
+        }
+
         // Try to load from cache first
-        if let Ok(Some(cached)) = self.load_cache() {
-            // Check if the cached version is still current
-            match self.fetch_current_version().await {
-                Ok(current_version) => {
-                    if cached.version == current_version {
-                        tracing::debug!("Using cached constants (version: {})", cached.version);
+        let stale = match self.load_cache() {
+            Ok(Some(cached)) => {
+                // Check if the cached version is still current
+                match self.fetch_current_version().await {
+                    Ok(current_version) => {
+                        if cached.version == current_version {
+                            tracing::debug!("Using cached constants (version: {})", cached.version);
+                            #[cfg(feature = "metrics")]
+                            crate::metrics::metrics().constants_cache_hits.inc();
+                            return Ok(cached.into());
+                        }
+                        tracing::info!(
+                            "Geetest version changed: {} -> {}, refreshing constants",
+                            cached.version,
+                            current_version
+                        );
+                        Some(cached)
+                    }
+                    Err(e) => {
+                        // If we can't check version, use cache anyway
+                        tracing::warn!("Failed to check version, using cached constants: {}", e);
                         return Ok(cached.into());
                     }
-                    tracing::info!(
-                        "Geetest version changed: {} -> {}, refreshing constants",
-                        cached.version,
-                        current_version
-                    );
-                }
-                Err(e) => {
-                    // If we can't check version, use cache anyway
-                    tracing::warn!("Failed to check version, using cached constants: {}", e);
-                    return Ok(cached.into());
                 }
             }
-        }
+            _ => None,
+        };
 
         // Fetch and deobfuscate fresh constants
-        let constants = self.fetch_and_deobfuscate().await?;
+        let constants = match self.fetch_and_deobfuscate().await {
+            Ok(constants) => constants,
+            Err(e) => match stale {
+                Some(stale) if !self.strict => {
+                    tracing::warn!(
+                        "Failed to refresh constants, using outdated ones (version: {}): {}",
+                        stale.version,
+                        e
+                    );
+                    return Ok(stale.into());
+                }
+                _ => return Err(e),
+            },
+        };
         self.save_cache(&constants)?;
         Ok(constants.into())
     }
@@ -82,6 +183,14 @@ impl Deobfuscator {
         Ok(Some(cached))
     }
 
+    /// Save constants obtained elsewhere to the local cache, logging failures.
+    #[cfg(feature = "redis-cache")]
+    pub(crate) fn save_local(&self, constants: &CachedConstants) {
+        if let Err(e) = self.save_cache(constants) {
+            tracing::warn!("Failed to cache constants locally: {}", e);
+        }
+    }
+
     /// Save constants to cache.
     fn save_cache(&self, constants: &CachedConstants) -> Result<()> {
         if let Some(parent) = self.cache_path.parent() {
@@ -109,9 +218,6 @@ impl Deobfuscator {
 
     /// Get the static path for the current Geetest script.
     async fn get_static_path(&self) -> Result<String> {
-        // rquest v5 has TLS fingerprinting built-in by default
-        let client = rquest::Client::new();
-
         let params = [
             ("callback", "geetest_1738850809870"),
             ("captcha_id", "588a5218557e1eadf33d682a6958c31b"),
@@ -120,13 +226,8 @@ impl Deobfuscator {
             ("lang", "en"),
         ];
 
-        let resp = client
-            .get("https://gcaptcha4.geetest.com/load")
-            .query(&params)
-            .send()
-            .await?;
-
-        let text = resp.text().await?;
+        let request = TransportRequest::get("https://gcaptcha4.geetest.com/load").query(&params);
+        let text = self.transport.send(request).await?.text();
 
         // Parse JSONP response: geetest_xxx({"status": "success", "data": {...}})
         let json_start = text
@@ -149,7 +250,19 @@ impl Deobfuscator {
     }
 
     /// Fetch and deobfuscate the Geetest script to extract constants.
-    async fn fetch_and_deobfuscate(&self) -> Result<CachedConstants> {
+    ///
+    /// Does not read or write the cache.
+    pub async fn fetch_and_deobfuscate(&self) -> Result<CachedConstants> {
+        let constants = self.deobfuscate_live_script().await;
+        #[cfg(feature = "metrics")]
+        crate::metrics::metrics()
+            .deobfuscation_refreshes
+            .with_label_values(&[crate::metrics::outcome(&constants)])
+            .inc();
+        constants
+    }
+
+    async fn deobfuscate_live_script(&self) -> Result<CachedConstants> {
         let static_path = self.get_static_path().await?;
         let version = static_path
             .split('/')
@@ -159,33 +272,36 @@ impl Deobfuscator {
 
         tracing::info!("Fetching Geetest script version: {}", version);
 
-        // rquest v5 has TLS fingerprinting built-in by default
-        let client = rquest::Client::new();
-
         let script_url = format!("https://static.geetest.com{}/js/gcaptcha4.js", static_path);
-        let script = client.get(&script_url).send().await?.text().await?;
+        let script = self
+            .transport
+            .send(TransportRequest::get(script_url))
+            .await?
+            .text();
 
         // Extract XOR key and encrypted table
         let (encrypted_table, xor_key) = self.extract_table_and_key(&script)?;
 
         // Decrypt the lookup table
-        let table = self.decrypt_table(&encrypted_table, &xor_key);
+        let table = Self::decrypt_table(&encrypted_table, &xor_key)?;
 
         // Replace obfuscated names in script
         let deobfuscated = self.replace_obfuscated_names(&script, &table)?;
-        
+
         // Debug: Log a sample of the deobfuscated script to help diagnose extraction
         // Use char-safe operations to avoid panics on unicode
         if let Some(lib_pos) = deobfuscated.find("_lib") {
             // Take chars around the match position
-            let context: String = deobfuscated.chars()
+            let context: String = deobfuscated
+                .chars()
                 .skip(lib_pos.saturating_sub(20))
                 .take(120)
                 .collect();
             tracing::debug!("Deobfuscated _lib context: {}", context);
         }
         if let Some(abo_pos) = deobfuscated.find("_abo") {
-            let context: String = deobfuscated.chars()
+            let context: String = deobfuscated
+                .chars()
                 .skip(abo_pos.saturating_sub(20))
                 .take(170)
                 .collect();
@@ -196,6 +312,14 @@ impl Deobfuscator {
         let abo = self.extract_abo(&deobfuscated)?;
         let mapping = self.extract_mapping(&deobfuscated)?;
         let device_id = self.extract_device_id(&deobfuscated);
+        let device_id_fields = self.extract_device_id_fields(&deobfuscated);
+        let payload_fields = self.extract_payload_fields(&deobfuscated);
+        if payload_fields.is_empty() {
+            tracing::debug!(
+                "No payload template in script {}, using the built-in one",
+                version
+            );
+        }
 
         Ok(CachedConstants {
             version,
@@ -203,6 +327,9 @@ impl Deobfuscator {
             mapping,
             abo,
             device_id,
+            device_id_fields,
+            payload_fields,
+            protocol: Default::default(),
         })
     }
 
@@ -234,18 +361,24 @@ impl Deobfuscator {
     }
 
     /// Decrypt the lookup table using XOR.
-    fn decrypt_table(&self, encrypted: &str, key: &str) -> Vec<String> {
-        let key_bytes = key.as_bytes();
-        let decrypted: String = encrypted
-            .chars()
-            .enumerate()
-            .map(|(i, c)| {
-                let key_byte = key_bytes[i % key_bytes.len()];
-                ((c as u8) ^ key_byte) as char
-            })
+    ///
+    /// Works on UTF-16 code units like the script's `charCodeAt`, so
+    /// non-ASCII input is not truncated.
+    pub(crate) fn decrypt_table(encrypted: &str, key: &str) -> Result<Vec<String>> {
+        let key_units: Vec<u16> = key.encode_utf16().collect();
+        if key_units.is_empty() {
+            return Err(GeekedError::Deobfuscation("Empty XOR key".into()));
+        }
+        let units: Vec<u16> = encrypted
+            .encode_utf16()
+            .zip(key_units.iter().cycle())
+            .map(|(unit, key_unit)| unit ^ key_unit)
             .collect();
+        let decrypted = String::from_utf16(&units).map_err(|_| {
+            GeekedError::Deobfuscation("Decrypted table is not valid UTF-16".into())
+        })?;
 
-        decrypted.split('^').map(String::from).collect()
+        Ok(decrypted.split('^').map(String::from).collect())
     }
 
     /// Replace obfuscated function calls with actual strings.
@@ -258,7 +391,7 @@ impl Deobfuscator {
             if let (Some(full), Some(index_str)) = (cap.get(0), cap.get(2)) {
                 if let Ok(index) = index_str.as_str().parse::<usize>() {
                     if let Some(replacement) = table.get(index) {
-                        // Use double quotes like Go's fmt.Sprintf("%q", ...) 
+                        // Use double quotes like Go's fmt.Sprintf("%q", ...)
                         result = result.replace(full.as_str(), &format!("\"{}\"", replacement));
                     }
                 }
@@ -316,6 +449,90 @@ impl Deobfuscator {
             .map(|m| m.as_str().to_string())
             .unwrap_or_default()
     }
+
+    /// Extract the fingerprint components hashed into the device ID.
+    fn extract_device_id_fields(&self, script: &str) -> Vec<String> {
+        // Match: ["options"]["deviceIdFields"]=["userAgent","screen",...]
+        let re = Regex::new(r#"\["options"\]\["deviceIdFields"\]=\[([^\]]*)\]"#).ok();
+        re.and_then(|r| r.captures(script))
+            .and_then(|c| c.get(1))
+            .map(|m| {
+                m.as_str()
+                    .split(',')
+                    .map(|field| {
+                        field
+                            .trim()
+                            .trim_matches(|c| c == '"' || c == '\'')
+                            .to_string()
+                    })
+                    .filter(|field| !field.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Extract the top-level keys of the payload object literal, the one
+    /// starting with `"geetest":"captcha"`.
+    fn extract_payload_fields(&self, script: &str) -> Vec<String> {
+        let Some(start) = script
+            .find(r#""geetest":"captcha""#)
+            .and_then(|marker| script[..marker].rfind('{'))
+        else {
+            return Vec::new();
+        };
+        let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+        let mut fields = Vec::new();
+        let mut chars = script[start + 1..].chars().peekable();
+        // Nesting inside the payload object
+        let mut depth = 0usize;
+        let mut expect_key = true;
+        let mut key: Option<String> = None;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' | '\'' => {
+                    let mut text = String::new();
+                    while let Some(next) = chars.next() {
+                        match next {
+                            '\\' => {
+                                chars.next();
+                            }
+                            quote if quote == c => break,
+                            other => text.push(other),
+                        }
+                    }
+                    key = (depth == 0 && expect_key).then_some(text);
+                    expect_key = false;
+                }
+                c if is_ident(c) => {
+                    let mut ident = String::from(c);
+                    while let Some(next) = chars.next_if(|next| is_ident(*next)) {
+                        ident.push(next);
+                    }
+                    key = (depth == 0 && expect_key).then_some(ident);
+                    expect_key = false;
+                }
+                ':' if depth == 0 => fields.extend(key.take()),
+                ',' if depth == 0 => {
+                    key = None;
+                    expect_key = true;
+                }
+                '{' | '[' | '(' => {
+                    depth += 1;
+                    key = None;
+                }
+                '}' | ']' | ')' => match depth.checked_sub(1) {
+                    Some(outer) => depth = outer,
+                    None => break,
+                },
+                c if c.is_whitespace() => {}
+                _ => {
+                    key = None;
+                    expect_key = false;
+                }
+            }
+        }
+        fields
+    }
 }
 
 #[cfg(test)]
@@ -324,15 +541,36 @@ mod tests {
 
     #[test]
     fn test_decrypt_table() {
-        let deob = Deobfuscator::new();
-
         // Simple test case
         let encrypted = "hello";
         let key = "key";
-        let result = deob.decrypt_table(encrypted, key);
+        let result = Deobfuscator::decrypt_table(encrypted, key).unwrap();
 
         // The decryption should produce some output
         assert!(!result.is_empty());
+
+        // XOR is its own inverse, also for non-ASCII text
+        let plain = "_lib^é中^🦀";
+        let encrypted: Vec<u16> = plain
+            .encode_utf16()
+            .zip("key".encode_utf16().cycle())
+            .map(|(unit, key_unit)| unit ^ key_unit)
+            .collect();
+        let encrypted = String::from_utf16(&encrypted).unwrap();
+        assert_eq!(
+            Deobfuscator::decrypt_table(&encrypted, "key").unwrap(),
+            ["_lib", "é中", "🦀"]
+        );
+    }
+
+    #[test]
+    fn test_decrypt_table_malformed() {
+        assert!(matches!(
+            Deobfuscator::decrypt_table("hello", ""),
+            Err(GeekedError::Deobfuscation(_))
+        ));
+        // Leaves the high surrogate of the crab without its low half
+        assert!(Deobfuscator::decrypt_table("🦀", "\u{1}\u{1000}").is_err());
     }
 
     #[test]
@@ -340,11 +578,80 @@ mod tests {
         let deob = Deobfuscator::new();
 
         // Simulate what the deobfuscated script might look like
-        let script = r#"something['_lib']={'TYSC':'opMx'},other"#;
+        let script = r#"something["_lib"]={'TYSC':'opMx'},other"#;
         let result = deob.extract_abo(script);
 
         assert!(result.is_ok());
         let abo = result.unwrap();
         assert_eq!(abo.get("TYSC"), Some(&"opMx".to_string()));
     }
+
+    #[test]
+    fn test_extract_device_id_fields() {
+        let deob = Deobfuscator::new();
+        let script = r#"e["options"]["deviceIdFields"]=["userAgent", "screen",'timezone'];"#;
+        assert_eq!(
+            deob.extract_device_id_fields(script),
+            ["userAgent", "screen", "timezone"]
+        );
+        assert!(deob.extract_device_id_fields("no fields").is_empty());
+    }
+
+    #[test]
+    fn test_extract_payload_fields() {
+        let deob = Deobfuscator::new();
+        let script = r#"var t={"geetest":"captcha",lang:e["lang"]||"zh","ep":"123",
+            "em":{"ph":0,"cp":0},'lot_number':n?e["a:b"]:"",pow_msg:f(1,2)};"#;
+        assert_eq!(
+            deob.extract_payload_fields(script),
+            ["geetest", "lang", "ep", "em", "lot_number", "pow_msg"]
+        );
+        assert!(deob.extract_payload_fields("{lang:1}").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_current_version_through_transport() {
+        let load = r#"geetest_1({"status": "success", "data": {
+            "static_path": "/v4/static/v1.9.3-26b399/"}})"#;
+        let transport = crate::transport::MockTransport::new().fixture(
+            "https://gcaptcha4.geetest.com/load",
+            200,
+            load,
+        );
+        let deob = Deobfuscator::new().with_transport(Arc::new(transport));
+        assert_eq!(deob.current_version().await.unwrap(), "v1.9.3-26b399");
+    }
+
+    #[tokio::test]
+    async fn test_stale_constants_fallback() {
+        let load = r#"geetest_1({"status": "success", "data": {
+            "static_path": "/v4/static/v1.9.4-aaaaaa/"}})"#;
+        // The script itself cannot be fetched
+        let transport = Arc::new(crate::transport::MockTransport::new().fixture(
+            "https://gcaptcha4.geetest.com/load",
+            200,
+            load,
+        ));
+        let path =
+            std::env::temp_dir().join(format!("chaser-gt-stale-{}.json", uuid::Uuid::new_v4()));
+        let deob = Deobfuscator::with_cache_path(path.clone()).with_transport(transport);
+        deob.save_cache(&CachedConstants {
+            version: "v1.9.3-26b399".into(),
+            fetched_at: Utc::now(),
+            mapping: r#"{"n[0:1]":"n[1:2]"}"#.into(),
+            abo: HashMap::new(),
+            device_id: String::new(),
+            device_id_fields: Vec::new(),
+            payload_fields: Vec::new(),
+            protocol: Default::default(),
+        })
+        .unwrap();
+
+        let constants = deob.get_constants().await.unwrap();
+        assert_eq!(constants.mapping, r#"{"n[0:1]":"n[1:2]"}"#);
+
+        let deob = deob.with_strict_refresh(true);
+        assert!(deob.get_constants().await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,145 @@
+//! Randomness, time and UUIDs for the v4 solve path.
+//!
+//! Callback names, challenges, PoW nonces, encryption keys, passtime and
+//! synthetic behavior all draw from here. With the `test-util` feature,
+//! [`crate::test_util`] can replace them per thread so a whole `w` payload
+//! becomes reproducible.
+//!
+//! The clock follows the Geetest server: each `/load` reply carries the
+//! server time, and the measured offset is added to every timestamp handed
+//! out, so machines with a skewed clock still produce plausible values.
+
+#[cfg(feature = "test-util")]
+use std::cell::RefCell;
+#[cfg(feature = "test-util")]
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+
+#[cfg(feature = "test-util")]
+#[derive(Default)]
+pub(crate) struct Overrides {
+    pub(crate) rng: Option<rand::rngs::StdRng>,
+    pub(crate) clock_millis: Option<u64>,
+    pub(crate) uuids: VecDeque<uuid::Uuid>,
+}
+
+#[cfg(feature = "test-util")]
+thread_local! {
+    pub(crate) static OVERRIDES: RefCell<Overrides> = RefCell::default();
+}
+
+/// Random number generator: the thread's RNG, or the seeded one
+/// installed by [`crate::test_util`].
+pub(crate) struct Rng;
+
+/// Run `f` with the current RNG.
+fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    #[cfg(feature = "test-util")]
+    return OVERRIDES.with(|overrides| match overrides.borrow_mut().rng.as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    });
+    #[cfg(not(feature = "test-util"))]
+    f(&mut rand::thread_rng())
+}
+
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        with_rng(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with_rng(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        with_rng(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        with_rng(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+// Only seeded through `test_util`, which is not for production use
+impl rand::CryptoRng for Rng {}
+
+pub(crate) fn rng() -> Rng {
+    Rng
+}
+
+/// Server clock minus local clock, in milliseconds.
+static CLOCK_OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Milliseconds since the Unix epoch on the server's clock.
+pub(crate) fn now_millis() -> u64 {
+    #[cfg(feature = "test-util")]
+    if let Some(millis) = OVERRIDES.with(|overrides| overrides.borrow().clock_millis) {
+        return millis;
+    }
+    local_millis().saturating_add_signed(clock_offset_ms())
+}
+
+/// Milliseconds since the Unix epoch on the local clock, uncorrected.
+pub(crate) fn local_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Offset currently applied to [`now_millis`].
+pub(crate) fn clock_offset_ms() -> i64 {
+    CLOCK_OFFSET_MS.load(Ordering::Relaxed)
+}
+
+/// Record that the server clock read `server_millis` while the request
+/// was in flight between the local times `sent` and `received`, and
+/// return the new offset.
+pub(crate) fn observe_server_time(server_millis: i64, sent: u64, received: u64) -> i64 {
+    let offset = clock_offset(server_millis, sent, received);
+    CLOCK_OFFSET_MS.store(offset, Ordering::Relaxed);
+    offset
+}
+
+/// Offset from a server time, taken halfway through the request.
+fn clock_offset(server_millis: i64, sent: u64, received: u64) -> i64 {
+    let midpoint = sent / 2 + received / 2 + (sent % 2 + received % 2) / 2;
+    server_millis - midpoint as i64
+}
+
+/// A random (v4) UUID.
+pub(crate) fn uuid_v4() -> uuid::Uuid {
+    #[cfg(feature = "test-util")]
+    if let Some(uuid) = OVERRIDES.with(|overrides| overrides.borrow_mut().uuids.pop_front()) {
+        return uuid;
+    }
+    let mut bytes = [0u8; 16];
+    rng().fill_bytes(&mut bytes);
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_is_v4() {
+        let uuid = uuid_v4();
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_ne!(uuid, uuid_v4());
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        // Server 5s ahead, reply took 200ms round trip
+        assert_eq!(
+            clock_offset(1_700_000_005_100, 1_700_000_000_000, 1_700_000_000_200),
+            5_000
+        );
+        assert_eq!(clock_offset(1_000, 3_000, 3_000), -2_000);
+    }
+}
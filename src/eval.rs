@@ -0,0 +1,373 @@
+//! Solver accuracy on labeled samples.
+//!
+//! Runs the slide and icon solvers over a directory of [`dataset`] samples
+//! and compares their answers with the labels, the answers Geetest
+//! accepted. Run it before and after changing a solver to see whether the
+//! change is an improvement:
+//!
+//! ```ignore
+//! let report = eval::evaluate("samples", &EvalConfig::default())?;
+//! println!("{}", report);
+//! ```
+//!
+//! or `chaser-gt eval samples`. Samples without an accepted answer are
+//! skipped, as are icon samples without the `icon` feature.
+//!
+//! [`dataset`]: crate::dataset
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::dataset::{Sample, Verdict, SAMPLE_FILE};
+use crate::error::{GeekedError, Result};
+use crate::models::RiskType;
+use crate::sign::SolverResult;
+use crate::solvers::SlideSolver;
+
+/// How close an answer must be to its label to count as correct.
+#[derive(Debug, Clone)]
+pub struct EvalConfig {
+    /// Slide distance, in pixels
+    pub slide_tolerance: f64,
+    /// Icon distance per click, in percent of the image
+    pub icon_tolerance: f64,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            slide_tolerance: 4.0,
+            icon_tolerance: 5.0,
+        }
+    }
+}
+
+/// Solve time percentiles, in milliseconds.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Latency {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Latency {
+    fn from_millis(mut millis: Vec<f64>) -> Self {
+        millis.sort_by(f64::total_cmp);
+        // Nearest rank
+        let percentile = |p: f64| match millis.len() {
+            0 => 0.0,
+            n => millis[((p * n as f64).ceil() as usize).clamp(1, n) - 1],
+        };
+        Self {
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: percentile(1.0),
+        }
+    }
+}
+
+/// Results for one captcha type.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeReport {
+    pub risk_type: RiskType,
+    /// Labeled samples evaluated
+    pub samples: usize,
+    /// Answers within tolerance of the label
+    pub correct: usize,
+    /// Samples the solver failed on
+    pub errors: usize,
+    /// Mean distance from the label, in pixels (slide) or percent of the
+    /// image per click (icon)
+    pub mean_error: f64,
+    pub latency_ms: Latency,
+}
+
+impl TypeReport {
+    /// Share of the samples answered correctly, from 0 to 1.
+    pub fn accuracy(&self) -> f64 {
+        match self.samples {
+            0 => 0.0,
+            n => self.correct as f64 / n as f64,
+        }
+    }
+}
+
+/// Results of [`evaluate`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EvalReport {
+    /// By captcha type, in name order
+    pub types: Vec<TypeReport>,
+    /// Samples without an accepted answer or of types not evaluated
+    pub skipped: usize,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>9} {:>7} {:>10} {:>9} {:>9} {:>9}",
+            "type", "samples", "accuracy", "errors", "mean err", "p50 ms", "p90 ms", "p99 ms"
+        )?;
+        for report in &self.types {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>8.1}% {:>7} {:>10.2} {:>9.1} {:>9.1} {:>9.1}",
+                report.risk_type.as_str(),
+                report.samples,
+                report.accuracy() * 100.0,
+                report.errors,
+                report.mean_error,
+                report.latency_ms.p50,
+                report.latency_ms.p90,
+                report.latency_ms.p99
+            )?;
+        }
+        write!(f, "{} samples skipped", self.skipped)
+    }
+}
+
+/// Comparison of one answer with its label.
+struct Outcome {
+    /// Distance from the label; `None` if not comparable
+    error: Option<f64>,
+    correct: bool,
+}
+
+#[derive(Default)]
+struct Tally {
+    samples: usize,
+    correct: usize,
+    errors: usize,
+    distances: Vec<f64>,
+    latencies: Vec<f64>,
+}
+
+/// Evaluate the solvers on the samples under `dir`.
+pub fn evaluate(dir: impl AsRef<Path>, config: &EvalConfig) -> Result<EvalReport> {
+    let mut sample_dirs = Vec::new();
+    find_samples(dir.as_ref(), &mut sample_dirs)?;
+    sample_dirs.sort();
+
+    let mut tallies: BTreeMap<String, (RiskType, Tally)> = BTreeMap::new();
+    let mut skipped = 0;
+    for sample_dir in sample_dirs {
+        let sample = match Sample::load(&sample_dir) {
+            Ok(sample) => sample,
+            Err(e) => {
+                tracing::warn!("Skipping {}: {}", sample_dir.display(), e);
+                skipped += 1;
+                continue;
+            }
+        };
+        let (Verdict::Success, Some(label)) = (sample.verdict, &sample.answer) else {
+            skipped += 1;
+            continue;
+        };
+
+        let started = Instant::now();
+        let Some(result) = solve(&sample_dir, &sample, label, config) else {
+            skipped += 1;
+            continue;
+        };
+        let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let (_, tally) = tallies
+            .entry(sample.risk_type.as_str().to_string())
+            .or_insert_with(|| (sample.risk_type.clone(), Tally::default()));
+        tally.samples += 1;
+        match result {
+            Ok(outcome) => {
+                tally.latencies.push(elapsed_ms);
+                tally.correct += usize::from(outcome.correct);
+                tally.distances.extend(outcome.error);
+            }
+            Err(e) => {
+                tracing::debug!("Solver failed on {}: {}", sample_dir.display(), e);
+                tally.errors += 1;
+            }
+        }
+    }
+
+    let types = tallies
+        .into_values()
+        .map(|(risk_type, tally)| TypeReport {
+            risk_type,
+            samples: tally.samples,
+            correct: tally.correct,
+            errors: tally.errors,
+            mean_error: match tally.distances.len() {
+                0 => 0.0,
+                n => tally.distances.iter().sum::<f64>() / n as f64,
+            },
+            latency_ms: Latency::from_millis(tally.latencies),
+        })
+        .collect();
+    Ok(EvalReport { types, skipped })
+}
+
+/// Directories under `dir` holding a sample.
+fn find_samples(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_samples(&path, found)?;
+        } else if path.file_name().is_some_and(|name| name == SAMPLE_FILE) {
+            found.push(dir.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Solve `sample` again and compare with `label`; `None` for types not
+/// evaluated.
+fn solve(
+    dir: &Path,
+    sample: &Sample,
+    label: &SolverResult,
+    config: &EvalConfig,
+) -> Option<Result<Outcome>> {
+    match (&sample.risk_type, label) {
+        (RiskType::Slide, SolverResult::Slide { left, top, .. }) => {
+            Some(solve_slide(dir, sample, *left, *top, config))
+        }
+        #[cfg(feature = "icon")]
+        (RiskType::Icon, SolverResult::Icon { positions, .. }) => {
+            Some(solve_icon(dir, sample, positions, config))
+        }
+        _ => None,
+    }
+}
+
+fn solve_slide(
+    dir: &Path,
+    sample: &Sample,
+    left: f64,
+    top: Option<f64>,
+    config: &EvalConfig,
+) -> Result<Outcome> {
+    let solver = SlideSolver::from_bytes(
+        &read_image(dir, sample, "slice")?,
+        &read_image(dir, sample, "bg")?,
+    )?;
+    let error = match top {
+        Some(top) => {
+            let (x, y) = solver.find_placement();
+            (x - left).hypot(y - top)
+        }
+        None => (solver.find_position() - left).abs(),
+    };
+    Ok(Outcome {
+        error: Some(error),
+        correct: error <= config.slide_tolerance,
+    })
+}
+
+#[cfg(feature = "icon")]
+fn solve_icon(
+    dir: &Path,
+    sample: &Sample,
+    positions: &[Vec<f64>],
+    config: &EvalConfig,
+) -> Result<Outcome> {
+    let questions: Vec<String> = serde_json::from_value(sample.ques.clone().unwrap_or_default())?;
+    let clicks = crate::solvers::IconSolver::shared()?
+        .find_icon_positions(&read_image(dir, sample, "imgs")?, &questions)?;
+    if clicks.len() != positions.len() {
+        return Ok(Outcome {
+            error: None,
+            correct: false,
+        });
+    }
+    let distances: Vec<f64> = clicks
+        .iter()
+        .zip(positions)
+        .map(|([x, y], label)| match label[..] {
+            [label_x, label_y] => (x - label_x).hypot(y - label_y),
+            _ => f64::INFINITY,
+        })
+        .collect();
+    Ok(Outcome {
+        error: Some(distances.iter().sum::<f64>() / distances.len().max(1) as f64),
+        correct: distances.iter().all(|d| *d <= config.icon_tolerance),
+    })
+}
+
+/// The sample's image for `role`.
+fn read_image(dir: &Path, sample: &Sample, role: &str) -> Result<Vec<u8>> {
+    let file = sample
+        .images
+        .get(role)
+        .ok_or_else(|| GeekedError::InvalidResponse(format!("sample has no {} image", role)))?;
+    Ok(std::fs::read(dir.join(file))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage, ImageFormat, Luma};
+    use std::io::Cursor;
+
+    fn square_png(width: u32, height: u32, x0: u32, y0: u32) -> Vec<u8> {
+        let mut image = GrayImage::new(width, height);
+        for x in x0..x0 + 40 {
+            for y in y0..y0 + 40 {
+                image.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    fn write_sample(dir: &Path, lot_number: &str, left: f64, verdict: &str) {
+        let sample_dir = dir.join("slide").join(lot_number);
+        std::fs::create_dir_all(&sample_dir).unwrap();
+        std::fs::write(sample_dir.join("slice.png"), square_png(60, 60, 10, 10)).unwrap();
+        std::fs::write(sample_dir.join("bg.png"), square_png(300, 200, 150, 80)).unwrap();
+        let sample = serde_json::json!({
+            "captcha_id": "abc", "risk_type": "slide", "lot_number": lot_number,
+            "recorded_at": "2026-10-16T09:30:00Z",
+            "images": {"bg": "bg.png", "slice": "slice.png"}, "ques": null,
+            "answer": {"type": "slide", "left": left, "top": null},
+            "error": null, "verdict": verdict
+        });
+        std::fs::write(sample_dir.join(SAMPLE_FILE), sample.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_evaluate_slide() {
+        let dir = std::env::temp_dir().join(format!("chaser-gt-eval-{}", uuid::Uuid::new_v4()));
+        // The solver places the piece at 129
+        write_sample(&dir, "a", 129.5, "success");
+        write_sample(&dir, "b", 149.0, "success");
+        write_sample(&dir, "c", 129.0, "fail");
+
+        let report = evaluate(&dir, &EvalConfig::default()).unwrap();
+        assert_eq!(report.skipped, 1);
+        let slide = &report.types[0];
+        assert_eq!(slide.risk_type, RiskType::Slide);
+        assert_eq!((slide.samples, slide.correct, slide.errors), (2, 1, 0));
+        assert_eq!(slide.accuracy(), 0.5);
+        assert_eq!(slide.mean_error, 10.25);
+        assert!(report.to_string().contains("50.0%"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let latency = Latency::from_millis((1..=100).rev().map(f64::from).collect());
+        assert_eq!(
+            (latency.p50, latency.p90, latency.p99, latency.max),
+            (50.0, 90.0, 99.0, 100.0)
+        );
+        assert_eq!(Latency::from_millis(Vec::new()).max, 0.0);
+    }
+}
@@ -0,0 +1,370 @@
+//! Device fingerprints presented in the `w` payload.
+//!
+//! A [`FingerprintProfile`] describes one coherent device: its user agent,
+//! screen, timezone and language, the WebGL, canvas and audio hashes its
+//! browser produces, and the `em` and `gee_guard` environment checks
+//! Geetest's script derives from them. A client keeps one profile for its
+//! lifetime, like a real device.
+//!
+//! [`library`] holds a few common desktop and mobile browsers. Collections
+//! of captured profiles in the same JSON format load with
+//! [`load_profiles`]:
+//!
+//! ```ignore
+//! let profiles = fingerprint::load_profiles("fingerprints.json")?;
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .fingerprint(profiles[0].clone())
+//!     .build()
+//!     .await?;
+//! ```
+//!
+//! A profile must also agree with the TLS handshake and the `User-Agent`
+//! header of the traffic carrying it; [`inconsistencies`] lists the
+//! contradictions a server could spot.
+//!
+//! A profile's `device_id`, when set, is sent in place of the script's
+//! (usually empty) one; a profile saved with [`save_profiles`] keeps it.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{GeekedError, Result};
+
+/// Screen as reported by `window.screen` and `devicePixelRatio`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScreenMetrics {
+    pub width: u32,
+    pub height: u32,
+    pub avail_width: u32,
+    pub avail_height: u32,
+    pub color_depth: u8,
+    pub pixel_ratio: f64,
+}
+
+/// A coherent set of browser fingerprint values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintProfile {
+    /// Name in its collection, e.g. `chrome-windows`
+    pub name: String,
+    pub user_agent: String,
+    /// `navigator.platform`, e.g. `Win32`
+    pub platform: String,
+    /// Phone or tablet
+    #[serde(default)]
+    pub mobile: bool,
+    pub screen: ScreenMetrics,
+    /// IANA timezone, e.g. `Asia/Shanghai`
+    pub timezone: String,
+    /// `navigator.language`, e.g. `zh-CN`
+    pub language: String,
+    pub webgl_hash: String,
+    pub canvas_hash: String,
+    pub audio_hash: String,
+    /// Environment flags sent as `em`
+    pub em: Map<String, Value>,
+    /// Environment checks sent as `gee_guard`
+    pub gee_guard: Value,
+    /// Payload `device_id`; the script's own is sent if empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub device_id: String,
+}
+
+impl FingerprintProfile {
+    /// The built-in profile called `name`.
+    pub fn builtin(name: &str) -> Option<&'static FingerprintProfile> {
+        library().iter().find(|profile| profile.name == name)
+    }
+
+    /// A random built-in profile, mobile or desktop.
+    pub fn random(mobile: bool) -> FingerprintProfile {
+        let candidates: Vec<&FingerprintProfile> = library()
+            .iter()
+            .filter(|profile| profile.mobile == mobile)
+            .collect();
+        candidates
+            .choose(&mut crate::entropy::rng())
+            .map(|profile| (*profile).clone())
+            .unwrap_or_default()
+    }
+
+    /// Payload `lang`: the primary subtag of the language, e.g. `zh`.
+    pub fn payload_lang(&self) -> &str {
+        self.language.split('-').next().unwrap_or(&self.language)
+    }
+
+    /// Screen size in CSS pixels.
+    pub fn screen_size(&self) -> (u32, u32) {
+        (self.screen.width, self.screen.height)
+    }
+}
+
+impl Default for FingerprintProfile {
+    fn default() -> Self {
+        library()[0].clone()
+    }
+}
+
+/// Browser families with a recognisable TLS handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Chrome,
+    Safari,
+    Firefox,
+}
+
+/// The browser a user agent or TLS handshake claims to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrowserIdentity {
+    pub browser: Browser,
+    pub major_version: u32,
+    pub mobile: bool,
+}
+
+impl BrowserIdentity {
+    /// The browser named by `user_agent`, if recognised.
+    pub fn from_user_agent(user_agent: &str) -> Option<Self> {
+        let version = |token: &str| -> Option<u32> {
+            let (_, rest) = user_agent.split_once(token)?;
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()
+        };
+        let (browser, major_version) = if let Some(v) = version("Firefox/") {
+            (Browser::Firefox, v)
+        } else if let Some(v) = version("Chrome/") {
+            (Browser::Chrome, v)
+        } else if let (Some(v), true) = (version("Version/"), user_agent.contains("Safari/")) {
+            (Browser::Safari, v)
+        } else {
+            return None;
+        };
+        let mobile = ["Mobile", "iPhone", "Android"]
+            .iter()
+            .any(|token| user_agent.contains(token));
+        Some(Self {
+            browser,
+            major_version,
+            mobile,
+        })
+    }
+}
+
+impl fmt::Display for BrowserIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.mobile { "mobile" } else { "desktop" };
+        write!(f, "{:?} {} ({})", self.browser, self.major_version, kind)
+    }
+}
+
+/// Contradictions between a TLS handshake (`None` if it imitates no
+/// browser), the `User-Agent` header and the payload fields of `profile`.
+pub fn inconsistencies(
+    tls: Option<BrowserIdentity>,
+    user_agent: &str,
+    profile: &FingerprintProfile,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if user_agent != profile.user_agent {
+        problems.push(format!(
+            "User-Agent header `{}` differs from the payload's `{}`",
+            user_agent, profile.user_agent
+        ));
+    }
+    let Some(claimed) = BrowserIdentity::from_user_agent(&profile.user_agent) else {
+        problems.push(format!(
+            "unrecognised browser in user agent `{}`",
+            profile.user_agent
+        ));
+        return problems;
+    };
+    if let Some(tls) = tls.filter(|tls| *tls != claimed) {
+        problems.push(format!(
+            "{} TLS handshake with a {} user agent",
+            tls, claimed
+        ));
+    }
+    if claimed.mobile != profile.mobile {
+        problems.push(format!(
+            "{} user agent on a profile with mobile = {}",
+            claimed, profile.mobile
+        ));
+    }
+    let platform_os = match profile.platform.as_str() {
+        "Win32" => "Windows",
+        "MacIntel" => "Macintosh",
+        "iPhone" => "iPhone",
+        "iPad" => "iPad",
+        platform if platform.starts_with("Linux") => "Linux",
+        _ => "",
+    };
+    if platform_os.is_empty() || !profile.user_agent.contains(platform_os) {
+        problems.push(format!(
+            "platform `{}` does not match the user agent",
+            profile.platform
+        ));
+    }
+    // The payload's touch flag is only set by touch screens
+    if profile.em.contains_key("tc") != profile.mobile {
+        problems.push(format!(
+            "`em.tc` is {} on a profile with mobile = {}",
+            if profile.em.contains_key("tc") {
+                "set"
+            } else {
+                "missing"
+            },
+            profile.mobile
+        ));
+    }
+    problems
+}
+
+/// Fail with a [`GeekedError::Config`] listing the [`inconsistencies`].
+pub fn check_consistency(
+    tls: Option<BrowserIdentity>,
+    user_agent: &str,
+    profile: &FingerprintProfile,
+) -> Result<()> {
+    match inconsistencies(tls, user_agent, profile).as_slice() {
+        [] => Ok(()),
+        problems => Err(GeekedError::Config(format!(
+            "inconsistent fingerprint `{}`: {}",
+            profile.name,
+            problems.join("; ")
+        ))),
+    }
+}
+
+/// The built-in profiles; the first is the default.
+pub fn library() -> &'static [FingerprintProfile] {
+    static LIBRARY: OnceLock<Vec<FingerprintProfile>> = OnceLock::new();
+    LIBRARY.get_or_init(|| {
+        parse_profiles(include_str!("profiles.json")).expect("built-in profiles are valid")
+    })
+}
+
+/// Parse a JSON array of profiles.
+pub fn parse_profiles(json: &str) -> Result<Vec<FingerprintProfile>> {
+    let profiles: Vec<FingerprintProfile> = serde_json::from_str(json)
+        .map_err(|e| GeekedError::Config(format!("invalid fingerprint profiles: {}", e)))?;
+    if profiles.is_empty() {
+        return Err(GeekedError::Config(
+            "no fingerprint profiles in collection".into(),
+        ));
+    }
+    if let Some(profile) = profiles
+        .iter()
+        .find(|profile| profile.name.is_empty() || profile.user_agent.is_empty())
+    {
+        return Err(GeekedError::Config(format!(
+            "fingerprint profile {:?} needs a name and a user agent",
+            profile.name
+        )));
+    }
+    Ok(profiles)
+}
+
+/// Read a JSON file with an array of profiles.
+pub fn load_profiles(path: impl AsRef<Path>) -> Result<Vec<FingerprintProfile>> {
+    let path = path.as_ref();
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| GeekedError::Config(format!("cannot read {}: {}", path.display(), e)))?;
+    parse_profiles(&json)
+}
+
+/// Write `profiles` to a JSON file, e.g. to keep their device IDs.
+pub fn save_profiles(path: impl AsRef<Path>, profiles: &[FingerprintProfile]) -> Result<()> {
+    let path = path.as_ref();
+    std::fs::write(path, serde_json::to_vec_pretty(profiles)?)
+        .map_err(|e| GeekedError::Config(format!("cannot write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library() {
+        let default = FingerprintProfile::default();
+        assert_eq!(default.name, "chrome-windows");
+        assert_eq!(default.payload_lang(), "zh");
+        assert_eq!(default.screen_size(), crate::behavior::DEFAULT_VIEWPORT);
+
+        assert!(FingerprintProfile::random(true).mobile);
+        assert!(!FingerprintProfile::random(false).mobile);
+        assert_eq!(
+            FingerprintProfile::builtin("safari-ios").unwrap().platform,
+            "iPhone"
+        );
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let json = serde_json::to_string(library()).unwrap();
+        assert_eq!(parse_profiles(&json).unwrap(), library());
+
+        assert!(matches!(parse_profiles("[]"), Err(GeekedError::Config(_))));
+        let mut nameless = FingerprintProfile::default();
+        nameless.name.clear();
+        let json = serde_json::to_string(&[nameless]).unwrap();
+        assert!(matches!(parse_profiles(&json), Err(GeekedError::Config(_))));
+    }
+
+    #[test]
+    fn test_device_id_is_saved() {
+        let profile = FingerprintProfile::default();
+        assert!(profile.device_id.is_empty());
+        let id = "0123456789abcdef0123456789abcdef".to_string();
+
+        let path = std::env::temp_dir().join(format!("chaser-gt-fp-{}.json", uuid::Uuid::new_v4()));
+        let saved = FingerprintProfile {
+            device_id: id.clone(),
+            ..profile
+        };
+        save_profiles(&path, std::slice::from_ref(&saved)).unwrap();
+        assert_eq!(load_profiles(&path).unwrap()[0].device_id, id);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_consistency() {
+        for profile in library() {
+            let tls = BrowserIdentity::from_user_agent(&profile.user_agent);
+            assert!(tls.is_some(), "{}", profile.name);
+            assert_eq!(
+                inconsistencies(tls, &profile.user_agent, profile),
+                Vec::<String>::new(),
+                "{}",
+                profile.name
+            );
+        }
+
+        let chrome = FingerprintProfile::default();
+        let safari = FingerprintProfile::builtin("safari-ios").unwrap();
+        let safari_tls = BrowserIdentity::from_user_agent(&safari.user_agent);
+        assert_eq!(
+            safari_tls,
+            Some(BrowserIdentity {
+                browser: Browser::Safari,
+                major_version: 17,
+                mobile: true
+            })
+        );
+        let problems = inconsistencies(safari_tls, &chrome.user_agent, &chrome);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Safari 17 (mobile) TLS handshake"));
+
+        let mut mixed = chrome.clone();
+        mixed.platform = "iPhone".into();
+        assert_eq!(inconsistencies(None, &safari.user_agent, &mixed).len(), 2);
+        assert!(matches!(
+            check_consistency(None, &mixed.user_agent, &mixed),
+            Err(GeekedError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,27 @@
+//! Entry points for the cargo-fuzz targets in `fuzz/` (`fuzzing` feature).
+//!
+//! Each function feeds arbitrary input to a parser that handles data from
+//! Geetest. They must return errors, never panic.
+
+use serde_json::Value;
+
+use crate::deobfuscate::Deobfuscator;
+use crate::models::GeetestResponse;
+use crate::sign::LotParser;
+
+/// Parse `response` as a JSONP reply to `callback`.
+pub fn jsonp(response: &str, callback: &str) {
+    let _ = GeetestResponse::<Value>::parse_jsonp(response, callback, false);
+}
+
+/// Parse `mapping` and apply it to `lot_number`.
+pub fn mapping(mapping: &str, lot_number: &str) {
+    if let Ok(parser) = LotParser::new(mapping) {
+        let _ = parser.get_dict(lot_number);
+    }
+}
+
+/// Decrypt a script lookup table.
+pub fn decrypt_table(encrypted: &str, key: &str) {
+    let _ = Deobfuscator::decrypt_table(encrypted, key);
+}
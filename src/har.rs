@@ -0,0 +1,292 @@
+//! HAR capture of solve sessions.
+//!
+//! A [`HarRecorder`] wraps the transport of one solve and keeps every
+//! request and response: `/load`, the image downloads and each `/verify`
+//! round. Saved as a HAR 1.2 file it opens in browser dev tools and HAR
+//! viewers, and its bodies can be turned into [`MockTransport`] fixtures:
+//!
+//! ```ignore
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .record_har("/tmp/solves")
+//!     .build()
+//!     .await?;
+//! solver.solve().await?; // writes /tmp/solves/<solve id>.har
+//! ```
+//!
+//! Redaction masks the session tokens and the seccode (`payload`,
+//! `process_token`, `w`, `pass_token`, `captcha_output`) and `user_info`,
+//! so files can be attached to support tickets.
+//!
+//! [`MockTransport`]: crate::transport::MockTransport
+
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Instant;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::error::Result;
+use crate::transport::{Transport, TransportFuture, TransportRequest, TransportResponse};
+
+/// Query parameters and JSON fields masked by redaction.
+const SECRETS: &[&str] = &[
+    "payload",
+    "process_token",
+    "w",
+    "pass_token",
+    "captcha_output",
+    "user_info",
+];
+
+/// A secret JSON string field, name and value captured.
+fn secret_field() -> &'static Regex {
+    static SECRET_FIELD: OnceLock<Regex> = OnceLock::new();
+    SECRET_FIELD.get_or_init(|| {
+        Regex::new(&format!(r#""({})"\s*:\s*"([^"]*)""#, SECRETS.join("|"))).expect("valid regex")
+    })
+}
+
+struct Entry {
+    started: DateTime<Utc>,
+    request: TransportRequest,
+    elapsed_ms: f64,
+    outcome: std::result::Result<TransportResponse, String>,
+}
+
+/// Transport that records the requests it passes on.
+pub struct HarRecorder {
+    inner: Arc<dyn Transport>,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl HarRecorder {
+    /// Record everything sent through `inner`.
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        Self {
+            inner,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Number of requests recorded so far.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The recording as a HAR 1.2 document, with secrets masked if `redact`.
+    pub fn to_har(&self, redact: bool) -> Value {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "chaser-gt", "version": env!("CARGO_PKG_VERSION")},
+                "entries": entries.iter().map(|entry| entry.to_har(redact)).collect::<Vec<_>>(),
+            }
+        })
+    }
+
+    /// Write the recording to `path` as HAR.
+    pub fn save(&self, path: impl AsRef<Path>, redact: bool) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(&self.to_har(redact))?)?;
+        Ok(())
+    }
+}
+
+impl Transport for HarRecorder {
+    fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let started = Utc::now();
+            let timer = Instant::now();
+            let result = self.inner.send(request.clone()).await;
+            let entry = Entry {
+                started,
+                request,
+                elapsed_ms: timer.elapsed().as_secs_f64() * 1000.0,
+                outcome: match &result {
+                    Ok(response) => Ok(response.clone()),
+                    Err(e) => Err(e.to_string()),
+                },
+            };
+            self.entries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(entry);
+            result
+        })
+    }
+}
+
+impl Entry {
+    fn to_har(&self, redact: bool) -> Value {
+        let query: Vec<(String, String)> = self
+            .request
+            .query
+            .iter()
+            .map(
+                |(name, value)| match redact && SECRETS.contains(&name.as_str()) {
+                    true => (name.clone(), crate::models::redact(value)),
+                    false => (name.clone(), value.clone()),
+                },
+            )
+            .collect();
+        let url = match query.is_empty() {
+            true => self.request.url.clone(),
+            false => {
+                let encoded: Vec<String> = query
+                    .iter()
+                    .map(|(name, value)| {
+                        format!(
+                            "{}={}",
+                            urlencoding::encode(name),
+                            urlencoding::encode(value)
+                        )
+                    })
+                    .collect();
+                format!("{}?{}", self.request.url, encoded.join("&"))
+            }
+        };
+
+        let (status, content, error) = match &self.outcome {
+            Ok(response) => (response.status, content(&response.body, redact), None),
+            Err(e) => (0, json!({"size": 0, "mimeType": ""}), Some(e.as_str())),
+        };
+        let mut entry = json!({
+            "startedDateTime": self.started.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "time": self.elapsed_ms,
+            "request": {
+                "method": "GET",
+                "url": url,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": [],
+                "queryString": query
+                    .iter()
+                    .map(|(name, value)| json!({"name": name, "value": value}))
+                    .collect::<Vec<_>>(),
+                "headersSize": -1,
+                "bodySize": 0,
+            },
+            "response": {
+                "status": status,
+                "statusText": "",
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": [],
+                "content": content,
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+            },
+            "cache": {},
+            "timings": {"send": 0, "wait": self.elapsed_ms, "receive": 0},
+        });
+        if let Some(error) = error {
+            entry["_error"] = json!(error);
+        }
+        entry
+    }
+}
+
+/// HAR `content` of a response body: text as is, images base64-encoded.
+fn content(body: &[u8], redact: bool) -> Value {
+    use base64::Engine;
+
+    let mime_type = match body {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => match std::str::from_utf8(body) {
+            Ok(text) => {
+                let text = match redact {
+                    true => secret_field()
+                        .replace_all(text, |caps: &regex::Captures| {
+                            format!(r#""{}": "{}""#, &caps[1], crate::models::redact(&caps[2]))
+                        })
+                        .into_owned(),
+                    false => text.to_string(),
+                };
+                return json!({"size": body.len(), "mimeType": "text/javascript", "text": text});
+            }
+            Err(_) => "application/octet-stream",
+        },
+    };
+    json!({
+        "size": body.len(),
+        "mimeType": mime_type,
+        "text": base64::engine::general_purpose::STANDARD.encode(body),
+        "encoding": "base64",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    #[tokio::test]
+    async fn test_record_har() {
+        let mock = MockTransport::new()
+            .fixture(
+                "https://gcaptcha4.geetest.com/verify",
+                200,
+                r#"cb({"status": "success", "data": {"seccode": {"pass_token": "abcdef123456"}}})"#,
+            )
+            .fixture(
+                "https://static.geetest.com/bg.png",
+                200,
+                b"\x89PNG\r\n".to_vec(),
+            );
+        let recorder = HarRecorder::new(Arc::new(mock));
+        recorder
+            .send(
+                TransportRequest::get("https://gcaptcha4.geetest.com/verify")
+                    .query(&[("callback", "cb"), ("w", "secretpayload")]),
+            )
+            .await
+            .unwrap();
+        recorder
+            .send(TransportRequest::get("https://static.geetest.com/bg.png"))
+            .await
+            .unwrap();
+        recorder
+            .send(TransportRequest::get(
+                "https://static.geetest.com/missing.png",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(recorder.len(), 3);
+
+        let har = recorder.to_har(false);
+        let entries = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(
+            entries[0]["request"]["url"],
+            "https://gcaptcha4.geetest.com/verify?callback=cb&w=secretpayload"
+        );
+        assert!(entries[0]["response"]["content"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("abcdef123456"));
+        assert_eq!(entries[1]["response"]["content"]["mimeType"], "image/png");
+        assert_eq!(entries[1]["response"]["content"]["encoding"], "base64");
+        assert_eq!(entries[2]["response"]["status"], 0);
+        assert!(entries[2]["_error"].is_string());
+
+        let redacted = recorder.to_har(true).to_string();
+        assert!(!redacted.contains("secretpayload"));
+        assert!(!redacted.contains("abcdef123456"));
+        assert!(redacted.contains("abcd***"));
+    }
+}
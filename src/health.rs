@@ -0,0 +1,86 @@
+//! Health reports from [`Geeked::health_check`](crate::Geeked::health_check).
+
+use std::future::Future;
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// Outcome of one health check.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    /// Check name: `load`, `constants` or `solve`, or `geetest` for an
+    /// outage reported by the server
+    pub name: &'static str,
+    pub ok: bool,
+    /// What was found, or why the check failed
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+impl HealthCheck {
+    /// Run `check`, timing it.
+    pub(crate) async fn run<F>(name: &'static str, check: F) -> Self
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        let started = Instant::now();
+        let result = check.await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let (ok, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        if !ok {
+            tracing::warn!("Health check {} failed: {}", name, detail);
+        }
+        Self {
+            name,
+            ok,
+            detail,
+            duration_ms,
+        }
+    }
+}
+
+/// Result of [`Geeked::health_check`](crate::Geeked::health_check).
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// Whether every check passed
+    pub healthy: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    pub(crate) fn new(checks: Vec<HealthCheck>) -> Self {
+        Self {
+            healthy: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+
+    /// The check with the given name, if it ran.
+    pub fn check(&self, name: &str) -> Option<&HealthCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_report_is_unhealthy_if_any_check_fails() {
+        let report = HealthReport::new(vec![
+            HealthCheck::run("load", async { Ok("loaded".to_string()) }).await,
+            HealthCheck::run("constants", async { Err("stale".to_string()) }).await,
+        ]);
+
+        assert!(!report.healthy);
+        assert!(report.check("load").unwrap().ok);
+        assert_eq!(report.check("constants").unwrap().detail, "stale");
+        assert!(report.check("solve").is_none());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][1]["name"], "constants");
+    }
+}
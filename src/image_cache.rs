@@ -0,0 +1,215 @@
+//! Cache for captcha images.
+//!
+//! Icon question images and some backgrounds repeat across solves. An
+//! [`ImageCache`] shared by many clients keeps recently downloaded images
+//! in memory, least recently used first out, and optionally on disk:
+//!
+//! ```ignore
+//! let cache = Arc::new(ImageCache::new(ImageCacheConfig {
+//!     disk_dir: Some("/var/cache/chaser-gt/images".into()),
+//!     ..Default::default()
+//! }));
+//! let solver = Geeked::builder("captcha_id", RiskType::Icon)
+//!     .image_cache(cache.clone())
+//!     .build()
+//!     .await?;
+//! ```
+//!
+//! Images are keyed by URL. Disk entries are files named by the SHA-256 of
+//! the URL; the oldest are removed once the directory exceeds its budget.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use sha2::{Digest, Sha256};
+
+/// Cache sizes and location.
+#[derive(Debug, Clone)]
+pub struct ImageCacheConfig {
+    /// Bytes of images kept in memory
+    pub memory_bytes: usize,
+    /// Directory for the disk cache; memory only if unset
+    pub disk_dir: Option<PathBuf>,
+    /// Bytes of images kept on disk
+    pub disk_bytes: u64,
+}
+
+impl Default for ImageCacheConfig {
+    fn default() -> Self {
+        Self {
+            memory_bytes: 64 * 1024 * 1024,
+            disk_dir: None,
+            disk_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    images: HashMap<String, Arc<[u8]>>,
+    /// Least recently used first
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl Memory {
+    fn touch(&mut self, url: &str) {
+        if let Some(index) = self.order.iter().position(|u| u == url) {
+            let url = self.order.remove(index).expect("index is in bounds");
+            self.order.push_back(url);
+        }
+    }
+}
+
+/// LRU cache of downloaded images, shared between clients.
+pub struct ImageCache {
+    config: ImageCacheConfig,
+    memory: Mutex<Memory>,
+}
+
+impl ImageCache {
+    pub fn new(config: ImageCacheConfig) -> Self {
+        if let Some(dir) = &config.disk_dir {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                tracing::warn!("Cannot create image cache {}: {}", dir.display(), e);
+            }
+        }
+        Self {
+            config,
+            memory: Mutex::default(),
+        }
+    }
+
+    fn memory(&self) -> std::sync::MutexGuard<'_, Memory> {
+        self.memory.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The cached image for `url`, from memory or disk.
+    pub fn get(&self, url: &str) -> Option<Arc<[u8]>> {
+        {
+            let mut memory = self.memory();
+            if let Some(image) = memory.images.get(url).cloned() {
+                memory.touch(url);
+                return Some(image);
+            }
+        }
+        let image: Arc<[u8]> = std::fs::read(self.disk_path(url)?).ok()?.into();
+        self.insert_memory(url, image.clone());
+        Some(image)
+    }
+
+    /// Cache `image` as the content of `url`.
+    pub fn insert(&self, url: &str, image: &[u8]) {
+        self.insert_memory(url, image.into());
+        if let Some(path) = self.disk_path(url) {
+            match std::fs::write(&path, image) {
+                Ok(()) => self.trim_disk(),
+                Err(e) => tracing::debug!("Cannot write {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    fn insert_memory(&self, url: &str, image: Arc<[u8]>) {
+        if image.len() > self.config.memory_bytes {
+            return;
+        }
+        let mut memory = self.memory();
+        if let Some(old) = memory.images.insert(url.to_string(), image.clone()) {
+            memory.bytes -= old.len();
+            memory.touch(url);
+        } else {
+            memory.order.push_back(url.to_string());
+        }
+        memory.bytes += image.len();
+        while memory.bytes > self.config.memory_bytes {
+            let Some(oldest) = memory.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = memory.images.remove(&oldest) {
+                memory.bytes -= evicted.len();
+            }
+        }
+    }
+
+    fn disk_path(&self, url: &str) -> Option<PathBuf> {
+        let dir = self.config.disk_dir.as_ref()?;
+        Some(dir.join(hex::encode(Sha256::digest(url.as_bytes()))))
+    }
+
+    /// Remove the oldest files until the directory fits its budget.
+    fn trim_disk(&self) {
+        let Some(dir) = &self.config.disk_dir else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.config.disk_bytes {
+            return;
+        }
+        files.sort();
+        for (_, len, path) in files {
+            if total <= self.config.disk_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_lru() {
+        let cache = ImageCache::new(ImageCacheConfig {
+            memory_bytes: 10,
+            ..Default::default()
+        });
+        cache.insert("a", &[1; 4]);
+        cache.insert("b", &[2; 4]);
+        // Using "a" makes "b" the next to go
+        assert_eq!(cache.get("a").as_deref(), Some(&[1u8; 4][..]));
+        cache.insert("c", &[3; 4]);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+
+        cache.insert("huge", &[0; 11]);
+        assert!(cache.get("huge").is_none());
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("chaser-gt-images-{}", uuid::Uuid::new_v4()));
+        let config = ImageCacheConfig {
+            memory_bytes: 0,
+            disk_dir: Some(dir.clone()),
+            disk_bytes: 6,
+        };
+        let cache = ImageCache::new(config.clone());
+        cache.insert("https://static.geetest.com/a.png", &[1; 4]);
+
+        // A new cache (e.g. after a restart) finds it on disk
+        let reopened = ImageCache::new(config);
+        assert_eq!(
+            reopened.get("https://static.geetest.com/a.png").as_deref(),
+            Some(&[1u8; 4][..])
+        );
+        reopened.insert("https://static.geetest.com/b.png", &[2; 4]);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+//! Rolling in-memory solve statistics.
+//!
+//! Every solve in the process is counted in one-minute buckets kept for an
+//! hour, so callers can ask how things went over the last few minutes
+//! without a metrics backend:
+//!
+//! ```ignore
+//! let stats = Geeked::stats();
+//! println!("success rate: {:?}", stats.overall.success_rate);
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::models::RiskType;
+
+/// Minutes of history kept.
+const RETENTION_MINUTES: u64 = 60;
+
+/// Window used by [`Geeked::stats`](crate::Geeked::stats).
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    solves: u64,
+    successes: u64,
+    latency_ms: u64,
+    verify_rounds: u64,
+    continues: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.solves += other.solves;
+        self.successes += other.successes;
+        self.latency_ms += other.latency_ms;
+        self.verify_rounds += other.verify_rounds;
+        self.continues += other.continues;
+    }
+}
+
+/// Statistics of one risk type, or of all of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SolveStats {
+    /// Finished solves
+    pub solves: u64,
+    /// Solves that returned a SecCode
+    pub successes: u64,
+    /// `successes / solves`; `None` without solves
+    pub success_rate: Option<f64>,
+    /// Verify requests sent
+    pub verify_rounds: u64,
+    /// Share of verify rounds Geetest answered with "continue"
+    pub continue_rate: Option<f64>,
+    /// Mean duration of a solve, including retries
+    pub mean_latency_ms: Option<f64>,
+}
+
+impl From<&Counts> for SolveStats {
+    fn from(counts: &Counts) -> Self {
+        let ratio = |num: u64, den: u64| (den > 0).then(|| num as f64 / den as f64);
+        Self {
+            solves: counts.solves,
+            successes: counts.successes,
+            success_rate: ratio(counts.successes, counts.solves),
+            verify_rounds: counts.verify_rounds,
+            continue_rate: ratio(counts.continues, counts.verify_rounds),
+            mean_latency_ms: ratio(counts.latency_ms, counts.solves),
+        }
+    }
+}
+
+/// Solve statistics over a recent window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LiveStats {
+    /// Length of the window, in minutes
+    pub window_minutes: u64,
+    pub overall: SolveStats,
+    /// Per risk type, by API name
+    pub by_risk_type: BTreeMap<String, SolveStats>,
+}
+
+struct Bucket {
+    minute: u64,
+    by_risk_type: HashMap<String, Counts>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    /// Oldest first
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl Recorder {
+    fn update(&self, minute: u64, risk_type: &RiskType, update: impl FnOnce(&mut Counts)) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.back().is_none_or(|bucket| bucket.minute < minute) {
+            buckets.push_back(Bucket {
+                minute,
+                by_risk_type: HashMap::new(),
+            });
+        }
+        while buckets
+            .front()
+            .is_some_and(|bucket| bucket.minute + RETENTION_MINUTES <= minute)
+        {
+            buckets.pop_front();
+        }
+        // Late records from a solve that straddled a minute land in the
+        // newest bucket
+        let bucket = buckets.back_mut().expect("bucket was just ensured");
+        update(
+            bucket
+                .by_risk_type
+                .entry(risk_type.as_str().to_string())
+                .or_default(),
+        );
+    }
+
+    fn snapshot(&self, now_minute: u64, window_minutes: u64) -> LiveStats {
+        let window_minutes = window_minutes.clamp(1, RETENTION_MINUTES);
+        let buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut overall = Counts::default();
+        let mut by_risk_type: BTreeMap<String, Counts> = BTreeMap::new();
+        for bucket in buckets
+            .iter()
+            .filter(|bucket| bucket.minute + window_minutes > now_minute)
+        {
+            for (risk_type, counts) in &bucket.by_risk_type {
+                overall.add(counts);
+                by_risk_type
+                    .entry(risk_type.clone())
+                    .or_default()
+                    .add(counts);
+            }
+        }
+        LiveStats {
+            window_minutes,
+            overall: (&overall).into(),
+            by_risk_type: by_risk_type
+                .iter()
+                .map(|(risk_type, counts)| (risk_type.clone(), counts.into()))
+                .collect(),
+        }
+    }
+}
+
+fn recorder() -> &'static Recorder {
+    static RECORDER: OnceLock<Recorder> = OnceLock::new();
+    RECORDER.get_or_init(Recorder::default)
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 60)
+        .unwrap_or(0)
+}
+
+/// Count a finished solve.
+pub(crate) fn record_solve(risk_type: &RiskType, success: bool, elapsed: Duration) {
+    recorder().update(current_minute(), risk_type, |counts| {
+        counts.solves += 1;
+        counts.successes += u64::from(success);
+        counts.latency_ms += elapsed.as_millis() as u64;
+    });
+}
+
+/// Count a verify round, and whether it was answered with "continue".
+pub(crate) fn record_verify(risk_type: &RiskType, continued: bool) {
+    recorder().update(current_minute(), risk_type, |counts| {
+        counts.verify_rounds += 1;
+        counts.continues += u64::from(continued);
+    });
+}
+
+/// Statistics of every solve in this process over the last `window`
+/// (rounded up to whole minutes, at most an hour).
+pub fn snapshot(window: Duration) -> LiveStats {
+    recorder().snapshot(current_minute(), window.as_secs().div_ceil(60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let recorder = Recorder::default();
+        let solve = |minute, risk_type: &RiskType, success: bool, ms: u64| {
+            recorder.update(minute, risk_type, |counts| {
+                counts.solves += 1;
+                counts.successes += u64::from(success);
+                counts.latency_ms += ms;
+            });
+        };
+        solve(100, &RiskType::Slide, true, 1000);
+        solve(110, &RiskType::Slide, false, 3000);
+        solve(110, &RiskType::Ai, true, 500);
+        recorder.update(110, &RiskType::Slide, |counts| {
+            counts.verify_rounds += 2;
+            counts.continues += 1;
+        });
+
+        let stats = recorder.snapshot(110, 5);
+        assert_eq!(stats.overall.solves, 2);
+        assert_eq!(stats.overall.success_rate, Some(0.5));
+        let slide = &stats.by_risk_type["slide"];
+        assert_eq!(slide.mean_latency_ms, Some(3000.0));
+        assert_eq!(slide.continue_rate, Some(0.5));
+        assert_eq!(stats.by_risk_type["ai"].continue_rate, None);
+
+        assert_eq!(recorder.snapshot(110, 15).overall.solves, 3);
+        // Buckets older than the retention are dropped
+        solve(200, &RiskType::Slide, true, 10);
+        assert_eq!(recorder.snapshot(200, 60).overall.solves, 1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Prometheus metrics.
+//!
+//! The client records into a crate-wide [`Registry`]. Serve [`encode`] from
+//! your own `/metrics` endpoint, or register [`registry`]'s collectors
+//! elsewhere; the HTTP server exposes it at `GET /metrics`.
+
+use std::sync::OnceLock;
+
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// All metrics recorded by the crate.
+pub struct Metrics {
+    registry: Registry,
+    /// Solves started, by risk type
+    pub solves_started: IntCounterVec,
+    /// Solves that returned a SecCode, by risk type
+    pub solves_succeeded: IntCounterVec,
+    /// Failed solves, by risk type and error code
+    pub solves_failed: IntCounterVec,
+    /// Verify rounds answered with "continue", by risk type
+    pub verify_continue_rounds: IntCounterVec,
+    /// Time spent computing proof of work
+    pub pow_duration: Histogram,
+    /// Image download latency, by outcome
+    pub image_download: HistogramVec,
+    /// Constants deobfuscated from a fresh Geetest script, by outcome
+    pub deobfuscation_refreshes: IntCounterVec,
+    /// Times the cached constants were found to be current
+    pub constants_cache_hits: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let solves_started = IntCounterVec::new(
+            Opts::new("chaser_gt_solves_started_total", "Solves started"),
+            &["risk_type"],
+        )
+        .unwrap();
+        let solves_succeeded = IntCounterVec::new(
+            Opts::new("chaser_gt_solves_succeeded_total", "Solves that succeeded"),
+            &["risk_type"],
+        )
+        .unwrap();
+        let solves_failed = IntCounterVec::new(
+            Opts::new("chaser_gt_solves_failed_total", "Solves that failed"),
+            &["risk_type", "error_code"],
+        )
+        .unwrap();
+        let verify_continue_rounds = IntCounterVec::new(
+            Opts::new(
+                "chaser_gt_verify_continue_rounds_total",
+                "Verify rounds answered with continue",
+            ),
+            &["risk_type"],
+        )
+        .unwrap();
+        let pow_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "chaser_gt_pow_duration_seconds",
+                "Time spent computing proof of work",
+            )
+            .buckets(prometheus::exponential_buckets(0.0005, 4.0, 8).unwrap()),
+        )
+        .unwrap();
+        let image_download = HistogramVec::new(
+            HistogramOpts::new(
+                "chaser_gt_image_download_seconds",
+                "Captcha image download latency",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let deobfuscation_refreshes = IntCounterVec::new(
+            Opts::new(
+                "chaser_gt_deobfuscation_refreshes_total",
+                "Constants deobfuscated from a fresh Geetest script",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let constants_cache_hits = IntCounter::new(
+            "chaser_gt_constants_cache_hits_total",
+            "Times the cached constants were current",
+        )
+        .unwrap();
+
+        // Names are unique and fixed, so registration cannot fail
+        registry.register(Box::new(solves_started.clone())).unwrap();
+        registry
+            .register(Box::new(solves_succeeded.clone()))
+            .unwrap();
+        registry.register(Box::new(solves_failed.clone())).unwrap();
+        registry
+            .register(Box::new(verify_continue_rounds.clone()))
+            .unwrap();
+        registry.register(Box::new(pow_duration.clone())).unwrap();
+        registry.register(Box::new(image_download.clone())).unwrap();
+        registry
+            .register(Box::new(deobfuscation_refreshes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(constants_cache_hits.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            solves_started,
+            solves_succeeded,
+            solves_failed,
+            verify_continue_rounds,
+            pow_duration,
+            image_download,
+            deobfuscation_refreshes,
+            constants_cache_hits,
+        }
+    }
+}
+
+/// The crate's metrics, created on first use.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Registry holding the crate's metrics.
+pub fn registry() -> &'static Registry {
+    &metrics().registry
+}
+
+/// All metrics in the Prometheus text exposition format.
+pub fn encode() -> String {
+    TextEncoder::new()
+        .encode_to_string(&registry().gather())
+        .unwrap_or_default()
+}
+
+/// Label value for a success/failure outcome.
+pub(crate) fn outcome<T, E>(result: &std::result::Result<T, E>) -> &'static str {
+    if result.is_ok() {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_includes_recorded_metrics() {
+        metrics().solves_started.with_label_values(&["slide"]).inc();
+        metrics().pow_duration.observe(0.002);
+
+        let text = encode();
+        assert!(text.contains("chaser_gt_solves_started_total{risk_type=\"slide\"}"));
+        assert!(text.contains("chaser_gt_pow_duration_seconds_count"));
+    }
+}
@@ -1,10 +1,14 @@
 //! Data models for Geetest v4 captcha.
 
+use crate::error::GeekedError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Supported captcha risk types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Parses case-insensitively from strings (`"slide"`, `"ai"`, ...) and
+/// serializes as its API name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RiskType {
     /// Slide puzzle captcha
     Slide,
@@ -16,17 +20,45 @@ pub enum RiskType {
     Ai,
     /// SVG animated icon captcha
     Svg,
+    /// Risk type this version does not know about.
+    ///
+    /// Only produced when deserializing server data; [`FromStr`](std::str::FromStr)
+    /// rejects unknown names so typos in configs fail loudly.
+    Unknown(String),
 }
 
 impl RiskType {
     /// Returns the string representation for API calls.
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             RiskType::Slide => "slide",
             RiskType::Gobang => "gobang",
             RiskType::Icon => "icon",
             RiskType::Ai => "ai",
             RiskType::Svg => "svg",
+            RiskType::Unknown(name) => name,
+        }
+    }
+
+    /// Whether this build has a solver for the type.
+    pub fn is_solvable(&self) -> bool {
+        match self {
+            RiskType::Slide | RiskType::Gobang | RiskType::Ai => true,
+            RiskType::Icon => cfg!(feature = "icon"),
+            RiskType::Svg => cfg!(feature = "svg"),
+            RiskType::Unknown(_) => false,
+        }
+    }
+
+    /// Parse a known risk type name, accepting `invisible` as an alias for `ai`.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "slide" => Some(RiskType::Slide),
+            "gobang" => Some(RiskType::Gobang),
+            "icon" => Some(RiskType::Icon),
+            "ai" | "invisible" => Some(RiskType::Ai),
+            "svg" => Some(RiskType::Svg),
+            _ => None,
         }
     }
 }
@@ -37,43 +69,240 @@ impl std::fmt::Display for RiskType {
     }
 }
 
+impl std::str::FromStr for RiskType {
+    type Err = GeekedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| {
+            GeekedError::UnsupportedType(format!(
+                "Invalid risk_type '{}'. Valid values: slide, gobang, icon, ai, svg",
+                s
+            ))
+        })
+    }
+}
+
+impl Serialize for RiskType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RiskType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::from_name(&name).unwrap_or(RiskType::Unknown(name)))
+    }
+}
+
 /// Response from successful captcha solve.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecCode {
     pub captcha_id: String,
     pub lot_number: String,
     pub pass_token: String,
+    /// Generation time reported by Geetest (Unix seconds, as a string)
     pub gen_time: String,
     pub captcha_output: String,
+    /// When the solve finished; set when the verify response is parsed.
+    /// Seccodes stored without it count as expired.
+    #[serde(default = "unknown_solve_time")]
+    pub solved_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Solve time of seccodes that do not carry one, old enough to be expired.
+fn unknown_solve_time() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::UNIX_EPOCH
+}
+
+impl SecCode {
+    /// How long Geetest accepts a seccode after the solve, unless a site is
+    /// known to allow longer.
+    pub const MAX_AGE: std::time::Duration = std::time::Duration::from_secs(60);
+
+    /// When the seccode stops being accepted, assuming [`MAX_AGE`](Self::MAX_AGE).
+    pub fn expires_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.solved_at + Self::MAX_AGE
+    }
+
+    /// Whether the seccode is older than `max_age`.
+    pub fn is_expired(&self, max_age: std::time::Duration) -> bool {
+        self.age() >= max_age
+    }
+
+    /// Time since the solve finished.
+    pub fn age(&self) -> std::time::Duration {
+        (chrono::Utc::now() - self.solved_at)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    /// Form fields for submitting the result to a site's backend.
+    ///
+    /// Uses the field names Geetest's server-side validation expects.
+    pub fn to_form_params(&self) -> [(&'static str, &str); 4] {
+        [
+            ("lot_number", self.lot_number.as_str()),
+            ("captcha_output", self.captcha_output.as_str()),
+            ("pass_token", self.pass_token.as_str()),
+            ("gen_time", self.gen_time.as_str()),
+        ]
+    }
+
+    /// URL-encoded query string of [`to_form_params`](Self::to_form_params).
+    pub fn to_query_string(&self) -> String {
+        self.to_form_params()
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Copy with the tokens masked, safe to log.
+    pub fn redacted(&self) -> Self {
+        Self {
+            pass_token: redact(&self.pass_token),
+            captcha_output: redact(&self.captcha_output),
+            ..self.clone()
+        }
+    }
+
+    /// JSON object of [`to_form_params`](Self::to_form_params).
+    pub fn to_json_body(&self) -> serde_json::Value {
+        self.to_form_params()
+            .iter()
+            .map(|(k, v)| (k.to_string(), serde_json::Value::from(*v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
 }
 
 /// Raw response wrapper from Geetest API (JSONP format).
 #[derive(Debug, Deserialize)]
 pub struct GeetestResponse<T> {
+    #[serde(default)]
     pub status: String,
     /// Data field - only present on success
     pub data: Option<T>,
-    /// Error code (present when status != "success")
-    #[serde(default)]
+    /// Error code (present when status != "success", can be string or integer)
+    #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
     pub code: Option<String>,
     /// Error message (present when status != "success")  
     #[serde(default)]
     pub msg: Option<String>,
 }
 
+impl<T: serde::de::DeserializeOwned> GeetestResponse<T> {
+    /// Parse a JSONP reply and unwrap its data.
+    ///
+    /// A non-success status becomes [`GeekedError::Geetest`], with the code
+    /// and message taken from the top level or from `data`. Unknown fields
+    /// are ignored, unless `strict`: then they fail the parse with
+    /// [`GeekedError::InvalidResponse`] and the raw JSON is logged.
+    pub(crate) fn parse_jsonp(response: &str, callback: &str, strict: bool) -> crate::Result<T> {
+        // Format: callback({"status": "success", "data": {...}})
+        let json_str = jsonp_body(response, callback).ok_or_else(|| {
+            let preview: String = response.chars().take(200).collect();
+            tracing::error!("Invalid JSONP response: {}", preview);
+            GeekedError::InvalidResponse("Invalid JSONP format".into())
+        })?;
+        // Error replies carry data of a different shape, so only deserialize
+        // it into `T` on success
+        let mut unknown = Vec::new();
+        let wrapper: GeetestResponse<serde_json::Value> = serde_ignored::deserialize(
+            &mut serde_json::Deserializer::from_str(json_str),
+            |path| unknown.push(path.to_string()),
+        )?;
+
+        if wrapper.status != "success" {
+            let field = |name: &str| match wrapper.data.as_ref()?.get(name)? {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            };
+            let code = wrapper
+                .code
+                .clone()
+                .or_else(|| field("code"))
+                .unwrap_or_else(|| wrapper.status.clone());
+            let message = wrapper
+                .msg
+                .clone()
+                .or_else(|| field("msg"))
+                .unwrap_or_else(|| wrapper.status.clone());
+            tracing::error!("Geetest API error: code={}, msg={}", code, message);
+            return Err(GeekedError::Geetest { code, message });
+        }
+
+        let data = wrapper.data.ok_or_else(|| {
+            GeekedError::InvalidResponse("Geetest returned success but no data".into())
+        })?;
+        let data = serde_ignored::deserialize(data, |path| unknown.push(format!("data.{}", path)))?;
+
+        if strict && !unknown.is_empty() {
+            tracing::error!(
+                fields = ?unknown,
+                raw = json_str,
+                "Geetest response carries unknown fields"
+            );
+            return Err(GeekedError::InvalidResponse(format!(
+                "Unknown fields in Geetest response: {}",
+                unknown.join(", ")
+            )));
+        }
+        Ok(data)
+    }
+}
+
+/// The argument of the `callback(...)` wrapper of a JSONP reply.
+///
+/// Some CDNs rewrite the callback name, so any other `geetest_*(...)`
+/// wrapper is accepted as well.
+pub(crate) fn jsonp_body<'a>(response: &'a str, callback: &str) -> Option<&'a str> {
+    const PREFIX: &str = "geetest_";
+    let start = match response.find(&format!("{}(", callback)) {
+        Some(index) => index + callback.len() + 1,
+        None => {
+            let start = response.match_indices(PREFIX).find_map(|(index, _)| {
+                let name_start = index + PREFIX.len();
+                let name_len = response[name_start..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+                response[name_start + name_len..]
+                    .starts_with('(')
+                    .then_some(name_start + name_len + 1)
+            })?;
+            tracing::debug!(callback, "JSONP reply uses a different callback name");
+            start
+        }
+    };
+    let rest = &response[start..];
+    rest.rfind(')').map(|end| &rest[..end])
+}
+
 /// Response from /load endpoint.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoadResponse {
     pub lot_number: String,
     pub payload: String,
     pub process_token: String,
     pub pt: String,
-    pub pow_detail: PowDetail,
+    /// Proof of work to solve; some AI loads come without one
+    #[serde(default)]
+    pub pow_detail: Option<PowDetail>,
+    /// Captcha type the server handed out
+    #[serde(default)]
+    pub captcha_type: Option<RiskType>,
+    /// Verify request layout announced by the server (can be string or integer)
+    #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
+    pub payload_protocol: Option<String>,
     // Slide-specific
     #[serde(default)]
     pub slice: Option<String>,
     #[serde(default)]
     pub bg: Option<String>,
+    /// Axes the slide piece moves along; `xy` for the 2D placement variant
+    #[serde(default)]
+    pub slide_axis: Option<String>,
     // Gobang-specific
     #[serde(default)]
     pub ques: Option<serde_json::Value>,
@@ -85,17 +314,253 @@ pub struct LoadResponse {
     pub svg: Option<String>,
     #[serde(default)]
     pub prompt: Option<String>,
+    // Session metadata
+    #[serde(default)]
+    pub static_path: Option<String>,
+    #[serde(default)]
+    pub feedback: Option<String>,
+    #[serde(default)]
+    pub gct_path: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
+    pub width: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
+    pub height: Option<String>,
+}
+
+impl LoadResponse {
+    /// Copy with the session tokens masked, safe to log or persist.
+    pub fn redacted(&self) -> Self {
+        Self {
+            payload: redact(&self.payload),
+            process_token: redact(&self.process_token),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the slide piece has to be placed vertically as well.
+    pub fn slide_needs_y(&self) -> bool {
+        self.slide_axis.as_deref() == Some("xy")
+    }
+
+    /// Returns the payload protocol the verify request must follow.
+    ///
+    /// Fails with [`GeekedError::UnsupportedProtocol`] for a protocol this
+    /// build cannot send.
+    pub fn payload_protocol(&self) -> Result<PayloadProtocol, GeekedError> {
+        PayloadProtocol::from_server(self.payload_protocol.as_deref())
+    }
+
+    /// Apply the updated state carried by a `continue` verify round.
+    ///
+    /// Returns `true` if the round carries a new challenge (images or
+    /// board) that has to be solved again before the next verify request.
+    pub fn apply_continue(&mut self, round: ContinueRound) -> bool {
+        if let Some(payload) = round.payload {
+            self.payload = payload;
+        }
+        if let Some(process_token) = round.process_token {
+            self.process_token = process_token;
+        }
+        if round.payload_protocol.is_some() {
+            self.payload_protocol = round.payload_protocol;
+        }
+        if let Some(lot_number) = round.lot_number {
+            self.lot_number = lot_number;
+        }
+        if round.pow_detail.is_some() {
+            self.pow_detail = round.pow_detail;
+        }
+
+        let Some(challenge) = round.challenge else {
+            return false;
+        };
+
+        self.captcha_type = challenge.captcha_type.or(self.captcha_type.take());
+        self.slice = challenge.slice.or(self.slice.take());
+        self.bg = challenge.bg.or(self.bg.take());
+        self.ques = challenge.ques.or(self.ques.take());
+        self.imgs = challenge.imgs.or(self.imgs.take());
+        self.svg = challenge.svg.or(self.svg.take());
+        self.prompt = challenge.prompt.or(self.prompt.take());
+
+        true
+    }
+}
+
+/// A loaded captcha session with its metadata.
+///
+/// Wraps the `/load` response and exposes the session fields that the solve
+/// flow itself does not use, for integrators building custom flows.
+#[derive(Debug, Clone)]
+pub struct CaptchaSession {
+    data: LoadResponse,
+}
+
+impl CaptchaSession {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn new(data: LoadResponse) -> Self {
+        Self { data }
+    }
+
+    /// Lot number identifying this session.
+    pub fn lot_number(&self) -> &str {
+        &self.data.lot_number
+    }
+
+    /// Captcha type the server handed out, if it said.
+    pub fn captcha_type(&self) -> Option<&RiskType> {
+        self.data.captcha_type.as_ref()
+    }
+
+    /// Path of the static assets (script version), e.g. `/v4/static/v1.9.3-26b399`.
+    pub fn static_path(&self) -> Option<&str> {
+        self.data.static_path.as_deref()
+    }
+
+    /// URL of the captcha feedback page.
+    pub fn feedback_url(&self) -> Option<&str> {
+        self.data.feedback.as_deref()
+    }
+
+    /// Path of the GCT fingerprint script, if the server requires it.
+    pub fn gct_path(&self) -> Option<&str> {
+        self.data.gct_path.as_deref()
+    }
+
+    /// Captcha widget dimensions in pixels, if the server sent them.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        let parse = |value: &Option<String>| -> Option<u32> {
+            value.as_deref()?.trim().trim_end_matches("px").parse().ok()
+        };
+        Some((parse(&self.data.width)?, parse(&self.data.height)?))
+    }
+
+    /// The underlying `/load` response.
+    pub fn load_response(&self) -> &LoadResponse {
+        &self.data
+    }
+
+    /// Consume the session and return the `/load` response.
+    pub fn into_load_response(self) -> LoadResponse {
+        self.data
+    }
+}
+
+/// Page flavor a solve presents itself as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientType {
+    /// Desktop browser
+    #[default]
+    Web,
+    /// Mobile browser (Geetest's h5 flow)
+    H5,
+}
+
+impl ClientType {
+    /// Returns the string representation for API calls.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientType::Web => "web",
+            ClientType::H5 => "h5",
+        }
+    }
+}
+
+/// Layout of the `/verify` request.
+///
+/// Protocol 1 sends `payload` and `process_token` as plain query parameters.
+/// It is the only layout known; variants are added as Geetest's scripts
+/// show others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum PayloadProtocol {
+    #[default]
+    #[serde(rename = "1")]
+    V1,
+}
+
+impl PayloadProtocol {
+    /// Parse the `payload_protocol` value sent by the server.
+    ///
+    /// Missing values default to protocol 1, which is what the server
+    /// accepted before the field existed. Other values fail with
+    /// [`GeekedError::UnsupportedProtocol`] rather than sending a layout the
+    /// server did not ask for.
+    pub fn from_server(value: Option<&str>) -> Result<Self, GeekedError> {
+        match value.map(str::trim) {
+            None | Some("") | Some("1") => Ok(PayloadProtocol::V1),
+            Some(other) => Err(GeekedError::UnsupportedProtocol(format!(
+                "payload_protocol {} is not implemented",
+                other
+            ))),
+        }
+    }
+
+    /// Returns the string representation for API calls.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadProtocol::V1 => "1",
+        }
+    }
+}
+
+/// Protocol values sent with every solve.
+///
+/// They change with Geetest's scripts; overriding them in the cached
+/// constants or with
+/// [`GeekedBuilder::protocol`](crate::GeekedBuilder::protocol) follows a
+/// change without waiting for a release.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolFields {
+    /// Payload `ep`
+    pub ep: String,
+    /// Payload `biht`
+    pub biht: String,
+    /// `/verify` `pt`
+    pub pt: String,
+}
+
+impl Default for ProtocolFields {
+    fn default() -> Self {
+        Self {
+            ep: "123".to_string(),
+            biht: "1426265548".to_string(),
+            pt: "1".to_string(),
+        }
+    }
 }
 
 /// Proof of Work details from server.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PowDetail {
+    #[serde(default)]
     pub hashfunc: String,
+    #[serde(default)]
     pub version: String,
+    #[serde(default)]
     pub bits: u32,
+    #[serde(default)]
     pub datetime: String,
 }
 
+impl PowDetail {
+    /// Whether the server asks for a proof of work at all. Without a hash
+    /// function the `pow_msg`/`pow_sign` fields are sent empty.
+    pub fn is_required(&self) -> bool {
+        !self.hashfunc.is_empty()
+    }
+
+    /// Server time of the load in milliseconds since the Unix epoch, if
+    /// `datetime` is an RFC 3339 timestamp.
+    pub fn server_millis(&self) -> Option<i64> {
+        chrono::DateTime::parse_from_rfc3339(&self.datetime)
+            .ok()
+            .map(|datetime| datetime.timestamp_millis())
+    }
+}
+
 /// Proof of Work result.
 #[derive(Debug, Clone, Serialize)]
 pub struct PowResult {
@@ -104,7 +569,7 @@ pub struct PowResult {
 }
 
 /// Response from /verify endpoint.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifyResponse {
     pub seccode: Option<SecCode>,
     #[serde(default)]
@@ -112,6 +577,9 @@ pub struct VerifyResponse {
     /// Score from verification (can be string or integer)
     #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
     pub score: Option<String>,
+    /// Failure code for rejected solves (can be string or integer)
+    #[serde(default, deserialize_with = "deserialize_optional_string_or_int")]
+    pub code: Option<String>,
     /// Updated payload for continue responses
     #[serde(default)]
     pub payload: Option<String>,
@@ -124,6 +592,128 @@ pub struct VerifyResponse {
     /// Updated lot_number for continue responses
     #[serde(default)]
     pub lot_number: Option<String>,
+    /// Updated PoW parameters for continue responses
+    #[serde(default)]
+    pub pow_detail: Option<PowDetail>,
+    // New challenge data for continue responses (same fields as LoadResponse)
+    /// Captcha type of the new challenge, if the server switched types
+    #[serde(default)]
+    pub captcha_type: Option<RiskType>,
+    #[serde(default)]
+    pub slice: Option<String>,
+    #[serde(default)]
+    pub bg: Option<String>,
+    #[serde(default)]
+    pub ques: Option<serde_json::Value>,
+    #[serde(default)]
+    pub imgs: Option<String>,
+    #[serde(default)]
+    pub svg: Option<String>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+impl VerifyResponse {
+    /// Copy with the tokens masked, safe to log or persist.
+    pub fn redacted(&self) -> Self {
+        Self {
+            seccode: self.seccode.as_ref().map(SecCode::redacted),
+            payload: self.payload.as_deref().map(redact),
+            process_token: self.process_token.as_deref().map(redact),
+            ..self.clone()
+        }
+    }
+}
+
+/// Interpreted result of a `/verify` request.
+#[derive(Debug, Clone)]
+pub enum VerifyOutcome {
+    /// The captcha was accepted
+    Success(SecCode),
+    /// Multi-round verification: submit again with the updated state
+    Continue(ContinueRound),
+    /// The solve was rejected
+    Fail {
+        /// `result` value reported by the server
+        reason: String,
+        /// Failure code, if the server sent one
+        code: Option<String>,
+    },
+    /// The challenge is no longer valid and a fresh one has to be loaded
+    Retry,
+}
+
+/// State update carried by a `continue` verify response.
+///
+/// Every field is optional: the server only sends what changed.
+#[derive(Debug, Clone, Default)]
+pub struct ContinueRound {
+    pub payload: Option<String>,
+    pub process_token: Option<String>,
+    pub payload_protocol: Option<String>,
+    pub lot_number: Option<String>,
+    pub pow_detail: Option<PowDetail>,
+    /// New challenge to solve before the next round, if any
+    pub challenge: Option<Box<ChallengeData>>,
+}
+
+/// Challenge images or board handed out by a `continue` response.
+#[derive(Debug, Clone, Default)]
+pub struct ChallengeData {
+    pub captcha_type: Option<RiskType>,
+    pub slice: Option<String>,
+    pub bg: Option<String>,
+    pub ques: Option<serde_json::Value>,
+    pub imgs: Option<String>,
+    pub svg: Option<String>,
+    pub prompt: Option<String>,
+}
+
+impl From<VerifyResponse> for VerifyOutcome {
+    fn from(response: VerifyResponse) -> Self {
+        if let Some(mut seccode) = response.seccode {
+            seccode.solved_at = chrono::Utc::now();
+            return VerifyOutcome::Success(seccode);
+        }
+
+        match response.result.as_deref() {
+            Some("continue") => {
+                let has_challenge = response.captcha_type.is_some()
+                    || response.slice.is_some()
+                    || response.bg.is_some()
+                    || response.ques.is_some()
+                    || response.imgs.is_some()
+                    || response.svg.is_some()
+                    || response.prompt.is_some();
+
+                let challenge = has_challenge.then(|| {
+                    Box::new(ChallengeData {
+                        captcha_type: response.captcha_type,
+                        slice: response.slice,
+                        bg: response.bg,
+                        ques: response.ques,
+                        imgs: response.imgs,
+                        svg: response.svg,
+                        prompt: response.prompt,
+                    })
+                });
+
+                VerifyOutcome::Continue(ContinueRound {
+                    payload: response.payload,
+                    process_token: response.process_token,
+                    payload_protocol: response.payload_protocol,
+                    lot_number: response.lot_number,
+                    pow_detail: response.pow_detail,
+                    challenge,
+                })
+            }
+            Some("retry") | Some("reload") => VerifyOutcome::Retry,
+            other => VerifyOutcome::Fail {
+                reason: other.unwrap_or("Unknown verification error").to_string(),
+                code: response.code,
+            },
+        }
+    }
 }
 
 /// Helper to deserialize fields that can be either string or integer
@@ -203,14 +793,51 @@ pub struct CachedConstants {
     pub abo: HashMap<String, String>,
     /// Device ID (usually empty)
     pub device_id: String,
+    /// Fingerprint components newer scripts hash into the device ID, in
+    /// order; empty if the script does not list them
+    #[serde(default)]
+    pub device_id_fields: Vec<String>,
+    /// Top-level fields of the `w` payload this version sends; empty if
+    /// they could not be extracted
+    #[serde(default)]
+    pub payload_fields: Vec<String>,
+    /// Protocol literals; edit to follow a Geetest change without a release
+    #[serde(default)]
+    pub protocol: ProtocolFields,
 }
 
 /// Runtime constants used for signing.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Constants {
     pub mapping: String,
     pub abo: HashMap<String, String>,
     pub device_id: String,
+    pub device_id_fields: Vec<String>,
+    pub payload_fields: Vec<String>,
+    pub protocol: ProtocolFields,
+}
+
+impl Constants {
+    /// Copy with the device ID masked, safe to log.
+    pub fn redacted(&self) -> Self {
+        Self {
+            device_id: redact(&self.device_id),
+            ..self.clone()
+        }
+    }
+}
+
+/// Mask a secret, keeping a short prefix so values can still be told apart.
+pub(crate) fn redact(value: &str) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    let prefix: String = value.chars().take(4).collect();
+    if prefix.len() == value.len() {
+        "***".to_string()
+    } else {
+        format!("{}***", prefix)
+    }
 }
 
 impl From<CachedConstants> for Constants {
@@ -219,6 +846,266 @@ impl From<CachedConstants> for Constants {
             mapping: cached.mapping,
             abo: cached.abo,
             device_id: cached.device_id,
+            device_id_fields: cached.device_id_fields,
+            payload_fields: cached.payload_fields,
+            protocol: cached.protocol,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_protocol_from_server() {
+        assert_eq!(
+            PayloadProtocol::from_server(None).unwrap(),
+            PayloadProtocol::V1
+        );
+        assert_eq!(
+            PayloadProtocol::from_server(Some("1")).unwrap(),
+            PayloadProtocol::V1
+        );
+        assert!(matches!(
+            PayloadProtocol::from_server(Some("2")),
+            Err(GeekedError::UnsupportedProtocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_protocol_fields_override() {
+        let defaults = ProtocolFields::default();
+
+        // Partial overrides keep the other defaults; cached constants that
+        // still carry a payload_protocol override load as before
+        let fields: ProtocolFields =
+            serde_json::from_str(r#"{"ep": "124", "payload_protocol": "1"}"#).unwrap();
+        assert_eq!(fields.ep, "124");
+        assert_eq!(fields.biht, defaults.biht);
+        assert_eq!(fields.pt, defaults.pt);
+    }
+
+    #[test]
+    fn test_load_response_payload_protocol_int() {
+        let json = r#"{
+            "lot_number": "abc",
+            "payload": "p",
+            "process_token": "t",
+            "pt": "1",
+            "payload_protocol": 2,
+            "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "now"}
+        }"#;
+        let data: LoadResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(data.payload_protocol.as_deref(), Some("2"));
+        assert!(matches!(
+            data.payload_protocol(),
+            Err(GeekedError::UnsupportedProtocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_continue_updates_round() {
+        let json = r#"{
+            "lot_number": "old",
+            "payload": "p",
+            "process_token": "t",
+            "pt": "1",
+            "bg": "old_bg.png",
+            "slice": "old_slice.png",
+            "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "then"}
+        }"#;
+        let mut data: LoadResponse = serde_json::from_str(json).unwrap();
+
+        let verify: VerifyResponse = serde_json::from_str(
+            r#"{"result": "continue", "lot_number": "new", "payload": "p2", "process_token": "t2"}"#,
+        )
+        .unwrap();
+        let VerifyOutcome::Continue(round) = VerifyOutcome::from(verify) else {
+            panic!("expected continue outcome");
+        };
+        assert!(!data.apply_continue(round));
+        assert_eq!(data.lot_number, "new");
+        assert_eq!(data.payload, "p2");
+        assert_eq!(data.bg.as_deref(), Some("old_bg.png"));
+
+        let verify: VerifyResponse = serde_json::from_str(
+            r#"{"result": "continue", "bg": "new_bg.png", "slice": "new_slice.png",
+                "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "now"}}"#,
+        )
+        .unwrap();
+        let VerifyOutcome::Continue(round) = VerifyOutcome::from(verify) else {
+            panic!("expected continue outcome");
+        };
+        assert!(data.apply_continue(round));
+        assert_eq!(data.bg.as_deref(), Some("new_bg.png"));
+        assert_eq!(data.pow_detail.as_ref().unwrap().datetime, "now");
+
+        let verify: VerifyResponse =
+            serde_json::from_str(r#"{"result": "continue", "captcha_type": "icon"}"#).unwrap();
+        let VerifyOutcome::Continue(round) = VerifyOutcome::from(verify) else {
+            panic!("expected continue outcome");
+        };
+        assert!(data.apply_continue(round));
+        assert_eq!(data.captcha_type, Some(RiskType::Icon));
+    }
+
+    #[test]
+    fn test_load_response_without_pow() {
+        let json = r#"{"lot_number": "abc", "payload": "p", "process_token": "t", "pt": "1"}"#;
+        let data: LoadResponse = serde_json::from_str(json).unwrap();
+        assert!(data.pow_detail.is_none());
+
+        let json = r#"{"lot_number": "abc", "payload": "p", "process_token": "t", "pt": "1",
+            "pow_detail": {"hashfunc": "", "bits": 0}}"#;
+        let data: LoadResponse = serde_json::from_str(json).unwrap();
+        assert!(!data.pow_detail.unwrap().is_required());
+    }
+
+    #[test]
+    fn test_pow_server_time() {
+        let pow = PowDetail {
+            datetime: "2024-06-04T13:54:56.771880+08:00".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(pow.server_millis(), Some(1_717_480_496_771));
+        let pow = PowDetail {
+            datetime: "now".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(pow.server_millis(), None);
+    }
+
+    #[test]
+    fn test_verify_outcome_from_response() {
+        let success: VerifyResponse = serde_json::from_str(
+            r#"{"result": "success", "seccode": {"captcha_id": "c", "lot_number": "l",
+                "pass_token": "p", "gen_time": "1", "captcha_output": "o"}}"#,
+        )
+        .unwrap();
+        assert!(
+            matches!(VerifyOutcome::from(success), VerifyOutcome::Success(s) if s.pass_token == "p")
+        );
+
+        let fail: VerifyResponse =
+            serde_json::from_str(r#"{"result": "fail", "code": 41}"#).unwrap();
+        match VerifyOutcome::from(fail) {
+            VerifyOutcome::Fail { reason, code } => {
+                assert_eq!(reason, "fail");
+                assert_eq!(code.as_deref(), Some("41"));
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        let retry: VerifyResponse = serde_json::from_str(r#"{"result": "retry"}"#).unwrap();
+        assert!(matches!(VerifyOutcome::from(retry), VerifyOutcome::Retry));
+    }
+
+    #[test]
+    fn test_seccode_submission_helpers() {
+        let seccode = SecCode {
+            captcha_id: "id".into(),
+            lot_number: "lot".into(),
+            pass_token: "token".into(),
+            gen_time: "123".into(),
+            captcha_output: "a+b/c=".into(),
+            solved_at: chrono::Utc::now(),
+        };
+
+        assert_eq!(
+            seccode.to_query_string(),
+            "lot_number=lot&captcha_output=a%2Bb%2Fc%3D&pass_token=token&gen_time=123"
+        );
+
+        let body = seccode.to_json_body();
+        assert_eq!(body["captcha_output"], "a+b/c=");
+        assert!(body.get("captcha_id").is_none());
+    }
+
+    #[test]
+    fn test_seccode_expiry() {
+        let json = r#"{"captcha_id": "id", "lot_number": "lot", "pass_token": "p",
+                       "gen_time": "1", "captcha_output": "o"}"#;
+        // Stored without a solve time: too old to trust
+        let stored: SecCode = serde_json::from_str(json).unwrap();
+        assert!(stored.is_expired(std::time::Duration::from_secs(3600)));
+
+        let verify: VerifyResponse =
+            serde_json::from_str(&format!(r#"{{"result": "success", "seccode": {}}}"#, json))
+                .unwrap();
+        let VerifyOutcome::Success(mut seccode) = verify.into() else {
+            panic!("expected a seccode");
+        };
+        assert!(!seccode.is_expired(SecCode::MAX_AGE));
+        assert!(seccode.expires_at() > chrono::Utc::now());
+
+        seccode.solved_at -= chrono::Duration::seconds(90);
+        assert!(seccode.is_expired(SecCode::MAX_AGE));
+        assert!(!seccode.is_expired(std::time::Duration::from_secs(120)));
+
+        let round_trip: SecCode =
+            serde_json::from_str(&serde_json::to_string(&seccode).unwrap()).unwrap();
+        assert_eq!(round_trip.solved_at, seccode.solved_at);
+    }
+
+    #[test]
+    fn test_redacted_masks_tokens() {
+        let json = r#"{
+            "lot_number": "lot",
+            "payload": "secret_payload",
+            "process_token": "abc",
+            "pt": "1",
+            "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "now"}
+        }"#;
+        let data: LoadResponse = serde_json::from_str(json).unwrap();
+        let redacted = data.redacted();
+        assert_eq!(redacted.payload, "secr***");
+        assert_eq!(redacted.process_token, "***");
+        assert_eq!(redacted.lot_number, "lot");
+
+        // Redacted sessions serialize like the real thing
+        let value = serde_json::to_value(&redacted).unwrap();
+        assert_eq!(value["pow_detail"]["hashfunc"], "md5");
+        let roundtrip: LoadResponse = serde_json::from_value(value).unwrap();
+        assert_eq!(roundtrip.payload, "secr***");
+    }
+
+    #[test]
+    fn test_risk_type_parsing() {
+        assert_eq!("Slide".parse::<RiskType>().unwrap(), RiskType::Slide);
+        assert_eq!("invisible".parse::<RiskType>().unwrap(), RiskType::Ai);
+        assert!("slid".parse::<RiskType>().is_err());
+
+        let known: RiskType = serde_json::from_str(r#""gobang""#).unwrap();
+        assert_eq!(known, RiskType::Gobang);
+        let unknown: RiskType = serde_json::from_str(r#""winlinze""#).unwrap();
+        assert_eq!(unknown, RiskType::Unknown("winlinze".into()));
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), r#""winlinze""#);
+    }
+
+    #[test]
+    fn test_captcha_session_metadata() {
+        let json = r#"{
+            "lot_number": "lot",
+            "payload": "p",
+            "process_token": "t",
+            "pt": "1",
+            "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "now"},
+            "captcha_type": "slide",
+            "static_path": "/v4/static/v1.9.3-26b399",
+            "feedback": "https://www.geetest.com/Helper",
+            "width": "300px",
+            "height": 200
+        }"#;
+        let session = CaptchaSession::new(serde_json::from_str(json).unwrap());
+
+        assert_eq!(session.captcha_type(), Some(&RiskType::Slide));
+        assert_eq!(session.static_path(), Some("/v4/static/v1.9.3-26b399"));
+        assert_eq!(
+            session.feedback_url(),
+            Some("https://www.geetest.com/Helper")
+        );
+        assert_eq!(session.gct_path(), None);
+        assert_eq!(session.dimensions(), Some((300, 200)));
+    }
+}
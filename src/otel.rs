@@ -0,0 +1,198 @@
+//! OpenTelemetry export of solve traces and metrics over OTLP/gRPC.
+//!
+//! The solve spans from [`Geeked::solve`](crate::Geeked::solve) (load,
+//! download, solve_captcha, pow, verify) become OTLP traces, and each solve
+//! records `chaser_gt.solves` and `chaser_gt.solve.duration` metrics.
+//!
+//! ```ignore
+//! let _guard = chaser_gt::otel::init_subscriber(&OtelConfig::default())?;
+//! // ... solve; the guard flushes pending telemetry when dropped
+//! ```
+//!
+//! Call [`init`] or [`init_subscriber`] before the first solve: metric
+//! instruments are bound to the meter provider installed at that time.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{ExporterBuildError, MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SdkTracerProvider, Tracer};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::error::GeekedError;
+use crate::models::RiskType;
+
+/// Name used for the tracer, meter and default service.
+const INSTRUMENTATION_NAME: &str = "chaser-gt";
+
+/// Exporter settings.
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    /// OTLP/gRPC collector endpoint
+    pub endpoint: String,
+    /// `service.name` resource attribute
+    pub service_name: String,
+    /// How often metrics are pushed
+    pub metrics_interval: Duration,
+}
+
+impl Default for OtelConfig {
+    /// Reads `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_SERVICE_NAME`, falling
+    /// back to `http://localhost:4317` and `chaser-gt`.
+    fn default() -> Self {
+        Self {
+            endpoint: std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .unwrap_or_else(|_| "http://localhost:4317".to_string()),
+            service_name: std::env::var("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|_| INSTRUMENTATION_NAME.to_string()),
+            metrics_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Keeps the exporters alive; flushes and shuts them down when dropped.
+pub struct OtelGuard {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("Failed to shut down OTLP trace export: {}", e);
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            eprintln!("Failed to shut down OTLP metric export: {}", e);
+        }
+    }
+}
+
+/// Install the global meter provider and build a tracing layer exporting
+/// spans, for adding to an existing subscriber.
+pub fn init<S>(
+    config: &OtelConfig,
+) -> Result<(OtelGuard, OpenTelemetryLayer<S, Tracer>), ExporterBuildError>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let resource = Resource::builder()
+        .with_service_name(config.service_name.clone())
+        .build();
+
+    let span_exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_batch_exporter(span_exporter)
+        .with_resource(resource.clone())
+        .build();
+
+    let metric_exporter = MetricExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+    let reader = PeriodicReader::builder(metric_exporter)
+        .with_interval(config.metrics_interval)
+        .build();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(reader)
+        .with_resource(resource)
+        .build();
+
+    global::set_tracer_provider(tracer_provider.clone());
+    global::set_meter_provider(meter_provider.clone());
+
+    let layer =
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(INSTRUMENTATION_NAME));
+    Ok((
+        OtelGuard {
+            tracer_provider,
+            meter_provider,
+        },
+        layer,
+    ))
+}
+
+/// Install a global subscriber that logs to stderr (filtered by `RUST_LOG`,
+/// default `info`) and exports spans and metrics over OTLP.
+pub fn init_subscriber(config: &OtelConfig) -> Result<OtelGuard, ExporterBuildError> {
+    let (guard, layer) = init(config)?;
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(layer)
+        .init();
+    Ok(guard)
+}
+
+struct Instruments {
+    solves: Counter<u64>,
+    solve_duration: Histogram<f64>,
+}
+
+fn instruments() -> &'static Instruments {
+    static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter(INSTRUMENTATION_NAME);
+        Instruments {
+            solves: meter
+                .u64_counter("chaser_gt.solves")
+                .with_description("Finished solves")
+                .build(),
+            solve_duration: meter
+                .f64_histogram("chaser_gt.solve.duration")
+                .with_description("Solve duration")
+                .with_unit("s")
+                .build(),
+        }
+    })
+}
+
+/// Attributes describing a finished solve.
+fn solve_attributes(risk_type: &RiskType, error: Option<&GeekedError>) -> Vec<KeyValue> {
+    let mut attributes = vec![
+        KeyValue::new("risk_type", risk_type.as_str().to_string()),
+        KeyValue::new("outcome", if error.is_some() { "error" } else { "ok" }),
+    ];
+    if let Some(e) = error {
+        attributes.push(KeyValue::new("error_code", i64::from(e.error_code())));
+    }
+    attributes
+}
+
+/// Record a finished solve.
+pub(crate) fn record_solve(risk_type: &RiskType, error: Option<&GeekedError>, elapsed: Duration) {
+    let attributes = solve_attributes(risk_type, error);
+    let instruments = instruments();
+    instruments.solves.add(1, &attributes);
+    instruments
+        .solve_duration
+        .record(elapsed.as_secs_f64(), &attributes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_attributes() {
+        let ok = solve_attributes(&RiskType::Slide, None);
+        assert_eq!(ok.len(), 2);
+        assert_eq!(ok[1], KeyValue::new("outcome", "ok"));
+
+        let err = GeekedError::LowScore { score: 3 };
+        let failed = solve_attributes(&RiskType::Ai, Some(&err));
+        assert_eq!(failed[0], KeyValue::new("risk_type", "ai"));
+        assert_eq!(failed[2], KeyValue::new("error_code", 202_i64));
+    }
+}
@@ -0,0 +1,30 @@
+//! The types most programs need, in one import.
+//!
+//! ```ignore
+//! use chaser_gt::prelude::*;
+//! use std::sync::Arc;
+//!
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .proxy_pool(Arc::new(ProxyPool::from_file("proxies.toml")?))
+//!     .build()
+//!     .await?;
+//! let seccode: SecCode = solver.solve().await?;
+//! ```
+
+pub use crate::error::{mask_proxy_credentials, GeekedError, Result};
+pub use crate::models::{
+    CaptchaSession, ClientType, LoadResponse, ProtocolFields, RiskType, SecCode, VerifyOutcome,
+};
+pub use crate::sign::SolverResult;
+pub use crate::user_info::UserInfo;
+pub use crate::v3::V3SecCode;
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub use crate::blocking::BlockingGeeked;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::{
+    BodyLimits, CircuitBreaker, CircuitBreakerConfig, Geeked, GeekedBuilder, GeekedConfig,
+    GeekedV3, ImageCache, ImageCacheConfig, Profile, Profiles, ProxyOutcome, ProxyPool,
+    ProxyPoolConfig, ProxySpec, SessionBundle, SolveContext, SolveEvent, SolveOptions, SolvePhase,
+    TokenPool, TokenPoolConfig,
+};
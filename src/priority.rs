@@ -0,0 +1,203 @@
+//! Job priorities for the server and worker.
+//!
+//! Interactive solves (someone is waiting on the result) and batch solves
+//! (background work) share one concurrency limit, but each class may only
+//! hold its [`PriorityShares`] of the slots. With the default shares batch
+//! work can fill at most half of them, so a flood of it never starves
+//! interactive requests.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::GeekedError;
+
+/// How urgently a solve is needed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// A client is waiting on the result
+    #[default]
+    Interactive,
+    /// Background work that can wait for free capacity
+    Batch,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Batch => "batch",
+        }
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = GeekedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "interactive" => Ok(Priority::Interactive),
+            "batch" => Ok(Priority::Batch),
+            _ => Err(GeekedError::UnsupportedType(format!(
+                "Invalid priority '{}'. Valid values: interactive, batch",
+                s
+            ))),
+        }
+    }
+}
+
+/// Share of the concurrency limit each priority may use, from 0 to 1.
+///
+/// Shares may add up to more than 1; the overall limit still applies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriorityShares {
+    pub interactive: f64,
+    pub batch: f64,
+}
+
+impl Default for PriorityShares {
+    fn default() -> Self {
+        Self {
+            interactive: 1.0,
+            batch: 0.5,
+        }
+    }
+}
+
+impl PriorityShares {
+    /// Slots `priority` may hold out of `max_concurrency`; at least one.
+    pub fn slots(&self, priority: Priority, max_concurrency: usize) -> usize {
+        let share = match priority {
+            Priority::Interactive => self.interactive,
+            Priority::Batch => self.batch,
+        };
+        let slots = (share.clamp(0.0, 1.0) * max_concurrency as f64).ceil() as usize;
+        slots.clamp(1, max_concurrency.max(1))
+    }
+}
+
+/// Concurrency limit with a cap per priority.
+///
+/// [`resize`](Self::resize) swaps in new semaphores; slots already held keep
+/// counting against the old ones until they are released.
+pub(crate) struct PriorityLimiter {
+    slots: RwLock<Arc<Slots>>,
+}
+
+struct Slots {
+    total: Arc<Semaphore>,
+    interactive: Arc<Semaphore>,
+    batch: Arc<Semaphore>,
+}
+
+impl Slots {
+    fn new(max_concurrency: usize, shares: &PriorityShares) -> Self {
+        let max = max_concurrency.max(1);
+        Self {
+            total: Arc::new(Semaphore::new(max)),
+            interactive: Arc::new(Semaphore::new(shares.slots(Priority::Interactive, max))),
+            batch: Arc::new(Semaphore::new(shares.slots(Priority::Batch, max))),
+        }
+    }
+
+    fn class(&self, priority: Priority) -> &Arc<Semaphore> {
+        match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Batch => &self.batch,
+        }
+    }
+}
+
+/// A held solve slot, released when dropped.
+pub(crate) struct SlotPermit {
+    _class: OwnedSemaphorePermit,
+    _total: OwnedSemaphorePermit,
+}
+
+impl PriorityLimiter {
+    pub(crate) fn new(max_concurrency: usize, shares: &PriorityShares) -> Self {
+        Self {
+            slots: RwLock::new(Arc::new(Slots::new(max_concurrency, shares))),
+        }
+    }
+
+    /// Apply a new limit to slots acquired from now on.
+    pub(crate) fn resize(&self, max_concurrency: usize, shares: &PriorityShares) {
+        let slots = Arc::new(Slots::new(max_concurrency, shares));
+        let old = std::mem::replace(
+            &mut *self.slots.write().unwrap_or_else(PoisonError::into_inner),
+            slots,
+        );
+        // Waiters on the old semaphores fail and retry on the new ones
+        old.total.close();
+        old.interactive.close();
+        old.batch.close();
+    }
+
+    /// Wait for a slot within `priority`'s share.
+    pub(crate) async fn acquire(&self, priority: Priority) -> SlotPermit {
+        loop {
+            let slots = self
+                .slots
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
+            let Ok(class) = slots.class(priority).clone().acquire_owned().await else {
+                continue;
+            };
+            let Ok(total) = slots.total.clone().acquire_owned().await else {
+                continue;
+            };
+            return SlotPermit {
+                _class: class,
+                _total: total,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shares() {
+        let shares = PriorityShares::default();
+        assert_eq!(shares.slots(Priority::Interactive, 16), 16);
+        assert_eq!(shares.slots(Priority::Batch, 16), 8);
+        assert_eq!(shares.slots(Priority::Batch, 1), 1);
+        assert_eq!("Batch".parse::<Priority>().unwrap(), Priority::Batch);
+        assert!("urgent".parse::<Priority>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_cannot_take_every_slot() {
+        let limiter = PriorityLimiter::new(2, &PriorityShares::default());
+        let _batch = limiter.acquire(Priority::Batch).await;
+
+        // The second batch job waits, but an interactive one still gets in
+        let second = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            limiter.acquire(Priority::Batch),
+        );
+        assert!(second.await.is_err());
+        let _interactive = limiter.acquire(Priority::Interactive).await;
+    }
+
+    #[tokio::test]
+    async fn test_resize_keeps_held_slots() {
+        let limiter = PriorityLimiter::new(1, &PriorityShares::default());
+        let held = limiter.acquire(Priority::Interactive).await;
+        let waiting = limiter.acquire(Priority::Interactive);
+        tokio::pin!(waiting);
+        let timeout = std::time::Duration::from_millis(20);
+        assert!(tokio::time::timeout(timeout, &mut waiting).await.is_err());
+
+        // The waiter moves to the larger limit while the held slot stays valid
+        limiter.resize(2, &PriorityShares::default());
+        let _second = tokio::time::timeout(timeout, waiting).await.unwrap();
+        drop(held);
+    }
+}
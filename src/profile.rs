@@ -0,0 +1,207 @@
+//! Per-site solver profiles.
+//!
+//! A profile bundles everything a target site needs, so applications do not
+//! repeat it at every call site:
+//!
+//! ```toml
+//! [profiles.shuffle]
+//! captcha_id = "54088bb07d2df3c46b79f80300b0abbe"
+//! risk_type = "slide"
+//! lang = "eng"
+//! user_info = "account_id={account}"
+//! referer = "https://shuffle.com/"
+//! origin = "https://shuffle.com"
+//! client_type = "web"
+//! proxy_group = "us"
+//! min_score = 40
+//! low_score_retries = 2
+//! ```
+//!
+//! ```ignore
+//! let solver = Geeked::from_profile("shuffle")?
+//!     .user_info_var("account", "12345")
+//!     .build()
+//!     .await?;
+//! ```
+//!
+//! [`Geeked::from_profile`](crate::Geeked::from_profile) reads the file named
+//! by `CHASER_GT_PROFILES`, or `profiles.toml` in the platform config
+//! directory.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::client::GeekedBuilder;
+use crate::error::{GeekedError, Result};
+use crate::models::{ClientType, RiskType};
+
+/// Settings for one target site.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub captcha_id: String,
+    /// Captcha type; unknown names are rejected when the file is loaded
+    #[serde(deserialize_with = "known_risk_type")]
+    pub risk_type: RiskType,
+    /// Language requested from Geetest
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// `user_info`, with `{name}` placeholders filled by
+    /// [`GeekedBuilder::user_info_var`]
+    #[serde(default)]
+    pub user_info: Option<String>,
+    /// `Referer` header sent with every request
+    #[serde(default)]
+    pub referer: Option<String>,
+    /// `Origin` header sent with every request
+    #[serde(default)]
+    pub origin: Option<String>,
+    /// `web` or `h5`; see [`GeekedBuilder::client_type`]
+    #[serde(default)]
+    pub client_type: Option<ClientType>,
+    /// Pool proxies to use: those tagged with this `geo`
+    #[serde(default)]
+    pub proxy_group: Option<String>,
+    /// See [`GeekedBuilder::min_score`]
+    #[serde(default)]
+    pub min_score: Option<u32>,
+    /// See [`GeekedBuilder::low_score_retries`]
+    #[serde(default)]
+    pub low_score_retries: Option<u32>,
+}
+
+pub(crate) fn known_risk_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<RiskType, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(serde::de::Error::custom)
+}
+
+impl Profile {
+    /// Builder with every setting of the profile applied.
+    pub fn builder(&self) -> GeekedBuilder {
+        self.apply(GeekedBuilder::new(&self.captcha_id, self.risk_type.clone()))
+    }
+
+    /// Apply the profile's settings, except the captcha ID and type, to
+    /// `builder`.
+    pub fn apply(&self, mut builder: GeekedBuilder) -> GeekedBuilder {
+        if let Some(lang) = &self.lang {
+            builder = builder.lang(lang);
+        }
+        if let Some(user_info) = &self.user_info {
+            builder = builder.user_info(user_info);
+        }
+        if let Some(referer) = &self.referer {
+            builder = builder.referer(referer);
+        }
+        if let Some(origin) = &self.origin {
+            builder = builder.origin(origin);
+        }
+        if let Some(client_type) = self.client_type {
+            builder = builder.client_type(client_type);
+        }
+        if let Some(group) = &self.proxy_group {
+            builder = builder.proxy_geo(group);
+        }
+        if let Some(min_score) = self.min_score {
+            builder = builder.min_score(min_score);
+        }
+        if let Some(retries) = self.low_score_retries {
+            builder = builder.low_score_retries(retries);
+        }
+        builder
+    }
+}
+
+/// A set of named profiles, as loaded from a profiles file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Parse a TOML profiles file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)?;
+        Self::parse(&raw).map_err(|e| match e {
+            GeekedError::Config(message) => {
+                GeekedError::Config(format!("{}: {}", path.display(), message))
+            }
+            e => e,
+        })
+    }
+
+    /// Parse profiles from TOML.
+    pub fn parse(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| GeekedError::Config(e.to_string()))
+    }
+
+    /// Profiles file used by [`Geeked::from_profile`](crate::Geeked::from_profile):
+    /// `CHASER_GT_PROFILES`, or `profiles.toml` in the platform config
+    /// directory.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os("CHASER_GT_PROFILES") {
+            return path.into();
+        }
+        directories::ProjectDirs::from("com", "geeked", "chaser-gt")
+            .map(|dirs| dirs.config_dir().to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("profiles.toml")
+    }
+
+    /// Profile named `name`.
+    pub fn get(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| GeekedError::Config(format!("no profile named '{}'", name)))
+    }
+
+    /// Profile for `captcha_id`, if one is configured.
+    pub fn for_captcha_id(&self, captcha_id: &str) -> Option<&Profile> {
+        self.profiles.values().find(|p| p.captcha_id == captcha_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+        [profiles.shuffle]
+        captcha_id = "abc"
+        risk_type = "slide"
+        user_info = "account_id={account}"
+        referer = "https://shuffle.com/"
+        min_score = 40
+
+        [profiles.other]
+        captcha_id = "def"
+        risk_type = "invisible"
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let profiles = Profiles::parse(PROFILES).unwrap();
+        let shuffle = profiles.get("shuffle").unwrap();
+        assert_eq!(shuffle.risk_type, RiskType::Slide);
+        assert_eq!(shuffle.min_score, Some(40));
+        assert_eq!(
+            profiles.for_captcha_id("def").unwrap().risk_type,
+            RiskType::Ai
+        );
+        assert!(matches!(
+            profiles.get("missing"),
+            Err(GeekedError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_risk_type_is_rejected() {
+        let err = Profiles::parse("[profiles.x]\ncaptcha_id = \"a\"\nrisk_type = \"puzzle\"\n");
+        assert!(matches!(err, Err(GeekedError::Config(_))));
+    }
+}
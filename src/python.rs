@@ -0,0 +1,439 @@
+//! Python bindings for chaser-gt.
+//!
+//! Builds the native `chaser_gt` extension module. Build the wheel with
+//! [maturin](https://www.maturin.rs/), which enables the `python` feature
+//! (see `pyproject.toml`).
+//!
+//! # Example (Python)
+//!
+//! ```python
+//! import asyncio
+//! import chaser_gt
+//!
+//! solver = chaser_gt.Geeked("captcha_id", "slide", proxy="http://host:port")
+//! result = asyncio.run(solver.solve())
+//! print(result.pass_token)
+//!
+//! # Blocking convenience wrapper
+//! result = chaser_gt.solve("captcha_id", "slide")
+//! ```
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tokio::sync::OnceCell;
+
+use crate::error::GeekedError as Error;
+use crate::{Geeked, GeekedBuilder, RiskType, SecCode};
+
+create_exception!(
+    chaser_gt,
+    GeekedError,
+    PyException,
+    "Base class for chaser-gt errors. `error_code` holds the numeric code."
+);
+create_exception!(
+    chaser_gt,
+    NetworkError,
+    GeekedError,
+    "Request failed or the server answered with something unexpected."
+);
+create_exception!(
+    chaser_gt,
+    GeetestApiError,
+    GeekedError,
+    "Geetest rejected the request."
+);
+create_exception!(
+    chaser_gt,
+    VerificationFailedError,
+    GeekedError,
+    "Geetest rejected the solution."
+);
+create_exception!(
+    chaser_gt,
+    LowScoreError,
+    GeekedError,
+    "The solve was accepted with a score below `min_score`."
+);
+create_exception!(
+    chaser_gt,
+    UnsupportedTypeError,
+    GeekedError,
+    "Unknown or unsupported captcha type."
+);
+
+/// Convert a library error into the matching Python exception.
+fn to_py_err(err: Error) -> PyErr {
+    let code = err.error_code();
+    let message = err.to_string();
+    let py_err = match err {
+        Error::Http(_) | Error::UnexpectedResponse { .. } | Error::InvalidResponse(_) => {
+            NetworkError::new_err(message)
+        }
+        Error::Geetest { .. } => GeetestApiError::new_err(message),
+        Error::VerificationFailed { .. } => VerificationFailedError::new_err(message),
+        Error::LowScore { .. } => LowScoreError::new_err(message),
+        Error::UnsupportedType(_) => UnsupportedTypeError::new_err(message),
+        _ => GeekedError::new_err(message),
+    };
+
+    Python::with_gil(|py| {
+        let _ = py_err.value(py).setattr("error_code", code);
+    });
+    py_err
+}
+
+/// Solver options, validated when the Python object is created.
+#[derive(Clone)]
+struct Config {
+    captcha_id: String,
+    risk_type: RiskType,
+    proxy: Option<String>,
+    user_info: Option<String>,
+    timeout: Option<Duration>,
+    max_retries: Option<u32>,
+    min_score: Option<u32>,
+    local_address: Option<IpAddr>,
+    lang: Option<String>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Config {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        captcha_id: String,
+        risk_type: &str,
+        proxy: Option<String>,
+        user_info: Option<String>,
+        timeout: Option<f64>,
+        max_retries: Option<u32>,
+        min_score: Option<u32>,
+        local_address: Option<&str>,
+        lang: Option<String>,
+        cache_dir: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let risk_type = risk_type.parse::<RiskType>().map_err(to_py_err)?;
+        let timeout = timeout
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs)
+                    .map_err(|_| PyValueError::new_err(format!("Invalid timeout: {}", secs)))
+            })
+            .transpose()?;
+        let local_address = local_address
+            .map(|addr| {
+                addr.parse::<IpAddr>().map_err(|e| {
+                    PyValueError::new_err(format!("Invalid local_address '{}': {}", addr, e))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            captcha_id,
+            risk_type,
+            proxy,
+            user_info,
+            timeout,
+            max_retries,
+            min_score,
+            local_address,
+            lang,
+            cache_dir,
+        })
+    }
+
+    fn builder(&self) -> GeekedBuilder {
+        let mut builder = Geeked::builder(self.captcha_id.clone(), self.risk_type.clone());
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(user_info) = &self.user_info {
+            builder = builder.user_info(user_info.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max_retries) = self.max_retries {
+            builder = builder.max_retries(max_retries);
+        }
+        if let Some(min_score) = self.min_score {
+            builder = builder.min_score(min_score);
+        }
+        if let Some(addr) = self.local_address {
+            builder = builder.local_address(addr);
+        }
+        if let Some(lang) = &self.lang {
+            builder = builder.lang(lang.clone());
+        }
+        if let Some(dir) = &self.cache_dir {
+            builder = builder.cache_dir(dir.clone());
+        }
+        builder
+    }
+}
+
+/// Solved captcha, mirroring [`SecCode`].
+#[pyclass(name = "SecCode", module = "chaser_gt", frozen, get_all)]
+#[derive(Clone)]
+pub struct PySecCode {
+    captcha_id: String,
+    lot_number: String,
+    pass_token: String,
+    gen_time: String,
+    captcha_output: String,
+}
+
+impl From<SecCode> for PySecCode {
+    fn from(code: SecCode) -> Self {
+        Self {
+            captcha_id: code.captcha_id,
+            lot_number: code.lot_number,
+            pass_token: code.pass_token,
+            gen_time: code.gen_time,
+            captcha_output: code.captcha_output,
+        }
+    }
+}
+
+#[pymethods]
+impl PySecCode {
+    /// The fields as a dict, ready to post to the site's backend.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("captcha_id", &self.captcha_id)?;
+        dict.set_item("lot_number", &self.lot_number)?;
+        dict.set_item("pass_token", &self.pass_token)?;
+        dict.set_item("gen_time", &self.gen_time)?;
+        dict.set_item("captcha_output", &self.captcha_output)?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SecCode(captcha_id='{}', lot_number='{}')",
+            self.captcha_id, self.lot_number
+        )
+    }
+}
+
+/// Reusable solver.
+///
+/// The underlying client is built on the first solve and kept for later
+/// ones, so connections and constants are reused.
+#[pyclass(name = "Geeked", module = "chaser_gt", frozen)]
+pub struct PyGeeked {
+    config: Config,
+    solver: Arc<OnceCell<Geeked>>,
+}
+
+impl PyGeeked {
+    /// Build the solver if needed and run one solve.
+    async fn run(config: Config, solver: Arc<OnceCell<Geeked>>) -> PyResult<PySecCode> {
+        let solver = solver
+            .get_or_try_init(|| config.builder().build())
+            .await
+            .map_err(to_py_err)?;
+        solver.solve().await.map(Into::into).map_err(to_py_err)
+    }
+}
+
+#[pymethods]
+impl PyGeeked {
+    #[new]
+    #[pyo3(signature = (
+        captcha_id,
+        risk_type,
+        *,
+        proxy = None,
+        user_info = None,
+        timeout = None,
+        max_retries = None,
+        min_score = None,
+        local_address = None,
+        lang = None,
+        cache_dir = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        captcha_id: String,
+        risk_type: &str,
+        proxy: Option<String>,
+        user_info: Option<String>,
+        timeout: Option<f64>,
+        max_retries: Option<u32>,
+        min_score: Option<u32>,
+        local_address: Option<&str>,
+        lang: Option<String>,
+        cache_dir: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let config = Config::new(
+            captcha_id,
+            risk_type,
+            proxy,
+            user_info,
+            timeout,
+            max_retries,
+            min_score,
+            local_address,
+            lang,
+            cache_dir,
+        )?;
+        Ok(Self {
+            config,
+            solver: Arc::new(OnceCell::new()),
+        })
+    }
+
+    /// Solve a captcha. Returns an awaitable resolving to a `SecCode`.
+    fn solve<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let config = self.config.clone();
+        let solver = self.solver.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, Self::run(config, solver))
+    }
+
+    /// Solve a captcha, blocking the calling thread (the GIL is released).
+    fn solve_blocking(&self, py: Python<'_>) -> PyResult<PySecCode> {
+        let config = self.config.clone();
+        let solver = self.solver.clone();
+        py.allow_threads(|| {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(Self::run(config, solver))
+        })
+    }
+
+    #[getter]
+    fn captcha_id(&self) -> &str {
+        &self.config.captcha_id
+    }
+
+    #[getter]
+    fn risk_type(&self) -> &str {
+        self.config.risk_type.as_str()
+    }
+}
+
+/// Solve a captcha once, blocking the calling thread.
+#[pyfunction]
+#[pyo3(signature = (captcha_id, risk_type, *, proxy = None, user_info = None, timeout = None))]
+fn solve(
+    py: Python<'_>,
+    captcha_id: String,
+    risk_type: &str,
+    proxy: Option<String>,
+    user_info: Option<String>,
+    timeout: Option<f64>,
+) -> PyResult<PySecCode> {
+    let config = Config::new(
+        captcha_id, risk_type, proxy, user_info, timeout, None, None, None, None, None,
+    )?;
+    py.allow_threads(|| {
+        pyo3_async_runtimes::tokio::get_runtime()
+            .block_on(PyGeeked::run(config, Arc::new(OnceCell::new())))
+    })
+}
+
+/// Solve a captcha once. Returns an awaitable resolving to a `SecCode`.
+#[pyfunction]
+#[pyo3(signature = (captcha_id, risk_type, *, proxy = None, user_info = None, timeout = None))]
+fn solve_async<'py>(
+    py: Python<'py>,
+    captcha_id: String,
+    risk_type: &str,
+    proxy: Option<String>,
+    user_info: Option<String>,
+    timeout: Option<f64>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let config = Config::new(
+        captcha_id, risk_type, proxy, user_info, timeout, None, None, None, None, None,
+    )?;
+    pyo3_async_runtimes::tokio::future_into_py(py, PyGeeked::run(config, Arc::new(OnceCell::new())))
+}
+
+#[pymodule]
+fn chaser_gt(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyGeeked>()?;
+    m.add_class::<PySecCode>()?;
+    m.add_function(wrap_pyfunction!(solve, m)?)?;
+    m.add_function(wrap_pyfunction!(solve_async, m)?)?;
+
+    m.add("GeekedError", py.get_type::<GeekedError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("GeetestApiError", py.get_type::<GeetestApiError>())?;
+    m.add(
+        "VerificationFailedError",
+        py.get_type::<VerificationFailedError>(),
+    )?;
+    m.add("LowScoreError", py.get_type::<LowScoreError>())?;
+    m.add(
+        "UnsupportedTypeError",
+        py.get_type::<UnsupportedTypeError>(),
+    )?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_map_to_exception_classes() {
+        pyo3::prepare_freethreaded_python();
+
+        let err = to_py_err(Error::LowScore { score: 3 });
+        Python::with_gil(|py| {
+            assert!(err.is_instance_of::<LowScoreError>(py));
+            assert!(err.is_instance_of::<GeekedError>(py));
+            let code: i32 = err
+                .value(py)
+                .getattr("error_code")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(code, 202);
+        });
+    }
+
+    #[test]
+    fn test_invalid_options_raise() {
+        pyo3::prepare_freethreaded_python();
+
+        let err = Config::new(
+            "id".into(),
+            "puzzle",
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        Python::with_gil(|py| assert!(err.is_instance_of::<UnsupportedTypeError>(py)));
+
+        let err = Config::new(
+            "id".into(),
+            "slide",
+            None,
+            None,
+            Some(-1.0),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyValueError>(py)));
+    }
+}
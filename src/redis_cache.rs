@@ -0,0 +1,163 @@
+//! Constants shared between instances through Redis.
+//!
+//! A fleet of servers or workers would otherwise each deobfuscate the new
+//! Geetest script after an update. With a [`RedisCache`] attached to the
+//! [`Deobfuscator`], every instance still checks the live version, but only
+//! the one that takes the refresh lock fetches and deobfuscates the script;
+//! the others wait for its result to appear in Redis.
+//!
+//! ```ignore
+//! let deobfuscator = Deobfuscator::new()
+//!     .with_redis_cache(RedisCache::connect("redis://127.0.0.1/").await?);
+//! let constants = deobfuscator.get_constants().await?;
+//! ```
+//!
+//! If Redis is unreachable the deobfuscator falls back to its local cache.
+
+use std::time::{Duration, Instant};
+
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use crate::deobfuscate::Deobfuscator;
+use crate::error::{GeekedError, Result};
+use crate::models::{CachedConstants, Constants};
+
+/// How long an instance may hold the refresh lock.
+const LOCK_TTL: Duration = Duration::from_secs(60);
+
+/// Pause between checks while another instance refreshes.
+const LOCK_POLL: Duration = Duration::from_millis(500);
+
+/// Deletes the lock only if it still holds our token.
+const UNLOCK_SCRIPT: &str = r#"
+    if redis.call("GET", KEYS[1]) == ARGV[1] then
+        return redis.call("DEL", KEYS[1])
+    end
+    return 0
+"#;
+
+fn cache_error(err: redis::RedisError) -> GeekedError {
+    GeekedError::Cache(format!("Redis: {}", err))
+}
+
+/// Redis-backed constants cache with a refresh lock.
+#[derive(Clone)]
+pub struct RedisCache {
+    redis: ConnectionManager,
+    key: String,
+}
+
+impl RedisCache {
+    /// Connect to Redis at `url`; constants are stored under
+    /// `chaser-gt:constants`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(cache_error)?;
+        Ok(Self {
+            redis: client.get_connection_manager().await.map_err(cache_error)?,
+            key: "chaser-gt:constants".to_string(),
+        })
+    }
+
+    /// Store the constants under `key` (and lock `<key>:lock`) instead.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    fn lock_key(&self) -> String {
+        format!("{}:lock", self.key)
+    }
+
+    /// Constants currently in Redis, if any.
+    pub async fn load(&self) -> Result<Option<CachedConstants>> {
+        let raw: Option<String> = self
+            .redis
+            .clone()
+            .get(&self.key)
+            .await
+            .map_err(cache_error)?;
+        raw.map(|raw| serde_json::from_str(&raw))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn store(&self, constants: &CachedConstants) -> Result<()> {
+        let raw = serde_json::to_string(constants)?;
+        let _: () = self
+            .redis
+            .clone()
+            .set(&self.key, raw)
+            .await
+            .map_err(cache_error)?;
+        Ok(())
+    }
+
+    /// Take the refresh lock, returning its token, or `None` if another
+    /// instance holds it.
+    async fn try_lock(&self) -> Result<Option<String>> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(self.lock_key())
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOCK_TTL.as_millis() as u64)
+            .query_async(&mut self.redis.clone())
+            .await
+            .map_err(cache_error)?;
+        Ok(acquired.map(|_| token))
+    }
+
+    async fn unlock(&self, token: &str) {
+        let released: redis::RedisResult<i64> = redis::cmd("EVAL")
+            .arg(UNLOCK_SCRIPT)
+            .arg(1)
+            .arg(self.lock_key())
+            .arg(token)
+            .query_async(&mut self.redis.clone())
+            .await;
+        if let Err(e) = released {
+            // Expires on its own after LOCK_TTL
+            tracing::warn!("Failed to release the constants refresh lock: {}", e);
+        }
+    }
+
+    /// Constants for the live script version, refreshed by at most one
+    /// instance at a time.
+    pub(crate) async fn get_constants(&self, deobfuscator: &Deobfuscator) -> Result<Constants> {
+        let live = deobfuscator.current_version().await?;
+        let deadline = Instant::now() + LOCK_TTL;
+
+        loop {
+            if let Some(shared) = self.load().await? {
+                if shared.version == live {
+                    tracing::debug!("Using shared constants (version: {})", shared.version);
+                    deobfuscator.save_local(&shared);
+                    return Ok(shared.into());
+                }
+            }
+
+            if let Some(token) = self.try_lock().await? {
+                tracing::info!("Refreshing shared constants for version {}", live);
+                let fetched = deobfuscator.fetch_and_deobfuscate().await;
+                if let Ok(constants) = &fetched {
+                    if let Err(e) = self.store(constants).await {
+                        tracing::warn!("Failed to share refreshed constants: {}", e);
+                    }
+                    deobfuscator.save_local(constants);
+                }
+                self.unlock(&token).await;
+                return fetched.map(Into::into);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(GeekedError::Cache(format!(
+                    "timed out waiting for another instance to refresh constants for {}",
+                    live
+                )));
+            }
+            tokio::time::sleep(LOCK_POLL).await;
+        }
+    }
+}
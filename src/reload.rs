@@ -0,0 +1,155 @@
+//! Configuration hot-reload for the server and worker.
+//!
+//! Settings that are safe to change at runtime can live in a TOML file
+//! (`CHASER_GT_CONFIG`) instead of the environment:
+//!
+//! ```toml
+//! max_concurrency = 32
+//! interactive_share = 1.0
+//! batch_share = 0.5
+//! ```
+//!
+//! The file, and the server's API key, profiles and proxy pool files, are
+//! re-read on SIGHUP or when they change. New limits apply to solves started
+//! afterwards; running solves are not interrupted. A file that fails to
+//! parse is logged and the previous settings stay in effect.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+use crate::error::{GeekedError, Result};
+use crate::priority::PriorityShares;
+
+/// How often watched files are checked for changes.
+pub(crate) const FILE_POLL: Duration = Duration::from_secs(5);
+
+/// Settings from the runtime config file; unset ones keep their value.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Maximum number of solves running at once
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// Share of the limit interactive solves may use
+    #[serde(default)]
+    pub interactive_share: Option<f64>,
+    /// Share of the limit batch solves may use
+    #[serde(default)]
+    pub batch_share: Option<f64>,
+}
+
+impl RuntimeConfig {
+    /// Parse a TOML runtime config file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)?;
+        toml::from_str(&raw).map_err(|e| GeekedError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Override the settings the file sets.
+    pub fn apply(&self, max_concurrency: &mut usize, shares: &mut PriorityShares) {
+        if let Some(max) = self.max_concurrency {
+            *max_concurrency = max;
+        }
+        if let Some(share) = self.interactive_share {
+            shares.interactive = share;
+        }
+        if let Some(share) = self.batch_share {
+            shares.batch = share;
+        }
+    }
+}
+
+/// Call `reload` on SIGHUP, or when one of `files` changes.
+pub(crate) fn spawn(
+    files: Vec<PathBuf>,
+    interval: Duration,
+    reload: impl Fn() + Send + 'static,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let modified = |path: &PathBuf| -> Option<SystemTime> {
+            std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        };
+        let mut last: Vec<_> = files.iter().map(modified).collect();
+
+        #[cfg(unix)]
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(hangup) => Some(hangup),
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGHUP: {}", e);
+                None
+            }
+        };
+
+        loop {
+            #[cfg(unix)]
+            let hangup_received = async {
+                match hangup.as_mut() {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup_received = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = hangup_received => {
+                    tracing::info!("SIGHUP received, reloading configuration");
+                }
+                _ = tokio::time::sleep(interval) => {
+                    let current: Vec<_> = files.iter().map(modified).collect();
+                    if current == last {
+                        continue;
+                    }
+                    tracing::info!("Configuration files changed, reloading");
+                }
+            }
+            last = files.iter().map(modified).collect();
+            reload();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_runtime_config() {
+        let config: RuntimeConfig =
+            toml::from_str("max_concurrency = 4\nbatch_share = 0.25").unwrap();
+        let mut max = 16;
+        let mut shares = PriorityShares::default();
+        config.apply(&mut max, &mut shares);
+        assert_eq!(max, 4);
+        assert_eq!(shares.batch, 0.25);
+        assert_eq!(shares.interactive, 1.0);
+
+        assert!(toml::from_str::<RuntimeConfig>("bind = \"0.0.0.0:1\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_change() {
+        let path =
+            std::env::temp_dir().join(format!("chaser-gt-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "max_concurrency = 4").unwrap();
+
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let counter = reloads.clone();
+        let watcher = spawn(vec![path.clone()], Duration::from_millis(10), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 0);
+
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+        watcher.abort();
+    }
+}
@@ -0,0 +1,266 @@
+//! Offline replay of captured solves.
+//!
+//! Runs the solver and `w` payload generation on a recorded challenge
+//! without touching the network, so a change in Geetest's script or in the
+//! solvers shows up as a diff against a known-good payload:
+//!
+//! ```ignore
+//! let replay = Replay::from_har_file("fixtures/slide.har")?;
+//! let output = replay.run(constants).await?;
+//! assert_eq!(output.mismatches(&expected), Vec::<String>::new());
+//! ```
+//!
+//! A replay comes from a HAR file written by
+//! [`GeekedBuilder::record_har`](crate::GeekedBuilder::record_har), or from
+//! a saved [`LoadResponse`] plus its images. The expected payload only needs
+//! the fields that matter; leave out the random ones (`pow_msg`,
+//! `passtime`, ...) or make them reproducible with the `test-util` feature.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::client::Geeked;
+use crate::error::{GeekedError, Result};
+use crate::models::{ClientType, Constants, GeetestResponse, LoadResponse, RiskType};
+use crate::sign::SolverResult;
+use crate::transport::MockTransport;
+
+const LOAD_URL: &str = "https://gcaptcha4.geetest.com/load";
+const STATIC_URL: &str = "https://static.geetest.com/";
+
+/// A recorded challenge to solve offline.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    captcha_id: String,
+    risk_type: RiskType,
+    client_type: ClientType,
+    load: LoadResponse,
+    /// Images by path on the static server
+    images: Vec<(String, Vec<u8>)>,
+}
+
+/// What a replay produced.
+#[derive(Debug, Clone)]
+pub struct ReplayOutput {
+    pub solver_result: SolverResult,
+    /// Plaintext `w` payload
+    pub payload: Value,
+}
+
+impl Replay {
+    /// Replay `load`, a saved `/load` response.
+    pub fn new(captcha_id: impl Into<String>, risk_type: RiskType, load: LoadResponse) -> Self {
+        Self {
+            captcha_id: captcha_id.into(),
+            risk_type,
+            client_type: ClientType::Web,
+            load,
+            images: Vec::new(),
+        }
+    }
+
+    /// Serve `image` for `path`, as named in the load response (e.g. its `bg`).
+    pub fn image(mut self, path: impl Into<String>, image: impl Into<Vec<u8>>) -> Self {
+        self.images.push((path.into(), image.into()));
+        self
+    }
+
+    /// Replay as the given client type.
+    pub fn client_type(mut self, client_type: ClientType) -> Self {
+        self.client_type = client_type;
+        self
+    }
+
+    /// Replay the first `/load` of a HAR file and the images it downloaded.
+    pub fn from_har_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_har(&serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Replay the first `/load` of a HAR document.
+    pub fn from_har(har: &Value) -> Result<Self> {
+        let entries = har["log"]["entries"]
+            .as_array()
+            .ok_or_else(|| invalid("no log.entries"))?;
+        let load = entries
+            .iter()
+            .find(|entry| url(entry).starts_with(LOAD_URL))
+            .ok_or_else(|| invalid("no /load request"))?;
+        let param = |name: &str| {
+            load["request"]["queryString"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|param| param["name"] == name)
+                .and_then(|param| param["value"].as_str())
+                .ok_or_else(|| invalid(&format!("/load without {}", name)))
+        };
+
+        let text = String::from_utf8_lossy(&body(load)?).into_owned();
+        let data: LoadResponse = GeetestResponse::parse_jsonp(&text, param("callback")?, false)?;
+        let client_type = match param("client_type") {
+            Ok("h5") => ClientType::H5,
+            _ => ClientType::Web,
+        };
+        let mut replay = Self::new(param("captcha_id")?, param("risk_type")?.parse()?, data)
+            .client_type(client_type);
+        for entry in entries {
+            if let Some(path) = url(entry).strip_prefix(STATIC_URL) {
+                replay = replay.image(path, body(entry)?);
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Solve the challenge and build the `w` payload with `constants`.
+    pub async fn run(&self, constants: Arc<Constants>) -> Result<ReplayOutput> {
+        // Unknown URLs fail, so nothing leaves the machine
+        let transport =
+            self.images
+                .iter()
+                .fold(MockTransport::new(), |transport, (path, image)| {
+                    transport.fixture(format!("{}{}", STATIC_URL, path), 200, image.clone())
+                });
+        let solver = Geeked::builder(self.captcha_id.clone(), self.risk_type.clone())
+            .client_type(self.client_type)
+            .constants(constants)
+            .transport(Arc::new(transport))
+            .build()
+            .await?;
+        let (solver_result, payload) = solver.solve_payload(&self.load).await?;
+        Ok(ReplayOutput {
+            solver_result,
+            payload,
+        })
+    }
+}
+
+impl ReplayOutput {
+    /// Fields of `expected` the payload lacks or has a different value for,
+    /// e.g. `userresponse: expected [[1,2]], got [[1,3]]`. Nested objects
+    /// are compared field by field; other fields of the payload are ignored.
+    pub fn mismatches(&self, expected: &Value) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        compare("", expected, &self.payload, &mut mismatches);
+        mismatches
+    }
+}
+
+fn compare(path: &str, expected: &Value, actual: &Value, mismatches: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", path, key),
+                };
+                match actual.get(key) {
+                    Some(actual) => compare(&path, expected, actual, mismatches),
+                    None => mismatches.push(format!("{}: expected {}, missing", path, expected)),
+                }
+            }
+        }
+        _ if expected != actual => {
+            mismatches.push(format!("{}: expected {}, got {}", path, expected, actual))
+        }
+        _ => {}
+    }
+}
+
+fn url(entry: &Value) -> &str {
+    entry["request"]["url"].as_str().unwrap_or_default()
+}
+
+/// Response body of a HAR entry.
+fn body(entry: &Value) -> Result<Vec<u8>> {
+    use base64::Engine;
+
+    let content = &entry["response"]["content"];
+    let text = content["text"]
+        .as_str()
+        .ok_or_else(|| invalid(&format!("no response body for {}", url(entry))))?;
+    match content["encoding"].as_str() {
+        Some("base64") => base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(|e| invalid(&format!("bad base64 body for {}: {}", url(entry), e))),
+        _ => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+fn invalid(problem: &str) -> GeekedError {
+    GeekedError::InvalidResponse(format!("Unusable HAR: {}", problem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::har::HarRecorder;
+    use crate::transport::{Transport, TransportRequest};
+    use serde_json::json;
+
+    const LOAD: &str = r#"geetest_1({"status": "success", "data": {
+        "lot_number": "f4744c44df4541b3be48c5c270ced20b", "payload": "p",
+        "process_token": "t", "pt": "0", "captcha_type": "gobang",
+        "ques": [[1, 1, 1, 0, 1], [2, 2, 2, 2, 1], [3, 3, 3, 3, 3],
+                 [4, 4, 4, 4, 4], [5, 5, 5, 5, 5]]}})"#;
+
+    fn constants() -> Arc<Constants> {
+        Arc::new(Constants {
+            mapping: r#"{"(n[13:15]+n[3:5])+.+(n[1:3])":"n[13:18]"}"#.to_string(),
+            abo: [("ab".to_string(), "cd".to_string())].into(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_har() {
+        let mock = MockTransport::new().fixture(LOAD_URL, 200, LOAD);
+        let recorder = HarRecorder::new(Arc::new(mock));
+        recorder
+            .send(TransportRequest::get(LOAD_URL).query(&[
+                ("captcha_id", "abc"),
+                ("risk_type", "gobang"),
+                ("client_type", "web"),
+                ("callback", "geetest_2"),
+            ]))
+            .await
+            .unwrap();
+
+        let replay = Replay::from_har(&recorder.to_har(false)).unwrap();
+        let output = replay.run(constants()).await.unwrap();
+        assert!(matches!(output.solver_result, SolverResult::Gobang { .. }));
+
+        let expected = json!({
+            "lot_number": "f4744c44df4541b3be48c5c270ced20b",
+            "ab": "cd",
+            "1b344c": {"474": "1b3be4"},
+            "userresponse": [[1, 4], [0, 3]],
+        });
+        assert_eq!(output.mismatches(&expected), Vec::<String>::new());
+
+        let wrong = json!({"ab": "xx", "1b344c": {"475": "1b3be4"}});
+        assert_eq!(
+            output.mismatches(&wrong),
+            vec![
+                r#"1b344c.475: expected "1b3be4", missing"#.to_string(),
+                r#"ab: expected "xx", got "cd""#.to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_needs_images() {
+        let load: LoadResponse = serde_json::from_value(json!({
+            "lot_number": "l", "payload": "p", "process_token": "t", "pt": "0",
+            "slice": "slice.png", "bg": "bg.png"
+        }))
+        .unwrap();
+        let replay = Replay::new("abc", RiskType::Slide, load);
+        assert!(matches!(
+            replay.run(constants()).await,
+            Err(GeekedError::UnexpectedResponse { status: 404, .. })
+        ));
+        assert!(Replay::from_har(&json!({"log": {"entries": []}})).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Admin endpoints under `/admin`, mounted when an admin key is configured.
+//!
+//! Every request must carry the key as `Authorization: Bearer <key>` or
+//! `X-Api-Key`; client API keys are not accepted.
+//!
+//! ```text
+//! GET  /admin/constants                 current constants and script version
+//! POST /admin/constants/refresh         deobfuscate the live script now
+//! POST /admin/token-pool/flush          discard pooled tokens
+//! GET  /admin/proxies                   proxy pool health
+//! POST /admin/proxies/quarantine        {"proxy": "...", "seconds": 600}
+//! POST /admin/proxies/unquarantine      {"proxy": "..."}
+//! PUT  /admin/log-level                 {"filter": "chaser_gt=debug,info"}
+//! ```
+
+use std::time::Duration;
+
+use axum::extract::{Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::auth::header_key;
+use super::{ApiError, AppState};
+use crate::error::GeekedError;
+
+/// Quarantine applied when the request does not name a duration.
+const DEFAULT_QUARANTINE: Duration = Duration::from_secs(600);
+
+/// Routes mounted under `/admin`.
+pub(crate) fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/constants", get(constants))
+        .route("/constants/refresh", post(refresh_constants))
+        .route("/token-pool/flush", post(flush_token_pool))
+        .route("/proxies", get(proxies))
+        .route("/proxies/quarantine", post(quarantine))
+        .route("/proxies/unquarantine", post(unquarantine))
+        .route("/log-level", put(log_level))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+async fn require_admin(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authorized = match (&state.admin_key, header_key(&headers)) {
+        (Some(expected), Some(key)) => constant_time_eq(expected.as_bytes(), key.as_bytes()),
+        _ => false,
+    };
+    if !authorized {
+        return Err(GeekedError::Unauthorized("missing or wrong admin key".into()).into());
+    }
+    Ok(next.run(request).await)
+}
+
+/// Compare without leaking the length of the matching prefix.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn unavailable(what: &str) -> ApiError {
+    ApiError::not_found(format!("this server has no {}", what))
+}
+
+/// `GET /admin/constants`
+async fn constants(State(state): State<AppState>) -> Json<Value> {
+    let version = state
+        .deobfuscator
+        .as_ref()
+        .and_then(|d| d.cached_constants().ok().flatten())
+        .map(|cached| cached.version);
+    Json(json!({
+        "version": version,
+        "constants": state.constants().redacted(),
+    }))
+}
+
+/// `POST /admin/constants/refresh`
+async fn refresh_constants(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let deobfuscator = state
+        .deobfuscator
+        .clone()
+        .ok_or_else(|| unavailable("deobfuscator"))?;
+    let cached = deobfuscator.refresh().await?;
+    let version = cached.version.clone();
+    state.set_constants(cached.into());
+    tracing::info!(
+        "Constants refreshed by admin request (version: {})",
+        version
+    );
+    Ok(Json(json!({ "version": version })))
+}
+
+/// `POST /admin/token-pool/flush`
+async fn flush_token_pool(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let pool = state
+        .token_pool
+        .as_ref()
+        .ok_or_else(|| unavailable("token pool"))?;
+    Ok(Json(json!({ "flushed": pool.flush() })))
+}
+
+/// `GET /admin/proxies`
+async fn proxies(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let pool = state
+        .proxy_pool
+        .as_ref()
+        .ok_or_else(|| unavailable("proxy pool"))?;
+    let proxies: Vec<Value> = pool
+        .stats()
+        .into_iter()
+        .map(|stats| {
+            json!({
+                "proxy": stats.proxy,
+                "successes": stats.successes,
+                "failures": stats.failures,
+                "fail_verdicts": stats.fail_verdicts,
+                "average_score": stats.average_score,
+                "quarantined_secs": stats.quarantined_for.map(|d| d.as_secs()),
+                "quarantine_count": stats.quarantine_count,
+            })
+        })
+        .collect();
+    Ok(Json(json!({ "proxies": proxies })))
+}
+
+/// Body of the quarantine endpoints.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ProxyAction {
+    /// Proxy URL, as configured or as masked in `GET /admin/proxies`
+    proxy: String,
+    #[serde(default)]
+    seconds: Option<u64>,
+}
+
+/// `POST /admin/proxies/quarantine`
+async fn quarantine(
+    State(state): State<AppState>,
+    Json(action): Json<ProxyAction>,
+) -> Result<Json<Value>, ApiError> {
+    let pool = state
+        .proxy_pool
+        .as_ref()
+        .ok_or_else(|| unavailable("proxy pool"))?;
+    let duration = action
+        .seconds
+        .map_or(DEFAULT_QUARANTINE, Duration::from_secs);
+    if !pool.quarantine(&action.proxy, duration) {
+        return Err(ApiError::not_found("no such proxy in the pool"));
+    }
+    Ok(Json(json!({ "quarantined_secs": duration.as_secs() })))
+}
+
+/// `POST /admin/proxies/unquarantine`
+async fn unquarantine(
+    State(state): State<AppState>,
+    Json(action): Json<ProxyAction>,
+) -> Result<Json<Value>, ApiError> {
+    let pool = state
+        .proxy_pool
+        .as_ref()
+        .ok_or_else(|| unavailable("proxy pool"))?;
+    if !pool.unquarantine(&action.proxy) {
+        return Err(ApiError::not_found("no such proxy in the pool"));
+    }
+    Ok(Json(json!({})))
+}
+
+/// Body of `PUT /admin/log-level`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct LogLevel {
+    /// `RUST_LOG`-style filter
+    filter: String,
+}
+
+/// `PUT /admin/log-level`
+async fn log_level(
+    State(state): State<AppState>,
+    Json(request): Json<LogLevel>,
+) -> Result<Json<Value>, ApiError> {
+    let set_filter = state
+        .log_filter
+        .as_ref()
+        .ok_or_else(|| unavailable("log level control"))?;
+    set_filter(&request.filter).map_err(GeekedError::UnsupportedType)?;
+    tracing::info!("Log filter changed to {}", request.filter);
+    Ok(Json(json!({ "filter": request.filter })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn state(admin_key: Option<&str>) -> AppState {
+        let config = ServerConfig {
+            admin_key: admin_key.map(str::to_string),
+            ..Default::default()
+        };
+        AppState::new(&config, Constants::default())
+    }
+
+    async fn status(state: AppState, request: Request<Body>) -> StatusCode {
+        router(state).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_key_required() {
+        let get = |key: Option<&str>| {
+            let request = Request::get("/admin/constants");
+            match key {
+                Some(key) => request.header("authorization", format!("Bearer {}", key)),
+                None => request,
+            }
+            .body(Body::empty())
+            .unwrap()
+        };
+        assert_eq!(
+            status(state(None), get(Some("k"))).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(state(Some("k")), get(None)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(state(Some("k")), get(Some("x"))).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(state(Some("k")), get(Some("k"))).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_proxy() {
+        let pool = Arc::new(ProxyPool::new(["http://user:pass@a:1", "http://b:1"]));
+        let state = state(Some("k")).with_proxy_pool(pool.clone());
+        let post = |uri: &str, body: &str| {
+            Request::post(uri)
+                .header("x-api-key", "k")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let quarantine = post(
+            "/admin/proxies/quarantine",
+            r#"{"proxy": "http://***@a:1"}"#,
+        );
+        assert_eq!(status(state.clone(), quarantine).await, StatusCode::OK);
+        assert_eq!(pool.acquire().unwrap(), "http://b:1");
+        assert_eq!(pool.acquire().unwrap(), "http://b:1");
+
+        let unquarantine = post(
+            "/admin/proxies/unquarantine",
+            r#"{"proxy": "http://***@a:1"}"#,
+        );
+        assert_eq!(status(state.clone(), unquarantine).await, StatusCode::OK);
+        let unknown = post("/admin/proxies/unquarantine", r#"{"proxy": "http://c:1"}"#);
+        assert_eq!(status(state, unknown).await, StatusCode::NOT_FOUND);
+    }
+}
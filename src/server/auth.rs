@@ -0,0 +1,327 @@
+//! API keys with per-key rate limits and daily quotas.
+//!
+//! Without keys configured the server is open to anyone. Otherwise every
+//! request must carry a known key: `Authorization: Bearer <key>` or
+//! `X-Api-Key` for `/solve`, `/tasks` and gRPC, `key` for
+//! `in.php`/`res.php` and `clientKey` for the task API. Each accepted solve
+//! counts against the key's per-minute rate limit and per-day (UTC) quota;
+//! with a stats store the daily counters survive restarts.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{GeekedError, Result as GeekedResult};
+#[cfg(feature = "stats")]
+use crate::stats::StatsStore;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Length of a rate limit window.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A client allowed to use the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Secret the client sends
+    pub key: String,
+    /// Tenant name, used in logs and usage counters
+    pub name: String,
+    /// Solves allowed per minute
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+    /// Solves allowed per UTC day
+    #[serde(default)]
+    pub daily_quota: Option<u64>,
+}
+
+/// Why a request was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Denied {
+    /// No key, or one that is not configured
+    UnknownKey,
+    /// The key's per-minute rate limit is reached
+    RateLimited,
+    /// The key's daily quota is used up
+    QuotaExhausted,
+}
+
+impl Denied {
+    /// Error code of the 2captcha and anti-captcha APIs.
+    pub(crate) fn provider_code(self) -> &'static str {
+        match self {
+            Denied::UnknownKey => "ERROR_KEY_DOES_NOT_EXIST",
+            Denied::RateLimited => "ERROR_NO_SLOT_AVAILABLE",
+            Denied::QuotaExhausted => "ERROR_ZERO_BALANCE",
+        }
+    }
+}
+
+impl From<Denied> for GeekedError {
+    fn from(denied: Denied) -> Self {
+        match denied {
+            Denied::UnknownKey => GeekedError::Unauthorized("missing or unknown API key".into()),
+            Denied::RateLimited => {
+                GeekedError::QuotaExceeded("rate limit reached, retry in a minute".into())
+            }
+            Denied::QuotaExhausted => GeekedError::QuotaExceeded("daily quota used up".into()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    /// Start and solve count of the current rate limit window
+    window: Option<(Instant, u32)>,
+    /// Day (since the epoch) and solve count of the current quota period
+    day: Option<(u64, u64)>,
+}
+
+struct Tenant {
+    key: ApiKey,
+    /// Shared with the tenant's replacement when the keys are reloaded
+    usage: Arc<Mutex<Usage>>,
+}
+
+/// Read the API keys from a JSON file.
+pub(crate) fn load_keys(path: &Path) -> GeekedResult<Vec<ApiKey>> {
+    let raw = std::fs::read_to_string(path)?;
+    serde_json::from_str(&raw)
+        .map_err(|e| GeekedError::Config(format!("{}: {}", path.display(), e)))
+}
+
+/// Configured keys and their usage.
+#[derive(Default)]
+pub(crate) struct ApiKeys {
+    tenants: RwLock<HashMap<String, Arc<Tenant>>>,
+    #[cfg(feature = "stats")]
+    stats: Option<Arc<StatsStore>>,
+}
+
+impl ApiKeys {
+    pub(crate) fn new(keys: &[ApiKey]) -> Self {
+        let api_keys = Self {
+            tenants: RwLock::default(),
+            #[cfg(feature = "stats")]
+            stats: None,
+        };
+        api_keys.replace(keys);
+        api_keys
+    }
+
+    /// Switch to `keys`, keeping the usage of keys that stay.
+    pub(crate) fn replace(&self, keys: &[ApiKey]) {
+        let mut tenants = self.tenants.write().unwrap_or_else(PoisonError::into_inner);
+        *tenants = keys
+            .iter()
+            .map(|key| {
+                let usage = tenants
+                    .get(&key.key)
+                    .map(|tenant| tenant.usage.clone())
+                    .unwrap_or_default();
+                let tenant = Tenant {
+                    key: key.clone(),
+                    usage,
+                };
+                (key.key.clone(), Arc::new(tenant))
+            })
+            .collect();
+    }
+
+    /// Persist daily usage to `stats`, and count what it already holds.
+    #[cfg(feature = "stats")]
+    pub(crate) fn with_stats(mut self, stats: Arc<StatsStore>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn keys(&self) -> Vec<ApiKey> {
+        self.tenants
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|t| t.key.clone())
+            .collect()
+    }
+
+    /// Tenant owning `key`; `None` if authentication is off.
+    fn tenant(&self, key: Option<&str>) -> Result<Option<Arc<Tenant>>, Denied> {
+        let tenants = self.tenants.read().unwrap_or_else(PoisonError::into_inner);
+        if tenants.is_empty() {
+            return Ok(None);
+        }
+        key.and_then(|key| tenants.get(key))
+            .cloned()
+            .map(Some)
+            .ok_or(Denied::UnknownKey)
+    }
+
+    /// Check `key` without counting a solve, returning the tenant name.
+    pub(crate) fn authenticate(&self, key: Option<&str>) -> Result<Option<String>, Denied> {
+        Ok(self.tenant(key)?.map(|tenant| tenant.key.name.clone()))
+    }
+
+    /// Count one solve against `key`, returning the tenant name.
+    pub(crate) fn admit(&self, key: Option<&str>) -> Result<Option<String>, Denied> {
+        let Some(tenant) = self.tenant(key)? else {
+            return Ok(None);
+        };
+        let today = today();
+        {
+            let mut usage = tenant.usage.lock().unwrap_or_else(PoisonError::into_inner);
+            let used = self.used_today(&tenant, &mut usage, today);
+            if tenant.key.daily_quota.is_some_and(|quota| used >= quota) {
+                return Err(Denied::QuotaExhausted);
+            }
+
+            if let Some(limit) = tenant.key.rate_limit {
+                let now = Instant::now();
+                let (start, count) = usage
+                    .window
+                    .filter(|(start, _)| now.duration_since(*start) < RATE_WINDOW)
+                    .unwrap_or((now, 0));
+                if count >= limit {
+                    return Err(Denied::RateLimited);
+                }
+                usage.window = Some((start, count + 1));
+            }
+            usage.day = Some((today, used + 1));
+        }
+
+        #[cfg(feature = "stats")]
+        if let Some(stats) = &self.stats {
+            if let Err(e) = stats.record_usage(&tenant.key.name, today) {
+                tracing::warn!("Failed to persist usage of {}: {}", tenant.key.name, e);
+            }
+        }
+        Ok(Some(tenant.key.name.clone()))
+    }
+
+    /// Solves `key` has left today; `None` if unlimited.
+    pub(crate) fn remaining(&self, key: Option<&str>) -> Result<Option<u64>, Denied> {
+        let Some(tenant) = self.tenant(key)? else {
+            return Ok(None);
+        };
+        let Some(quota) = tenant.key.daily_quota else {
+            return Ok(None);
+        };
+        let today = today();
+        let mut usage = tenant.usage.lock().unwrap_or_else(PoisonError::into_inner);
+        let used = self.used_today(&tenant, &mut usage, today);
+        usage.day = Some((today, used));
+        Ok(Some(quota.saturating_sub(used)))
+    }
+
+    /// Solves counted for `tenant` on `day`, loading the persisted count
+    /// when the day changes.
+    fn used_today(&self, tenant: &Tenant, usage: &mut Usage, day: u64) -> u64 {
+        match usage.day {
+            Some((current, used)) if current == day => used,
+            _ => self.persisted_usage(&tenant.key.name, day),
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    fn persisted_usage(&self, name: &str, day: u64) -> u64 {
+        let Some(stats) = &self.stats else {
+            return 0;
+        };
+        stats.usage(name, day).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load usage of {}: {}", name, e);
+            0
+        })
+    }
+
+    #[cfg(not(feature = "stats"))]
+    fn persisted_usage(&self, _name: &str, _day: u64) -> u64 {
+        0
+    }
+}
+
+/// API key from `Authorization: Bearer <key>` or `X-Api-Key`.
+pub(crate) fn header_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key")?.to_str().ok())
+        .map(str::trim)
+}
+
+/// Days since the Unix epoch (UTC).
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / SECS_PER_DAY)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(rate_limit: Option<u32>, daily_quota: Option<u64>) -> ApiKey {
+        ApiKey {
+            key: "secret".into(),
+            name: "team-a".into(),
+            rate_limit,
+            daily_quota,
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(ApiKeys::default().admit(None), Ok(None));
+
+        let keys = ApiKeys::new(&[key(Some(2), None)]);
+        assert_eq!(keys.admit(None), Err(Denied::UnknownKey));
+        assert_eq!(keys.admit(Some("wrong")), Err(Denied::UnknownKey));
+        assert_eq!(keys.admit(Some("secret")), Ok(Some("team-a".into())));
+        assert!(keys.admit(Some("secret")).is_ok());
+        assert_eq!(keys.admit(Some("secret")), Err(Denied::RateLimited));
+        assert_eq!(keys.remaining(Some("secret")), Ok(None));
+
+        let keys = ApiKeys::new(&[key(None, Some(1))]);
+        assert_eq!(keys.remaining(Some("secret")), Ok(Some(1)));
+        assert!(keys.admit(Some("secret")).is_ok());
+        assert_eq!(keys.admit(Some("secret")), Err(Denied::QuotaExhausted));
+        assert_eq!(keys.remaining(Some("secret")), Ok(Some(0)));
+    }
+
+    #[test]
+    fn test_replace_keeps_usage() {
+        let keys = ApiKeys::new(&[key(None, Some(2))]);
+        keys.admit(Some("secret")).unwrap();
+
+        keys.replace(&[key(None, Some(3))]);
+        assert_eq!(keys.remaining(Some("secret")), Ok(Some(2)));
+        keys.replace(&[]);
+        assert_eq!(keys.admit(Some("secret")), Ok(None));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_usage_survives_restart() {
+        let stats = Arc::new(StatsStore::open_in_memory().unwrap());
+        let keys = ApiKeys::new(&[key(None, Some(2))]).with_stats(stats.clone());
+        keys.admit(Some("secret")).unwrap();
+
+        let restarted = ApiKeys::new(&[key(None, Some(2))]).with_stats(stats);
+        assert_eq!(restarted.remaining(Some("secret")), Ok(Some(1)));
+    }
+
+    #[test]
+    fn test_header_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(header_key(&headers), None);
+        headers.insert("x-api-key", "abc".parse().unwrap());
+        assert_eq!(header_key(&headers), Some("abc"));
+        headers.insert("authorization", "Bearer xyz".parse().unwrap());
+        assert_eq!(header_key(&headers), Some("xyz"));
+    }
+}
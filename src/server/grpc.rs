@@ -0,0 +1,274 @@
+//! gRPC service defined in `proto/chaser_gt.proto`.
+//!
+//! Shares the HTTP server's [`AppState`], so both APIs draw from the same
+//! concurrency limit and constants. `SolveStream` accepts any number of
+//! requests on one stream and reports each one's progress as it goes.
+//! API keys go in the `authorization: Bearer <key>` or `x-api-key` metadata.
+
+use std::future::Future;
+use std::net::SocketAddr;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use super::auth::header_key;
+use super::solve::{acquire_slot, run_job, solve_job, SolveJob};
+use super::AppState;
+use crate::error::{GeekedError, Result};
+use crate::priority::Priority;
+use crate::SecCode;
+
+/// Generated protobuf types and service stubs.
+pub mod proto {
+    tonic::include_proto!("chaser_gt.v1");
+}
+
+use proto::solve_event::Event;
+use proto::solver_server::{Solver, SolverServer};
+use proto::{SolveEvent, SolveRequest, SolveResponse, SolveStage};
+
+/// Events buffered per stream before the sender waits on the client.
+const STREAM_BUFFER: usize = 64;
+
+/// `Solver` service implementation.
+pub struct SolverService {
+    state: AppState,
+}
+
+impl SolverService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+/// Build the tonic service, for mounting next to other gRPC services.
+pub fn service(state: AppState) -> SolverServer<SolverService> {
+    SolverServer::new(SolverService::new(state))
+}
+
+/// Serve the gRPC API on `addr` until the process is stopped.
+pub async fn serve(
+    addr: SocketAddr,
+    state: AppState,
+) -> std::result::Result<(), tonic::transport::Error> {
+    serve_with_shutdown(addr, state, std::future::pending()).await
+}
+
+/// Serve the gRPC API on `addr` until `shutdown` resolves and the calls
+/// in progress have finished.
+pub async fn serve_with_shutdown(
+    addr: SocketAddr,
+    state: AppState,
+    shutdown: impl Future<Output = ()>,
+) -> std::result::Result<(), tonic::transport::Error> {
+    tracing::info!("gRPC listening on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(service(state))
+        .serve_with_shutdown(addr, shutdown)
+        .await
+}
+
+#[tonic::async_trait]
+impl Solver for SolverService {
+    async fn solve(
+        &self,
+        request: Request<SolveRequest>,
+    ) -> std::result::Result<Response<SolveResponse>, Status> {
+        let key = api_key(&request);
+        let job = to_job(request.into_inner()).map_err(to_status)?;
+        let _job = self.state.start_job().map_err(to_status)?;
+        self.state
+            .api_keys
+            .admit(key.as_deref())
+            .map_err(|denied| to_status(denied.into()))?;
+        let seccode = run_job(&self.state, job).await.map_err(to_status)?;
+        Ok(Response::new(seccode.into()))
+    }
+
+    type SolveStreamStream = ReceiverStream<std::result::Result<SolveEvent, Status>>;
+
+    async fn solve_stream(
+        &self,
+        request: Request<Streaming<SolveRequest>>,
+    ) -> std::result::Result<Response<Self::SolveStreamStream>, Status> {
+        let key = api_key(&request);
+        self.state
+            .api_keys
+            .authenticate(key.as_deref())
+            .map_err(|denied| to_status(denied.into()))?;
+        let mut requests = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let state = self.state.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match requests.message().await {
+                    Ok(Some(request)) => {
+                        let solve = stream_solve(state.clone(), key.clone(), request, tx.clone());
+                        tokio::spawn(solve);
+                    }
+                    Ok(None) => break,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Solve one streamed request, sending progress and the outcome to `tx`.
+async fn stream_solve(
+    state: AppState,
+    key: Option<String>,
+    request: SolveRequest,
+    tx: mpsc::Sender<std::result::Result<SolveEvent, Status>>,
+) {
+    let request_id = request.request_id.clone();
+    let send = |event: Event| {
+        let tx = tx.clone();
+        let request_id = request_id.clone();
+        async move {
+            // The client hanging up is not an error for the solve
+            let _ = tx
+                .send(Ok(SolveEvent {
+                    request_id,
+                    event: Some(event),
+                }))
+                .await;
+        }
+    };
+
+    let job = match to_job(request) {
+        Ok(job) => job,
+        Err(e) => return send(Event::Error(e.into())).await,
+    };
+
+    let _job = match state.start_job() {
+        Ok(guard) => guard,
+        Err(e) => return send(Event::Error(e.into())).await,
+    };
+    if let Err(denied) = state.api_keys.admit(key.as_deref()) {
+        return send(Event::Error(GeekedError::from(denied).into())).await;
+    }
+
+    send(Event::Progress(SolveStage::Queued.into())).await;
+    let _permit = acquire_slot(&state, job.priority).await;
+    send(Event::Progress(SolveStage::Solving.into())).await;
+
+    match solve_job(&state, job).await {
+        Ok(seccode) => send(Event::Result(seccode.into())).await,
+        Err(e) => send(Event::Error(e.into())).await,
+    }
+}
+
+/// API key from the request metadata.
+fn api_key<T>(request: &Request<T>) -> Option<String> {
+    header_key(&request.metadata().clone().into_headers()).map(str::to_string)
+}
+
+fn to_job(request: SolveRequest) -> Result<SolveJob> {
+    let priority = match request.priority() {
+        proto::Priority::Unspecified | proto::Priority::Interactive => Priority::Interactive,
+        proto::Priority::Batch => Priority::Batch,
+    };
+    Ok(SolveJob {
+        captcha_id: request.captcha_id,
+        risk_type: request.risk_type.parse()?,
+        proxy: request.proxy,
+        user_info: request.user_info,
+        priority,
+    })
+}
+
+/// Map a solve error to a gRPC status, with the numeric code in the
+/// `error-code` metadata entry.
+fn to_status(err: GeekedError) -> Status {
+    let code = match &err {
+        GeekedError::UnsupportedType(_) | GeekedError::InvalidProxy(_) => Code::InvalidArgument,
+        GeekedError::Unauthorized(_) => Code::Unauthenticated,
+        GeekedError::QuotaExceeded(_) => Code::ResourceExhausted,
+        GeekedError::Geetest { .. } if err.is_captcha_id_invalid() => Code::InvalidArgument,
+        GeekedError::Geetest { .. } if err.is_rate_limited() => Code::ResourceExhausted,
+        GeekedError::VerificationFailed { .. } | GeekedError::LowScore { .. } => Code::Aborted,
+        GeekedError::Http(_)
+        | GeekedError::UnexpectedResponse { .. }
+        | GeekedError::InvalidResponse(_)
+        | GeekedError::Geetest { .. }
+        | GeekedError::NoProxyAvailable(_)
+        | GeekedError::Queue(_)
+        | GeekedError::CircuitOpen { .. }
+        | GeekedError::ServiceUnavailable(_) => Code::Unavailable,
+        GeekedError::DeadlineExceeded(_) => Code::DeadlineExceeded,
+        _ => Code::Internal,
+    };
+
+    let mut status = Status::new(code, err.to_string());
+    status
+        .metadata_mut()
+        .insert("error-code", MetadataValue::from(err.error_code()));
+    status
+}
+
+impl From<SecCode> for SolveResponse {
+    fn from(seccode: SecCode) -> Self {
+        Self {
+            captcha_id: seccode.captcha_id,
+            lot_number: seccode.lot_number,
+            pass_token: seccode.pass_token,
+            gen_time: seccode.gen_time,
+            captcha_output: seccode.captcha_output,
+        }
+    }
+}
+
+impl From<GeekedError> for proto::SolveError {
+    fn from(err: GeekedError) -> Self {
+        Self {
+            code: err.error_code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unsupported_type_is_invalid_argument() {
+        let request = SolveRequest {
+            captcha_id: "x".into(),
+            risk_type: "puzzle".into(),
+            ..Default::default()
+        };
+
+        let status = SolverService::new(AppState::for_test())
+            .solve(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.metadata().get("error-code").unwrap(), "300");
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_errors_per_request() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let request = SolveRequest {
+            request_id: "r1".into(),
+            captcha_id: "x".into(),
+            risk_type: "puzzle".into(),
+            ..Default::default()
+        };
+
+        stream_solve(AppState::for_test(), None, request, tx).await;
+        let event = rx.recv().await.unwrap().unwrap();
+        assert_eq!(event.request_id, "r1");
+        assert!(matches!(event.event, Some(Event::Error(e)) if e.code == 300));
+    }
+}
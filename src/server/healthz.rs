@@ -0,0 +1,149 @@
+//! `GET /healthz`.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+use super::solve::acquire_slot;
+use super::{ApiError, AppState};
+use crate::error::{GeekedError, Result};
+use crate::health::{HealthCheck, HealthReport};
+use crate::priority::Priority;
+use crate::{Geeked, RiskType, SecCode};
+
+/// How long after a solve found Geetest down new solves fail fast and
+/// `/healthz` reports the outage; the first solve after it probes again.
+pub(crate) const OUTAGE_HOLD: Duration = Duration::from_secs(30);
+
+/// The latest Geetest outage seen by a solve.
+#[derive(Debug, Default)]
+pub(crate) struct OutageTracker {
+    seen: Mutex<Option<(Instant, String)>>,
+}
+
+impl OutageTracker {
+    /// Note an outage reported by `result`, or its end.
+    pub(crate) fn observe(&self, result: &Result<SecCode>) {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        match result {
+            Err(GeekedError::ServiceUnavailable(reason)) => {
+                if seen.is_none() {
+                    tracing::warn!("Geetest is down: {}", reason);
+                }
+                *seen = Some((Instant::now(), reason.clone()));
+            }
+            Ok(_) => {
+                if seen.take().is_some() {
+                    tracing::info!("Geetest is back");
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Why Geetest is down, if a solve found it down within [`OUTAGE_HOLD`].
+    pub(crate) fn current(&self) -> Option<String> {
+        let seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.as_ref()
+            .filter(|(at, _)| at.elapsed() < OUTAGE_HOLD)
+            .map(|(_, reason)| reason.clone())
+    }
+}
+
+/// Query string of `GET /healthz`.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct HealthQuery {
+    /// Also solve the health captcha
+    #[serde(default)]
+    solve: bool,
+}
+
+/// Health report for the configured demo captcha; 503 if any check failed.
+///
+/// During a Geetest outage seen by recent solves, reports it without
+/// sending any request.
+pub(crate) async fn healthz(
+    State(state): State<AppState>,
+    Query(query): Query<HealthQuery>,
+) -> std::result::Result<(StatusCode, Json<HealthReport>), ApiError> {
+    if let Some(reason) = state.outage.current() {
+        let check = HealthCheck::run("geetest", async { Err(format!("unavailable: {}", reason)) });
+        let report = HealthReport::new(vec![check.await]);
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, Json(report)));
+    }
+    let mut builder = Geeked::builder(state.health_captcha_id.clone(), RiskType::Slide)
+        .constants(state.constants())
+        .strict_responses(state.strict_responses);
+    if let Some(dir) = &state.cache_dir {
+        builder = builder.cache_dir(dir);
+    }
+    let solver = builder.build().await?;
+
+    let report = if query.solve {
+        let _permit = acquire_slot(&state, Priority::Interactive).await;
+        solver.health_check(true).await
+    } else {
+        solver.health_check(false).await
+    };
+
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok((status, Json(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_outage_reported_without_requests() {
+        let state = AppState::for_test();
+        let down = Err(GeekedError::ServiceUnavailable("HTTP 503".into()));
+        state.outage.observe(&down);
+        assert_eq!(state.outage.current().as_deref(), Some("HTTP 503"));
+
+        let request = Request::get("/healthz").body(Body::empty()).unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["checks"][0]["name"], "geetest");
+
+        // Solves fail fast until the outage ends
+        let job = super::super::solve::SolveJob {
+            captcha_id: "abc".into(),
+            risk_type: RiskType::Slide,
+            proxy: None,
+            user_info: None,
+            priority: Default::default(),
+        };
+        assert!(matches!(
+            super::super::solve::solve_job(&state, job).await,
+            Err(GeekedError::ServiceUnavailable(_))
+        ));
+
+        state.outage.observe(&Err(GeekedError::VerificationFailed {
+            message: "fail".into(),
+        }));
+        assert!(state.outage.current().is_some());
+        let seccode: crate::SecCode = serde_json::from_value(serde_json::json!({
+            "captcha_id": "abc", "lot_number": "lot", "pass_token": "pass",
+            "gen_time": "1", "captcha_output": "out"
+        }))
+        .unwrap();
+        state.outage.observe(&Ok(seccode));
+        assert!(state.outage.current().is_none());
+    }
+}
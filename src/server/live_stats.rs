@@ -0,0 +1,48 @@
+//! `GET /stats`.
+
+use std::time::Duration;
+
+use axum::extract::Query;
+use axum::Json;
+use serde::Deserialize;
+
+use crate::live_stats::{self, LiveStats};
+
+/// Query string of `GET /stats`.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct StatsQuery {
+    /// Window in minutes, 1 to 60; 15 by default
+    #[serde(default)]
+    minutes: Option<u64>,
+}
+
+/// Rolling solve statistics of this server.
+pub(crate) async fn stats(Query(query): Query<StatsQuery>) -> Json<LiveStats> {
+    let window = query.minutes.map_or(live_stats::DEFAULT_WINDOW, |m| {
+        Duration::from_secs(m.saturating_mul(60))
+    });
+    Json(live_stats::snapshot(window))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_stats_window() {
+        let state = AppState::for_test();
+        let request = Request::get("/stats?minutes=500")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["window_minutes"], 60);
+    }
+}
@@ -0,0 +1,620 @@
+//! HTTP API server.
+//!
+//! Exposes the solver over REST so non-Rust programs do not have to wrap the
+//! FFI themselves. Run it with the `chaser-gt-server` binary or embed the
+//! [`router`] in an existing axum application.
+//!
+//! ```text
+//! POST /solve {"captcha_id": "...", "risk_type": "slide", "proxy": null, "user_info": null}
+//! -> 200 {"captcha_id": "...", "lot_number": "...", "pass_token": "...", "gen_time": "...", "captcha_output": "..."}
+//! ```
+//!
+//! Clients that cannot hold a connection open for a whole solve submit it to
+//! `POST /tasks` and poll `GET /tasks/{id}` instead.
+//!
+//! For existing bots, the 2captcha `in.php`/`res.php` API (`method=geetest_v4`)
+//! and the JSON `createTask`/`getTaskResult` task API are served as well.
+//! With the `grpc` feature, the [`grpc`] service can run next to them.
+//!
+//! Solves share one set of constants, refreshed in the background, and the
+//! number of concurrent solves is capped, with batch solves limited to
+//! their [`PriorityShares`] of it. With [`ServerConfig::api_keys`]
+//! set, clients need a key and are held to its limits (see [`ApiKey`]). On SIGTERM the server stops
+//! accepting work and gives running solves [`ServerConfig::drain_timeout`]
+//! to finish. On SIGHUP, or when they change, the config, API key, profiles
+//! and proxy files are re-read (see [`crate::reload`]).
+
+mod admin;
+mod auth;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod healthz;
+mod live_stats;
+mod rest_tasks;
+mod solve;
+mod task_api;
+mod tasks;
+mod twocaptcha;
+
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::json;
+
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+use crate::deobfuscate::Deobfuscator;
+use crate::error::{GeekedError, Result};
+use crate::image_cache::{ImageCache, ImageCacheConfig};
+use crate::models::{Constants, RiskType};
+use crate::priority::{PriorityLimiter, PriorityShares};
+use crate::profile::Profiles;
+use crate::proxy_pool::ProxyPool;
+#[cfg(feature = "redis-cache")]
+use crate::redis_cache::RedisCache;
+use crate::reload::{self, RuntimeConfig};
+use crate::shutdown::{self, JobGuard, JobTracker};
+#[cfg(feature = "icon")]
+use crate::solvers::{IconSolver, IconSolverConfig};
+#[cfg(feature = "stats")]
+use crate::stats::StatsStore;
+use crate::token_pool::TokenPool;
+use crate::webhook::Webhooks;
+
+use auth::ApiKeys;
+
+pub use auth::ApiKey;
+pub use tasks::{TaskStatus, TaskStore};
+
+/// Time left for the last responses to be written once all solves are done.
+const RESPONSE_FLUSH: Duration = Duration::from_secs(5);
+
+/// Server settings.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub bind: SocketAddr,
+    /// Maximum number of solves running at once; further requests wait
+    pub max_concurrency: usize,
+    /// Share of `max_concurrency` each priority may use
+    pub priority_shares: PriorityShares,
+    /// Directory for the constants cache (platform default if unset)
+    pub cache_dir: Option<PathBuf>,
+    /// How often to check for new constants
+    pub constants_refresh: Duration,
+    /// Refuse to start with outdated constants that cannot be refreshed
+    pub strict_constants: bool,
+    /// Fail solves on Geetest responses with unknown fields (canaries)
+    pub strict_responses: bool,
+    /// Upper bound on one solve, continue rounds included; unbounded if unset
+    pub solve_deadline: Option<Duration>,
+    /// Redis URL for sharing constants with other instances
+    #[cfg(feature = "redis-cache")]
+    pub constants_redis_url: Option<String>,
+    /// Proxy pool file (TOML or JSON) for requests without a proxy;
+    /// reloaded when it changes
+    pub proxy_file: Option<PathBuf>,
+    /// Session template appended to the proxy user name per solve, for
+    /// rotating gateways (e.g. `-session-{id}`)
+    pub proxy_session_template: Option<String>,
+    /// Cache for captcha images shared by all solves; off if unset
+    pub image_cache: Option<ImageCacheConfig>,
+    /// Fail solves fast for captcha IDs that keep failing; off if unset
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// ONNX Runtime settings for the icon model; library defaults if unset
+    #[cfg(feature = "icon")]
+    pub icon: Option<IconSolverConfig>,
+    /// Profiles file; solves for a profile's captcha ID get its settings
+    pub profiles_file: Option<PathBuf>,
+    /// Runtime config file overriding the concurrency settings; reloaded
+    /// when it changes
+    pub config_file: Option<PathBuf>,
+    /// Captcha type for provider-compatible APIs, which do not carry one
+    pub default_risk_type: RiskType,
+    /// Slide captcha ID probed by `GET /healthz`
+    pub health_captcha_id: String,
+    /// Key for signing task callbacks; unsigned if unset
+    pub webhook_secret: Option<String>,
+    /// Retries of a failed task callback
+    pub webhook_retries: u32,
+    /// Hosts task callbacks may be sent to; any public host if empty
+    pub callback_hosts: Vec<String>,
+    /// How long running solves may take to finish on shutdown
+    pub drain_timeout: Duration,
+    /// How long finished task results stay retrievable
+    pub result_ttl: Duration,
+    /// Keys clients must present; the server is open if empty
+    pub api_keys: Vec<ApiKey>,
+    /// JSON file with the keys, replacing `api_keys`; reloaded when it
+    /// changes
+    pub api_keys_file: Option<PathBuf>,
+    /// Key for the `/admin` endpoints, which are not served if unset
+    pub admin_key: Option<String>,
+    /// Sqlite database for solve outcomes and API key usage
+    #[cfg(feature = "stats")]
+    pub stats_db: Option<PathBuf>,
+    /// Address for the gRPC service; not started if unset
+    #[cfg(feature = "grpc")]
+    pub grpc_bind: Option<SocketAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            max_concurrency: 16,
+            priority_shares: PriorityShares::default(),
+            cache_dir: None,
+            constants_refresh: Duration::from_secs(600),
+            strict_constants: false,
+            strict_responses: false,
+            solve_deadline: None,
+            #[cfg(feature = "redis-cache")]
+            constants_redis_url: None,
+            proxy_file: None,
+            proxy_session_template: None,
+            image_cache: None,
+            circuit_breaker: None,
+            #[cfg(feature = "icon")]
+            icon: None,
+            profiles_file: None,
+            config_file: None,
+            default_risk_type: RiskType::Slide,
+            // Geetest's public slide demo
+            health_captcha_id: "54088bb07d2df3c46b79f80300b0abbe".to_string(),
+            webhook_secret: None,
+            webhook_retries: 3,
+            callback_hosts: Vec::new(),
+            drain_timeout: Duration::from_secs(30),
+            result_ttl: Duration::from_secs(300),
+            api_keys: Vec::new(),
+            api_keys_file: None,
+            admin_key: None,
+            #[cfg(feature = "stats")]
+            stats_db: None,
+            #[cfg(feature = "grpc")]
+            grpc_bind: None,
+        }
+    }
+}
+
+/// Applies a `RUST_LOG`-style filter at runtime, see [`AppState::with_log_filter`].
+pub type SetLogFilter = Arc<dyn Fn(&str) -> std::result::Result<(), String> + Send + Sync>;
+
+/// State shared by all handlers.
+#[derive(Clone)]
+pub struct AppState {
+    limiter: Arc<PriorityLimiter>,
+    constants: Arc<RwLock<Arc<Constants>>>,
+    tasks: Arc<TaskStore>,
+    /// Proxies for jobs that do not bring their own
+    proxy_pool: Option<Arc<ProxyPool>>,
+    proxy_session_template: Option<String>,
+    image_cache: Option<Arc<ImageCache>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Settings for known captcha IDs
+    profiles: Arc<RwLock<Arc<Profiles>>>,
+    default_risk_type: RiskType,
+    cache_dir: Option<PathBuf>,
+    strict_responses: bool,
+    solve_deadline: Option<Duration>,
+    health_captcha_id: String,
+    /// Latest Geetest outage seen by a solve
+    outage: Arc<healthz::OutageTracker>,
+    webhooks: Webhooks,
+    /// Running solves, for draining on shutdown
+    jobs: JobTracker,
+    api_keys: Arc<ApiKeys>,
+    admin_key: Option<String>,
+    /// Refreshes constants on admin request
+    deobfuscator: Option<Arc<Deobfuscator>>,
+    /// Pre-solved tokens handed out before solving
+    token_pool: Option<Arc<TokenPool>>,
+    log_filter: Option<SetLogFilter>,
+    #[cfg(feature = "stats")]
+    stats: Option<Arc<StatsStore>>,
+}
+
+impl AppState {
+    /// Create state around already loaded constants.
+    pub fn new(config: &ServerConfig, constants: Constants) -> Self {
+        Self {
+            limiter: Arc::new(PriorityLimiter::new(
+                config.max_concurrency,
+                &config.priority_shares,
+            )),
+            constants: Arc::new(RwLock::new(Arc::new(constants))),
+            tasks: Arc::new(TaskStore::new(config.result_ttl)),
+            proxy_pool: None,
+            proxy_session_template: config.proxy_session_template.clone(),
+            image_cache: config
+                .image_cache
+                .clone()
+                .map(|config| Arc::new(ImageCache::new(config))),
+            circuit_breaker: config
+                .circuit_breaker
+                .clone()
+                .map(|config| Arc::new(CircuitBreaker::with_config(config))),
+            profiles: Default::default(),
+            default_risk_type: config.default_risk_type.clone(),
+            cache_dir: config.cache_dir.clone(),
+            strict_responses: config.strict_responses,
+            solve_deadline: config.solve_deadline,
+            health_captcha_id: config.health_captcha_id.clone(),
+            outage: Default::default(),
+            webhooks: Webhooks {
+                secret: config.webhook_secret.clone(),
+                retries: config.webhook_retries,
+                hosts: Arc::new(config.callback_hosts.clone()),
+            },
+            jobs: JobTracker::default(),
+            api_keys: Arc::new(ApiKeys::new(&config.api_keys)),
+            admin_key: config.admin_key.clone(),
+            deobfuscator: None,
+            token_pool: None,
+            log_filter: None,
+            #[cfg(feature = "stats")]
+            stats: None,
+        }
+    }
+
+    /// Default config and empty constants.
+    #[cfg(test)]
+    pub(crate) fn for_test() -> Self {
+        Self::new(&ServerConfig::default(), Constants::default())
+    }
+
+    /// Let admins force a constants refresh through `deobfuscator`.
+    pub fn with_deobfuscator(mut self, deobfuscator: Arc<Deobfuscator>) -> Self {
+        self.deobfuscator = Some(deobfuscator);
+        self
+    }
+
+    /// Answer solves for captcha IDs in `pool` with pooled tokens when one
+    /// is ready.
+    pub fn with_token_pool(mut self, pool: Arc<TokenPool>) -> Self {
+        self.token_pool = Some(pool);
+        self
+    }
+
+    /// Let admins change the log filter through `set_filter`.
+    pub fn with_log_filter(mut self, set_filter: SetLogFilter) -> Self {
+        self.log_filter = Some(set_filter);
+        self
+    }
+
+    /// Record solves and API key usage in `stats`.
+    #[cfg(feature = "stats")]
+    pub fn with_stats(mut self, stats: Arc<StatsStore>) -> Self {
+        self.api_keys = Arc::new(ApiKeys::new(&self.api_keys.keys()).with_stats(stats.clone()));
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Solve jobs without a proxy through `pool`.
+    pub fn with_proxy_pool(mut self, pool: Arc<ProxyPool>) -> Self {
+        self.proxy_pool = Some(pool);
+        self
+    }
+
+    /// Apply a profile's settings to solves for its captcha ID.
+    pub fn with_profiles(self, profiles: Profiles) -> Self {
+        self.set_profiles(profiles);
+        self
+    }
+
+    fn set_profiles(&self, profiles: Profiles) {
+        *self
+            .profiles
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Arc::new(profiles);
+    }
+
+    fn profiles(&self) -> Arc<Profiles> {
+        self.profiles
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Re-read the reloadable files of `config`. A file that fails to load
+    /// is logged and keeps its previous settings.
+    fn reload(&self, config: &ServerConfig) {
+        if let Some(path) = &config.config_file {
+            match RuntimeConfig::load(path) {
+                Ok(runtime) => {
+                    let mut max_concurrency = config.max_concurrency;
+                    let mut shares = config.priority_shares.clone();
+                    runtime.apply(&mut max_concurrency, &mut shares);
+                    self.limiter.resize(max_concurrency, &shares);
+                }
+                Err(e) => tracing::warn!("Keeping the current concurrency limits: {}", e),
+            }
+        }
+        if let Some(path) = &config.api_keys_file {
+            match auth::load_keys(path) {
+                Ok(keys) => self.api_keys.replace(&keys),
+                Err(e) => tracing::warn!("Keeping the current API keys: {}", e),
+            }
+        }
+        if let Some(path) = &config.profiles_file {
+            match Profiles::load(path) {
+                Ok(profiles) => self.set_profiles(profiles),
+                Err(e) => tracing::warn!("Keeping the current profiles: {}", e),
+            }
+        }
+        if let (Some(pool), Some(path)) = (&self.proxy_pool, &config.proxy_file) {
+            if let Err(e) = pool.reload_file(path) {
+                tracing::warn!("Keeping the current proxy pool: {}", e);
+            }
+        }
+        tracing::info!("Configuration reloaded");
+    }
+
+    /// Constants used for new solves.
+    pub fn constants(&self) -> Arc<Constants> {
+        self.constants
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Register a new solve, or fail if the server is shutting down.
+    fn start_job(&self) -> Result<JobGuard> {
+        self.jobs
+            .start()
+            .ok_or_else(|| GeekedError::Queue("server is shutting down".to_string()))
+    }
+
+    fn set_constants(&self, constants: Constants) {
+        *self
+            .constants
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Arc::new(constants);
+    }
+}
+
+/// Build the API router.
+pub fn router(state: AppState) -> Router {
+    let router = Router::new();
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", get(|| async { crate::metrics::encode() }));
+
+    let router = match state.admin_key {
+        Some(_) => router.nest("/admin", admin::router(state.clone())),
+        None => router,
+    };
+
+    router
+        .route("/solve", post(solve::solve))
+        .route("/tasks", post(rest_tasks::submit_task))
+        .route("/tasks/{id}", get(rest_tasks::get_task))
+        .route("/healthz", get(healthz::healthz))
+        .route("/stats", get(live_stats::stats))
+        .route("/in.php", get(twocaptcha::in_get).post(twocaptcha::in_post))
+        .route("/res.php", get(twocaptcha::res))
+        .route("/createTask", post(task_api::create_task))
+        .route("/getTaskResult", post(task_api::get_task_result))
+        .route("/getBalance", post(task_api::get_balance))
+        .with_state(state)
+}
+
+/// Load constants, then serve until SIGTERM or Ctrl-C.
+pub async fn serve(config: ServerConfig) -> Result<()> {
+    serve_with_shutdown(config, shutdown::signal()).await
+}
+
+/// Like [`serve`], letting `/admin/log-level` change the log filter.
+pub async fn serve_with_log_filter(config: ServerConfig, set_filter: SetLogFilter) -> Result<()> {
+    run(config, Some(set_filter), shutdown::signal()).await
+}
+
+/// Load constants, then serve until `shutdown` resolves.
+///
+/// New requests are refused from then on, and running solves (including
+/// queued tasks) get [`ServerConfig::drain_timeout`] to finish.
+pub async fn serve_with_shutdown(
+    config: ServerConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    run(config, None, shutdown).await
+}
+
+async fn run(
+    config: ServerConfig,
+    log_filter: Option<SetLogFilter>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    #[cfg(feature = "icon")]
+    if let Some(icon) = &config.icon {
+        IconSolver::init_shared(icon)?;
+    }
+    let deobfuscator = match &config.cache_dir {
+        Some(dir) => Deobfuscator::with_cache_path(dir.join("constants.json")),
+        None => Deobfuscator::new(),
+    }
+    .with_strict_refresh(config.strict_constants);
+    #[cfg(feature = "redis-cache")]
+    let deobfuscator = match &config.constants_redis_url {
+        Some(url) => deobfuscator.with_redis_cache(RedisCache::connect(url).await?),
+        None => deobfuscator,
+    };
+    let deobfuscator = Arc::new(deobfuscator);
+
+    // `config` stays as given, so settings removed from the runtime files
+    // fall back to it on reload
+    let mut effective = config.clone();
+    if let Some(path) = &config.config_file {
+        RuntimeConfig::load(path)?.apply(
+            &mut effective.max_concurrency,
+            &mut effective.priority_shares,
+        );
+    }
+    if let Some(path) = &config.api_keys_file {
+        effective.api_keys = auth::load_keys(path)?;
+    }
+    let state = AppState::new(&effective, deobfuscator.get_constants().await?)
+        .with_deobfuscator(deobfuscator.clone());
+    let state = match log_filter {
+        Some(set_filter) => state.with_log_filter(set_filter),
+        None => state,
+    };
+    let state = match &config.proxy_file {
+        Some(path) => {
+            let pool = Arc::new(ProxyPool::from_file(path)?);
+            pool.watch(path, reload::FILE_POLL);
+            state.with_proxy_pool(pool)
+        }
+        None => state,
+    };
+    let state = match &config.profiles_file {
+        Some(path) => state.with_profiles(Profiles::load(path)?),
+        None => state,
+    };
+    #[cfg(feature = "stats")]
+    let state = match &config.stats_db {
+        Some(path) => state.with_stats(Arc::new(StatsStore::open(path)?)),
+        None => state,
+    };
+
+    let watched = [
+        &config.config_file,
+        &config.api_keys_file,
+        &config.profiles_file,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    let reload_state = state.clone();
+    let reload_config = config.clone();
+    reload::spawn(watched, reload::FILE_POLL, move || {
+        reload_state.reload(&reload_config)
+    });
+
+    // Pick up Geetest script updates without a restart
+    let refresh_state = state.clone();
+    let interval = config.constants_refresh;
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match deobfuscator.get_constants().await {
+                Ok(constants) => refresh_state.set_constants(constants),
+                Err(e) => tracing::warn!("Failed to refresh constants: {}", e),
+            }
+        }
+    });
+
+    // Refuse new solves as soon as shutdown starts; accepted ones keep
+    // their job guard until they finish
+    let (stopping, mut stopped) = tokio::sync::watch::channel(false);
+    let jobs = state.jobs.clone();
+    let shutdown = async move {
+        shutdown.await;
+        jobs.close();
+        let _ = stopping.send(true);
+    };
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = config.grpc_bind {
+        let grpc_state = state.clone();
+        let mut stopped = stopped.clone();
+        tokio::spawn(async move {
+            let shutdown = async move {
+                let _ = stopped.wait_for(|stopped| *stopped).await;
+            };
+            if let Err(e) = grpc::serve_with_shutdown(addr, grpc_state, shutdown).await {
+                tracing::error!("gRPC server failed: {}", e);
+            }
+        });
+    }
+
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    tracing::info!("Listening on {}", config.bind);
+    if !config.bind.ip().is_loopback() && effective.api_keys.is_empty() {
+        tracing::warn!(
+            "Listening on {} without API keys; anyone who can reach it can solve and send callbacks",
+            config.bind
+        );
+    }
+    let server = axum::serve(listener, router(state.clone()))
+        .with_graceful_shutdown(shutdown)
+        .into_future();
+    tokio::pin!(server);
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        _ = stopped.wait_for(|stopped| *stopped) => {}
+    }
+
+    let unfinished = state.jobs.drain(config.drain_timeout).await;
+    if unfinished > 0 {
+        tracing::warn!("Stopping with {} solves still running", unfinished);
+    } else if tokio::time::timeout(RESPONSE_FLUSH, server).await.is_err() {
+        tracing::warn!("Stopping with connections still open");
+    }
+    Ok(())
+}
+
+/// Error returned by the API as `{"error": ..., "error_code": ...}`.
+pub(crate) struct ApiError {
+    status: StatusCode,
+    code: Option<i32>,
+    message: String,
+}
+
+impl ApiError {
+    /// 404 for something that does not exist (or no longer does).
+    fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            code: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<GeekedError> for ApiError {
+    fn from(err: GeekedError) -> Self {
+        let status = match &err {
+            GeekedError::UnsupportedType(_) | GeekedError::InvalidProxy(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            GeekedError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            GeekedError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            GeekedError::Geetest { .. } if err.is_captcha_id_invalid() => StatusCode::BAD_REQUEST,
+            GeekedError::Geetest { .. } if err.is_rate_limited() => StatusCode::TOO_MANY_REQUESTS,
+            GeekedError::VerificationFailed { .. }
+            | GeekedError::LowScore { .. }
+            | GeekedError::RiskEscalated { .. }
+            | GeekedError::ImplausibleAnswer(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            GeekedError::Http(_)
+            | GeekedError::UnexpectedResponse { .. }
+            | GeekedError::InvalidResponse(_)
+            | GeekedError::Geetest { .. } => StatusCode::BAD_GATEWAY,
+            GeekedError::NoProxyAvailable(_)
+            | GeekedError::Queue(_)
+            | GeekedError::CircuitOpen { .. }
+            | GeekedError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            GeekedError::DeadlineExceeded(_) => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            code: Some(err.error_code()),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": self.message, "error_code": self.code });
+        (self.status, Json(body)).into_response()
+    }
+}
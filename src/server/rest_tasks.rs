@@ -0,0 +1,194 @@
+//! `POST /tasks` and `GET /tasks/{id}`: asynchronous solves.
+//!
+//! ```text
+//! POST /tasks {"captcha_id": "...", "risk_type": "slide", "callback_url": null}
+//! Idempotency-Key: order-1234
+//! -> 202 {"task_id": 7}
+//!
+//! GET /tasks/7
+//! -> 200 {"task_id": 7, "status": "pending"}
+//! -> 200 {"task_id": 7, "status": "ready", "solution": {...}}
+//! -> 200 {"task_id": 7, "status": "failed", "error": "...", "error_code": 201}
+//! ```
+//!
+//! Resubmitting with the same `Idempotency-Key` answers 200 with the ID of
+//! the task already submitted, so a client that lost the first response
+//! does not trigger a second solve. Results stay retrievable for
+//! [`ServerConfig::result_ttl`](super::ServerConfig::result_ttl) after the
+//! task finishes.
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::auth::header_key;
+use super::solve::{parse_priority, SolveJob};
+use super::tasks::{self, TaskStatus};
+use super::{ApiError, AppState};
+use crate::error::GeekedError;
+use crate::webhook;
+
+/// Longest accepted `Idempotency-Key`.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Request body of `POST /tasks`.
+#[derive(Debug, Deserialize)]
+pub struct SubmitTaskRequest {
+    pub captcha_id: String,
+    pub risk_type: String,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub user_info: Option<String>,
+    /// `interactive` (default) or `batch`
+    #[serde(default)]
+    pub priority: Option<String>,
+    /// URL the result is POSTed to when the task finishes
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+/// `Idempotency-Key` header, if present and usable.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, GeekedError> {
+    let Some(value) = headers.get("idempotency-key") else {
+        return Ok(None);
+    };
+    match value.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => {
+            Ok(Some(key.to_string()))
+        }
+        _ => Err(GeekedError::UnsupportedType(format!(
+            "Idempotency-Key must be 1 to {} visible ASCII characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        ))),
+    }
+}
+
+/// `POST /tasks`
+pub(crate) async fn submit_task(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitTaskRequest>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let key = header_key(&headers);
+    let owner = state
+        .api_keys
+        .authenticate(key)
+        .map_err(GeekedError::from)?;
+    let idempotency_key = idempotency_key(&headers)?;
+
+    // A retry of a submission that already went through
+    if let Some(id) = idempotency_key
+        .as_deref()
+        .and_then(|k| state.tasks.find(owner.as_deref(), k))
+    {
+        return Ok((StatusCode::OK, Json(json!({ "task_id": id }))));
+    }
+
+    let job = SolveJob {
+        captcha_id: request.captcha_id,
+        risk_type: request.risk_type.parse()?,
+        proxy: request.proxy,
+        user_info: request.user_info,
+        priority: parse_priority(request.priority.as_deref())?,
+    };
+    let callback_url = request.callback_url.filter(|url| !url.is_empty());
+    if let Some(url) = &callback_url {
+        webhook::check_url(&state.webhooks, url)?;
+    }
+    let guard = state.start_job()?;
+    let owner = state.api_keys.admit(key).map_err(GeekedError::from)?;
+    let id = tasks::submit(&state, guard, owner, idempotency_key, job, callback_url);
+    Ok((StatusCode::ACCEPTED, Json(json!({ "task_id": id }))))
+}
+
+/// `GET /tasks/{id}`
+pub(crate) async fn get_task(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Result<Json<Value>, ApiError> {
+    let owner = state
+        .api_keys
+        .authenticate(header_key(&headers))
+        .map_err(GeekedError::from)?;
+    let body = match state.tasks.get_for(id, owner.as_deref()) {
+        Some(TaskStatus::Pending) => json!({ "task_id": id, "status": "pending" }),
+        Some(TaskStatus::Ready(seccode)) => json!({
+            "task_id": id,
+            "status": "ready",
+            "solution": seccode,
+        }),
+        Some(TaskStatus::Failed { code, message }) => json!({
+            "task_id": id,
+            "status": "failed",
+            "error": message,
+            "error_code": code,
+        }),
+        None => return Err(ApiError::not_found("task not found or expired")),
+    };
+    Ok(Json(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn submit(idempotency_key: &str) -> Request<Body> {
+        Request::post("/tasks")
+            .header("content-type", "application/json")
+            .header("idempotency-key", idempotency_key)
+            .body(Body::from(r#"{"captcha_id": "x", "risk_type": "slide"}"#))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resubmit_returns_same_task() {
+        let state = AppState::for_test();
+        let (status, first) = call(&state, submit("order-1")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, second) = call(&state, submit("order-1")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(first["task_id"], second["task_id"]);
+
+        let (_, other) = call(&state, submit("order-2")).await;
+        assert_ne!(first["task_id"], other["task_id"]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_task_is_not_found() {
+        let request = Request::get("/tasks/42").body(Body::empty()).unwrap();
+        let (status, body) = call(&AppState::for_test(), request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error_code"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_internal_callback_is_rejected() {
+        for url in ["http://example.com/hook", "https://169.254.169.254/latest"] {
+            let body = json!({"captcha_id": "x", "risk_type": "slide", "callback_url": url});
+            let request = Request::post("/tasks")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let (status, body) = call(&AppState::for_test(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", url);
+            assert_eq!(body["error_code"], 300);
+        }
+    }
+}
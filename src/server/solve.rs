@@ -0,0 +1,171 @@
+//! `POST /solve`.
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::Deserialize;
+
+use super::auth::header_key;
+use super::{ApiError, AppState};
+use crate::error::{GeekedError, Result};
+use crate::priority::{Priority, SlotPermit};
+use crate::{Geeked, RiskType, SecCode};
+
+/// Request body of `POST /solve`.
+#[derive(Debug, Deserialize)]
+pub struct SolveRequest {
+    pub captcha_id: String,
+    pub risk_type: String,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub user_info: Option<String>,
+    /// `interactive` (default) or `batch`
+    #[serde(default)]
+    pub priority: Option<String>,
+}
+
+/// A solve to run, independent of the API it came in through.
+#[derive(Debug, Clone)]
+pub struct SolveJob {
+    pub captcha_id: String,
+    pub risk_type: RiskType,
+    pub proxy: Option<String>,
+    pub user_info: Option<String>,
+    pub priority: Priority,
+}
+
+/// Run a job once a concurrency slot is free, or answer it from the token
+/// pool.
+pub(crate) async fn run_job(state: &AppState, job: SolveJob) -> Result<SecCode> {
+    // Pooled tokens are solved as the pool's builder was set up: without a
+    // user_info binding or the job's own proxy, and only for its risk type
+    if job.user_info.is_none() && job.proxy.is_none() {
+        if let Some(seccode) = state
+            .token_pool
+            .as_ref()
+            .and_then(|pool| pool.try_get_token_for(&job.captcha_id, &job.risk_type))
+        {
+            return Ok(seccode);
+        }
+    }
+    let _permit = acquire_slot(state, job.priority).await;
+    solve_job(state, job).await
+}
+
+/// Wait for a free concurrency slot within `priority`'s share, held until
+/// the permit is dropped.
+pub(crate) async fn acquire_slot(state: &AppState, priority: Priority) -> SlotPermit {
+    state.limiter.acquire(priority).await
+}
+
+/// Run a job without waiting for a slot; callers hold one from [`acquire_slot`].
+///
+/// While Geetest is known to be down, fails at once instead of sending
+/// requests that cannot succeed.
+pub(crate) async fn solve_job(state: &AppState, job: SolveJob) -> Result<SecCode> {
+    if let Some(reason) = state.outage.current() {
+        return Err(GeekedError::ServiceUnavailable(reason));
+    }
+    let profile = state.profiles().for_captcha_id(&job.captcha_id).cloned();
+    let mut builder = Geeked::builder(job.captcha_id, job.risk_type)
+        .constants(state.constants())
+        .strict_responses(state.strict_responses);
+    if let Some(profile) = profile {
+        builder = profile.apply(builder);
+    }
+    if let Some(deadline) = state.solve_deadline {
+        builder = builder.solve_deadline(deadline);
+    }
+    if let Some(proxy) = job.proxy {
+        builder = builder.proxy(proxy);
+    } else if let Some(pool) = &state.proxy_pool {
+        builder = builder.proxy_pool(pool.clone());
+    }
+    if let Some(cache) = &state.image_cache {
+        builder = builder.image_cache(cache.clone());
+    }
+    if let Some(breaker) = &state.circuit_breaker {
+        builder = builder.circuit_breaker(breaker.clone());
+    }
+    if let Some(template) = &state.proxy_session_template {
+        if builder.has_proxy() {
+            builder = builder.proxy_session_template(template.clone());
+        }
+    }
+    if let Some(user_info) = job.user_info {
+        builder = builder.user_info(user_info);
+    }
+    #[cfg(feature = "stats")]
+    if let Some(stats) = &state.stats {
+        builder = builder.stats(stats.clone());
+    }
+
+    let result = async { builder.build().await?.solve().await }.await;
+    state.outage.observe(&result);
+    result
+}
+
+/// Parse an optional priority parameter, defaulting to interactive.
+pub(crate) fn parse_priority(priority: Option<&str>) -> Result<Priority> {
+    priority.map_or(Ok(Priority::default()), str::parse)
+}
+
+/// Solve one captcha and return its SecCode.
+pub(crate) async fn solve(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SolveRequest>,
+) -> std::result::Result<Json<SecCode>, ApiError> {
+    let job = SolveJob {
+        captcha_id: request.captcha_id,
+        risk_type: request.risk_type.parse()?,
+        proxy: request.proxy,
+        user_info: request.user_info,
+        priority: parse_priority(request.priority.as_deref())?,
+    };
+    let _job = state.start_job()?;
+    state
+        .api_keys
+        .admit(header_key(&headers))
+        .map_err(GeekedError::from)?;
+    Ok(Json(run_job(&state, job).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_invalid_risk_type_is_bad_request() {
+        let request = Request::post("/solve")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"captcha_id": "x", "risk_type": "puzzle"}"#))
+            .unwrap();
+
+        let response = router(AppState::for_test()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"], 300);
+    }
+
+    #[tokio::test]
+    async fn test_refused_while_shutting_down() {
+        let state = AppState::for_test();
+        state.jobs.close();
+        let request = Request::post("/solve")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"captcha_id": "x", "risk_type": "slide"}"#))
+            .unwrap();
+
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
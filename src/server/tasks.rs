@@ -0,0 +1,287 @@
+//! Background solve tasks for the polling APIs.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use super::solve::{run_job, SolveJob};
+use super::AppState;
+use crate::shutdown::JobGuard;
+use crate::webhook;
+use crate::SecCode;
+
+/// How long finished tasks stay retrievable by default.
+const DEFAULT_RESULT_TTL: Duration = Duration::from_secs(300);
+
+/// State of a submitted task.
+#[derive(Debug, Clone)]
+pub enum TaskStatus {
+    /// Still solving
+    Pending,
+    /// Solved
+    Ready(SecCode),
+    /// Failed with the given error code and message
+    Failed { code: i32, message: String },
+}
+
+struct TaskEntry {
+    status: TaskStatus,
+    /// Name of the API key that submitted the task
+    owner: Option<String>,
+    finished_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Tasks {
+    entries: HashMap<u64, TaskEntry>,
+    /// Task IDs by owner and idempotency key
+    by_key: HashMap<(Option<String>, String), u64>,
+}
+
+/// In-memory task table.
+pub struct TaskStore {
+    next_id: AtomicU64,
+    result_ttl: Duration,
+    tasks: Mutex<Tasks>,
+}
+
+impl Default for TaskStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESULT_TTL)
+    }
+}
+
+impl TaskStore {
+    /// Store keeping finished tasks for `result_ttl`.
+    pub fn new(result_ttl: Duration) -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            result_ttl,
+            tasks: Mutex::new(Tasks::default()),
+        }
+    }
+
+    fn tasks(&self) -> std::sync::MutexGuard<'_, Tasks> {
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_live(&self, entry: &TaskEntry) -> bool {
+        entry
+            .finished_at
+            .is_none_or(|at| at.elapsed() < self.result_ttl)
+    }
+
+    /// Register a pending task and return its ID.
+    ///
+    /// If `owner` already has a live task with `idempotency_key`, returns
+    /// that task's ID and `false` instead.
+    fn insert(&self, owner: Option<String>, idempotency_key: Option<String>) -> (u64, bool) {
+        let mut tasks = self.tasks();
+        // Drop results nobody picked up
+        let Tasks { entries, by_key } = &mut *tasks;
+        entries.retain(|_, entry| self.is_live(entry));
+        by_key.retain(|_, id| entries.contains_key(id));
+
+        if let Some(key) = &idempotency_key {
+            if let Some(&id) = by_key.get(&(owner.clone(), key.clone())) {
+                return (id, false);
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(key) = &idempotency_key {
+            by_key.insert((owner.clone(), key.clone()), id);
+        }
+        entries.insert(
+            id,
+            TaskEntry {
+                status: TaskStatus::Pending,
+                owner,
+                finished_at: None,
+            },
+        );
+        (id, true)
+    }
+
+    fn finish(&self, id: u64, status: TaskStatus) {
+        if let Some(entry) = self.tasks().entries.get_mut(&id) {
+            entry.status = status;
+            entry.finished_at = Some(Instant::now());
+        }
+    }
+
+    /// Current status of a task, `None` if unknown or expired.
+    pub fn get(&self, id: u64) -> Option<TaskStatus> {
+        self.tasks()
+            .entries
+            .get(&id)
+            .filter(|entry| self.is_live(entry))
+            .map(|entry| entry.status.clone())
+    }
+
+    /// Like [`get`](Self::get), but `None` for tasks of other API keys.
+    pub(crate) fn get_for(&self, id: u64, owner: Option<&str>) -> Option<TaskStatus> {
+        self.tasks()
+            .entries
+            .get(&id)
+            .filter(|entry| self.is_live(entry) && entry.owner.as_deref() == owner)
+            .map(|entry| entry.status.clone())
+    }
+
+    /// Live task `owner` submitted with `idempotency_key`, if any.
+    pub(crate) fn find(&self, owner: Option<&str>, idempotency_key: &str) -> Option<u64> {
+        let tasks = self.tasks();
+        let key = (owner.map(str::to_string), idempotency_key.to_string());
+        let id = *tasks.by_key.get(&key)?;
+        tasks
+            .entries
+            .get(&id)
+            .filter(|entry| self.is_live(entry))
+            .map(|_| id)
+    }
+}
+
+/// Start solving `job` in the background and return the task ID.
+///
+/// `guard` keeps shutdown waiting for the task. The outcome is POSTed to
+/// `callback_url`, if given, once it is known. If `owner` already has a live
+/// task under `idempotency_key`, that task's ID is returned and nothing new
+/// is solved.
+pub(crate) fn submit(
+    state: &AppState,
+    guard: JobGuard,
+    owner: Option<String>,
+    idempotency_key: Option<String>,
+    job: SolveJob,
+    callback_url: Option<String>,
+) -> u64 {
+    let (id, created) = state.tasks.insert(owner, idempotency_key);
+    if !created {
+        return id;
+    }
+    let state = state.clone();
+    tokio::spawn(async move {
+        let _guard = guard;
+        let status = match run_job(&state, job).await {
+            Ok(seccode) => TaskStatus::Ready(seccode),
+            Err(e) => {
+                tracing::debug!("Task {} failed: {}", id, e);
+                TaskStatus::Failed {
+                    code: e.error_code(),
+                    message: e.to_string(),
+                }
+            }
+        };
+        state.tasks.finish(id, status.clone());
+        if let Some(url) = callback_url {
+            let body = callback_body(id, &status).to_string();
+            webhook::deliver(&state.webhooks, &url, &format!("task {}", id), &body).await;
+        }
+    });
+    id
+}
+
+/// Callback body for a finished task:
+///
+/// ```text
+/// {"taskId": 7, "status": "ready", "solution": {...}}
+/// {"taskId": 7, "status": "failed", "errorCode": 201, "error": "..."}
+/// ```
+fn callback_body(id: u64, status: &TaskStatus) -> Value {
+    match status {
+        TaskStatus::Ready(seccode) => json!({
+            "taskId": id,
+            "status": "ready",
+            "solution": seccode,
+        }),
+        TaskStatus::Failed { code, message } => json!({
+            "taskId": id,
+            "status": "failed",
+            "errorCode": code,
+            "error": message,
+        }),
+        TaskStatus::Pending => json!({ "taskId": id, "status": "processing" }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_lifecycle() {
+        let store = TaskStore::default();
+        let (id, _) = store.insert(Some("team-a".into()), None);
+        assert!(matches!(store.get(id), Some(TaskStatus::Pending)));
+
+        store.finish(
+            id,
+            TaskStatus::Failed {
+                code: 201,
+                message: "fail".into(),
+            },
+        );
+        assert!(matches!(
+            store.get(id),
+            Some(TaskStatus::Failed { code: 201, .. })
+        ));
+        assert!(store.get(id + 1).is_none());
+        assert!(store.get_for(id, Some("team-a")).is_some());
+        assert!(store.get_for(id, Some("team-b")).is_none());
+    }
+
+    #[test]
+    fn test_callback_body() {
+        let failed = callback_body(
+            7,
+            &TaskStatus::Failed {
+                code: 201,
+                message: "fail".into(),
+            },
+        );
+        assert_eq!(failed["taskId"], 7);
+        assert_eq!(failed["status"], "failed");
+        assert_eq!(failed["errorCode"], 201);
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let store = TaskStore::default();
+        let (id, created) = store.insert(Some("team-a".into()), Some("order-1".into()));
+        assert!(created);
+        assert_eq!(
+            store.insert(Some("team-a".into()), Some("order-1".into())),
+            (id, false)
+        );
+        assert_eq!(store.find(Some("team-a"), "order-1"), Some(id));
+        // Keys are scoped to their owner
+        assert!(
+            store
+                .insert(Some("team-b".into()), Some("order-1".into()))
+                .1
+        );
+        assert!(store.find(Some("team-b"), "order-2").is_none());
+    }
+
+    #[test]
+    fn test_results_expire() {
+        let store = TaskStore::new(Duration::ZERO);
+        let (id, _) = store.insert(None, Some("order-1".into()));
+        // Pending tasks never expire
+        assert!(store.get(id).is_some());
+
+        store.finish(
+            id,
+            TaskStatus::Failed {
+                code: 201,
+                message: "fail".into(),
+            },
+        );
+        assert!(store.get(id).is_none());
+        assert!(store.find(None, "order-1").is_none());
+        assert!(store.insert(None, Some("order-1".into())).1);
+    }
+}
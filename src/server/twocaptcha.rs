@@ -0,0 +1,192 @@
+//! 2captcha-compatible `in.php`/`res.php` API.
+//!
+//! Lets bots built against 2captcha's `geetest_v4` method use this server
+//! by changing only the provider URL. The `key` parameter is only checked
+//! if the server has API keys configured.
+//!
+//! `risk_type` and `priority` are extension parameters; without them the
+//! server's default captcha type and interactive priority are used.
+
+use std::collections::HashMap;
+
+use axum::extract::{Form, Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::{json, Value};
+
+use super::solve::{parse_priority, SolveJob};
+use super::tasks::{self, TaskStatus};
+use super::AppState;
+use crate::webhook;
+use crate::RiskType;
+
+/// Balance reported to clients; a self-hosted instance has no credit limit.
+pub(crate) const UNLIMITED_BALANCE: f64 = 999_999.0;
+
+type Params = HashMap<String, String>;
+
+/// `GET /in.php`
+pub(crate) async fn in_get(
+    State(state): State<AppState>,
+    Query(params): Query<Params>,
+) -> Response {
+    submit(&state, &params)
+}
+
+/// `POST /in.php`
+pub(crate) async fn in_post(State(state): State<AppState>, Form(params): Form<Params>) -> Response {
+    submit(&state, &params)
+}
+
+/// `GET /res.php`
+pub(crate) async fn res(State(state): State<AppState>, Query(params): Query<Params>) -> Response {
+    let json = wants_json(&params);
+    let key = params.get("key").map(String::as_str);
+
+    match params.get("action").map(String::as_str) {
+        Some("get") => {}
+        Some("getbalance") => {
+            return match state.api_keys.remaining(key) {
+                Ok(remaining) => {
+                    let balance = remaining.map_or(UNLIMITED_BALANCE, |left| left as f64);
+                    reply(json, true, json!(balance))
+                }
+                Err(denied) => reply(json, false, json!(denied.provider_code())),
+            };
+        }
+        Some("reportbad") | Some("reportgood") => {
+            return reply(json, true, json!("OK_REPORT_RECORDED"))
+        }
+        _ => return reply(json, false, json!("ERROR_BAD_PARAMETERS")),
+    }
+
+    let Some(id) = params.get("id").and_then(|id| id.parse::<u64>().ok()) else {
+        return reply(json, false, json!("ERROR_WRONG_CAPTCHA_ID"));
+    };
+
+    let owner = match state.api_keys.authenticate(key) {
+        Ok(owner) => owner,
+        Err(denied) => return reply(json, false, json!(denied.provider_code())),
+    };
+    match state.tasks.get_for(id, owner.as_deref()) {
+        Some(TaskStatus::Pending) => reply(json, false, json!("CAPCHA_NOT_READY")),
+        Some(TaskStatus::Ready(seccode)) => reply(json, true, json!(seccode)),
+        Some(TaskStatus::Failed { .. }) => reply(json, false, json!("ERROR_CAPTCHA_UNSOLVABLE")),
+        None => reply(json, false, json!("ERROR_WRONG_CAPTCHA_ID")),
+    }
+}
+
+fn submit(state: &AppState, params: &Params) -> Response {
+    let json = wants_json(params);
+
+    if params.get("method").map(String::as_str) != Some("geetest_v4") {
+        return reply(json, false, json!("ERROR_BAD_PARAMETERS"));
+    }
+    let Some(captcha_id) = params.get("captcha_id").filter(|id| !id.is_empty()) else {
+        return reply(json, false, json!("ERROR_BAD_PARAMETERS"));
+    };
+    let risk_type = match params.get("risk_type") {
+        Some(name) => match name.parse::<RiskType>() {
+            Ok(risk_type) => risk_type,
+            Err(_) => return reply(json, false, json!("ERROR_BAD_PARAMETERS")),
+        },
+        None => state.default_risk_type.clone(),
+    };
+    let Ok(priority) = parse_priority(params.get("priority").map(String::as_str)) else {
+        return reply(json, false, json!("ERROR_BAD_PARAMETERS"));
+    };
+
+    let job = SolveJob {
+        captcha_id: captcha_id.clone(),
+        risk_type,
+        proxy: params.get("proxy").map(|proxy| {
+            let scheme = params
+                .get("proxytype")
+                .map(|kind| kind.to_lowercase())
+                .unwrap_or_else(|| "http".to_string());
+            format!("{}://{}", scheme, proxy)
+        }),
+        user_info: params.get("user_info").cloned(),
+        priority,
+    };
+
+    let pingback = params
+        .get("pingback")
+        .filter(|url| !url.is_empty())
+        .cloned();
+    if let Some(Err(_)) = pingback
+        .as_deref()
+        .map(|url| webhook::check_url(&state.webhooks, url))
+    {
+        return reply(json, false, json!("ERROR_BAD_PARAMETERS"));
+    }
+    let Ok(guard) = state.start_job() else {
+        return reply(json, false, json!("ERROR_NO_SLOT_AVAILABLE"));
+    };
+    let owner = match state.api_keys.admit(params.get("key").map(String::as_str)) {
+        Ok(owner) => owner,
+        Err(denied) => return reply(json, false, json!(denied.provider_code())),
+    };
+    let id = tasks::submit(state, guard, owner, None, job, pingback);
+    reply(json, true, json!(id.to_string()))
+}
+
+fn wants_json(params: &Params) -> bool {
+    params.get("json").map(String::as_str) == Some("1")
+}
+
+/// Format a reply as `{"status": 0|1, "request": ...}` or as plain text
+/// (`OK|<request>` on success, the bare error code otherwise).
+fn reply(json: bool, ok: bool, request: Value) -> Response {
+    if json {
+        return Json(json!({ "status": u8::from(ok), "request": request })).into_response();
+    }
+
+    let text = match request {
+        Value::String(s) => s,
+        other => other.to_string(),
+    };
+    if ok {
+        format!("OK|{}", text).into_response()
+    } else {
+        text.into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn get(uri: &str) -> String {
+        let state = AppState::for_test();
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_php_validates_parameters() {
+        assert_eq!(
+            get("/in.php?key=x&method=userrecaptcha").await,
+            "ERROR_BAD_PARAMETERS"
+        );
+        assert_eq!(
+            get("/in.php?key=x&method=geetest_v4&json=1").await,
+            r#"{"request":"ERROR_BAD_PARAMETERS","status":0}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_res_php_unknown_task() {
+        assert_eq!(
+            get("/res.php?key=x&action=get&id=42").await,
+            "ERROR_WRONG_CAPTCHA_ID"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! What the solving browser looked like, for the request that uses the token.
+//!
+//! Sites check the `pass_token` together with the request carrying it. A
+//! login sent with a different `User-Agent`, language or IP than the solve
+//! stands out; [`SessionBundle`] holds what the solve used so the follow-up
+//! request can match it:
+//!
+//! ```ignore
+//! let (seccode, bundle) = solver.solve_with_bundle().await?;
+//! let mut request = http.post("https://example.com/login");
+//! for (name, value) in &bundle.headers {
+//!     request = request.header(name, value);
+//! }
+//! if let Some(cookies) = bundle.cookie_header() {
+//!     request = request.header("Cookie", cookies);
+//! }
+//! ```
+
+use std::sync::{Mutex, PoisonError};
+
+use serde::Serialize;
+
+use crate::fingerprint::{Browser, BrowserIdentity, FingerprintProfile};
+use crate::transport::TransportResponse;
+
+/// Headers, cookies and proxy of a solve.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionBundle {
+    pub user_agent: String,
+    /// Headers the solving browser sends on every request, `User-Agent`
+    /// included, in the order it sends them
+    pub headers: Vec<(String, String)>,
+    /// Cookies set during the solve, as name and value
+    pub cookies: Vec<(String, String)>,
+    /// Proxy the solve went through, credentials included
+    pub proxy: Option<String>,
+}
+
+impl SessionBundle {
+    /// Headers matching `profile`, sent as `user_agent`.
+    pub(crate) fn new(
+        profile: &FingerprintProfile,
+        user_agent: &str,
+        referer: Option<&str>,
+        origin: Option<&str>,
+    ) -> Self {
+        let identity = BrowserIdentity::from_user_agent(user_agent);
+        let mut headers = vec![("User-Agent".to_string(), user_agent.to_string())];
+        if let Some(brands) = identity.and_then(|identity| client_hint_brands(user_agent, identity))
+        {
+            headers.push(("sec-ch-ua".to_string(), brands));
+            let mobile = if profile.mobile { "?1" } else { "?0" };
+            headers.push(("sec-ch-ua-mobile".to_string(), mobile.to_string()));
+            let platform = client_hint_platform(user_agent);
+            headers.push((
+                "sec-ch-ua-platform".to_string(),
+                format!("\"{}\"", platform),
+            ));
+        }
+        let firefox = identity.is_some_and(|identity| identity.browser == Browser::Firefox);
+        headers.push((
+            "Accept-Language".to_string(),
+            accept_language(&profile.language, firefox),
+        ));
+        if let Some(referer) = referer {
+            headers.push(("Referer".to_string(), referer.to_string()));
+        }
+        if let Some(origin) = origin {
+            headers.push(("Origin".to_string(), origin.to_string()));
+        }
+        Self {
+            user_agent: user_agent.to_string(),
+            headers,
+            ..Default::default()
+        }
+    }
+
+    /// The cookies as a `Cookie` header value, if any were set.
+    pub fn cookie_header(&self) -> Option<String> {
+        if self.cookies.is_empty() {
+            return None;
+        }
+        let pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+}
+
+/// `Accept-Language` a browser set to `language` sends, e.g.
+/// `zh-CN,zh;q=0.9`.
+fn accept_language(language: &str, firefox: bool) -> String {
+    let quality = if firefox { "0.5" } else { "0.9" };
+    match language.split_once('-') {
+        Some((primary, _)) => format!("{},{};q={}", language, primary, quality),
+        None => language.to_string(),
+    }
+}
+
+/// `sec-ch-ua` of Google Chrome, which shuffles its brands and GREASE
+/// entry by major version. Other browsers send no client hints, and
+/// Chromium forks name themselves, so they get none either.
+fn client_hint_brands(user_agent: &str, identity: BrowserIdentity) -> Option<String> {
+    const GREASE_CHARS: [&str; 11] = [" ", "(", ":", "-", ".", "/", ")", ";", "=", "?", "_"];
+    const GREASE_VERSIONS: [&str; 3] = ["8", "99", "24"];
+    const ORDERS: [[usize; 3]; 6] = [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ];
+
+    if identity.browser != Browser::Chrome
+        || ["Edg/", "OPR/"].iter().any(|t| user_agent.contains(t))
+    {
+        return None;
+    }
+    let seed = identity.major_version as usize;
+    let version = identity.major_version.to_string();
+    let grease = format!(
+        "Not{}A{}Brand",
+        GREASE_CHARS[seed % GREASE_CHARS.len()],
+        GREASE_CHARS[(seed + 1) % GREASE_CHARS.len()]
+    );
+    let mut brands: [(String, String); 3] = Default::default();
+    let order = ORDERS[seed % ORDERS.len()];
+    brands[order[0]] = (
+        grease,
+        GREASE_VERSIONS[seed % GREASE_VERSIONS.len()].to_string(),
+    );
+    brands[order[1]] = ("Chromium".to_string(), version.clone());
+    brands[order[2]] = ("Google Chrome".to_string(), version);
+    let brands: Vec<String> = brands
+        .iter()
+        .map(|(brand, version)| format!("\"{}\";v=\"{}\"", brand, version))
+        .collect();
+    Some(brands.join(", "))
+}
+
+/// `sec-ch-ua-platform` of the OS named by `user_agent`.
+fn client_hint_platform(user_agent: &str) -> &'static str {
+    if user_agent.contains("Windows") {
+        "Windows"
+    } else if user_agent.contains("Android") {
+        "Android"
+    } else if user_agent.contains("Mac OS X") {
+        "macOS"
+    } else if user_agent.contains("CrOS") {
+        "Chrome OS"
+    } else if user_agent.contains("Linux") {
+        "Linux"
+    } else {
+        "Unknown"
+    }
+}
+
+/// Cookies and proxy of a running solve, collected for its bundle.
+#[derive(Debug, Default)]
+pub(crate) struct SessionCapture {
+    cookies: Mutex<Vec<(String, String)>>,
+    proxy: Mutex<Option<String>>,
+}
+
+impl SessionCapture {
+    /// Keep the cookies `response` sets; a later cookie replaces an earlier
+    /// one of the same name.
+    pub(crate) fn record(&self, response: &TransportResponse) {
+        let mut cookies = self.cookies.lock().unwrap_or_else(PoisonError::into_inner);
+        for header in &response.set_cookies {
+            let pair = header.split(';').next().unwrap_or_default();
+            let Some((name, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            cookies.retain(|(existing, _)| existing != name);
+            cookies.push((name.to_string(), value.trim().to_string()));
+        }
+    }
+
+    /// Remember the proxy of the solve's own proxy session.
+    pub(crate) fn set_proxy(&self, proxy: String) {
+        *self.proxy.lock().unwrap_or_else(PoisonError::into_inner) = Some(proxy);
+    }
+
+    /// Fill `bundle` with what was collected; `proxy` unless the solve had
+    /// its own.
+    pub(crate) fn finish(&self, mut bundle: SessionBundle, proxy: Option<String>) -> SessionBundle {
+        bundle.cookies =
+            std::mem::take(&mut *self.cookies.lock().unwrap_or_else(PoisonError::into_inner));
+        bundle.proxy = self
+            .proxy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .or(proxy);
+        bundle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHROME_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+        (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+
+    #[test]
+    fn test_headers() {
+        let profile = FingerprintProfile {
+            language: "zh-CN".into(),
+            ..Default::default()
+        };
+        let bundle = SessionBundle::new(
+            &profile,
+            CHROME_WINDOWS,
+            Some("https://example.com/login"),
+            None,
+        );
+        assert_eq!(
+            bundle.headers,
+            [
+                ("User-Agent", CHROME_WINDOWS),
+                (
+                    "sec-ch-ua",
+                    r#""Google Chrome";v="131", "Chromium";v="131", "Not_A Brand";v="24""#
+                ),
+                ("sec-ch-ua-mobile", "?0"),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("Accept-Language", "zh-CN,zh;q=0.9"),
+                ("Referer", "https://example.com/login"),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0";
+        let profile = FingerprintProfile {
+            language: "en-US".into(),
+            ..Default::default()
+        };
+        let bundle = SessionBundle::new(&profile, firefox, None, None);
+        assert_eq!(
+            bundle.headers[1],
+            ("Accept-Language".to_string(), "en-US,en;q=0.5".to_string())
+        );
+        assert_eq!(bundle.headers.len(), 2);
+    }
+
+    #[test]
+    fn test_capture() {
+        let capture = SessionCapture::default();
+        capture.record(&TransportResponse {
+            status: 200,
+            set_cookies: vec![
+                "captcha_v4_user=abc; Path=/; Secure".into(),
+                "malformed".into(),
+                "lang=zh".into(),
+            ],
+            body: Vec::new(),
+        });
+        capture.record(&TransportResponse {
+            status: 200,
+            set_cookies: vec!["captcha_v4_user=def; HttpOnly".into()],
+            body: Vec::new(),
+        });
+        let bundle = capture.finish(SessionBundle::default(), Some("http://host:1".into()));
+        assert_eq!(
+            bundle.cookie_header().as_deref(),
+            Some("lang=zh; captcha_v4_user=def")
+        );
+        assert_eq!(bundle.proxy.as_deref(), Some("http://host:1"));
+        assert_eq!(SessionBundle::default().cookie_header(), None);
+    }
+}
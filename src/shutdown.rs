@@ -0,0 +1,141 @@
+//! Graceful shutdown for the server and worker.
+//!
+//! On SIGTERM (or Ctrl-C) new jobs are refused, and solves already running,
+//! including multi-round verify loops, get until the drain timeout to finish.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// Resolves when the process is asked to stop (SIGTERM or Ctrl-C).
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutdown requested");
+}
+
+#[derive(Default)]
+struct Inner {
+    active: AtomicUsize,
+    closed: AtomicBool,
+    idle: Notify,
+}
+
+/// Counts running jobs and stops accepting new ones once draining starts.
+#[derive(Clone, Default)]
+pub(crate) struct JobTracker {
+    inner: Arc<Inner>,
+}
+
+/// A running job; finished when dropped.
+pub(crate) struct JobGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if self.inner.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+impl JobTracker {
+    /// Register a new job, or `None` if the tracker is draining.
+    pub(crate) fn start(&self) -> Option<JobGuard> {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+        let guard = JobGuard {
+            inner: self.inner.clone(),
+        };
+        if self.inner.closed.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Refuse new jobs from now on.
+    pub(crate) fn close(&self) {
+        self.inner.closed.store(true, Ordering::Release);
+    }
+
+    /// Refuse new jobs and wait up to `timeout` for running ones.
+    ///
+    /// Returns the number of jobs still running when the timeout hit.
+    pub(crate) async fn drain(&self, timeout: Duration) -> usize {
+        self.close();
+        let idle = async {
+            loop {
+                let notified = self.inner.idle.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.inner.active.load(Ordering::Acquire) == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+
+        let active = self.inner.active.load(Ordering::Acquire);
+        if active > 0 {
+            tracing::info!("Waiting up to {:?} for {} running jobs", timeout, active);
+        }
+        match tokio::time::timeout(timeout, idle).await {
+            Ok(()) => 0,
+            Err(_) => self.inner.active.load(Ordering::Acquire),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running_jobs() {
+        let tracker = JobTracker::default();
+        let job = tracker.start().unwrap();
+
+        let finisher = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(job);
+        });
+        assert_eq!(tracker.drain(Duration::from_secs(5)).await, 0);
+        finisher.await.unwrap();
+
+        assert!(tracker.start().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_drain_timeout_reports_stragglers() {
+        let tracker = JobTracker::default();
+        let _job = tracker.start().unwrap();
+        assert_eq!(tracker.drain(Duration::from_millis(10)).await, 1);
+    }
+}
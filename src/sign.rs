@@ -1,9 +1,14 @@
 //! W parameter generation and LotParser for Geetest captcha.
 
-use crate::crypto::{encrypt_w, generate_pow};
+use crate::behavior::{BehaviorBundle, EventCounts};
+use crate::crypto::{encrypt_w, generate_pow, PowResult};
 use crate::error::{GeekedError, Result};
-use crate::models::{Constants, LoadResponse, RiskType};
+use crate::fingerprint::FingerprintProfile;
+use crate::models::{ClientType, Constants, LoadResponse, RiskType};
+use crate::trajectory::Trajectory;
+use rand::RngCore;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 
 /// Parser for generating lot-number-derived dictionary values.
@@ -56,15 +61,16 @@ impl LotParser {
 
             for sub in subs {
                 if let Some(caps) = slice_re.captures(sub) {
-                    let start: i32 = caps
-                        .get(1)
-                        .and_then(|m| m.as_str().parse().ok())
-                        .unwrap_or(0);
-                    let end: i32 = caps
-                        .get(2)
-                        .and_then(|m| m.as_str().parse().ok())
-                        .unwrap_or(0);
-                    group.push(vec![start, end]);
+                    let index = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<i32>().ok());
+                    match (index(1), index(2)) {
+                        (Some(start), Some(end)) if start <= end => group.push(vec![start, end]),
+                        _ => {
+                            return Err(GeekedError::Encryption(format!(
+                                "Invalid slice in mapping: {}",
+                                sub
+                            )))
+                        }
+                    }
                 }
             }
 
@@ -77,8 +83,12 @@ impl LotParser {
     }
 
     /// Build a string from parsed pattern and lot number.
+    ///
+    /// Indices count characters, not bytes, and slices past the end of the
+    /// lot number are clamped to it, like Python slicing.
     fn build_string(parsed: &[Vec<Vec<i32>>], lot_number: &str) -> String {
         let chars: Vec<char> = lot_number.chars().collect();
+        let index = |i: Option<&i32>| i.and_then(|&i| usize::try_from(i).ok());
 
         parsed
             .iter()
@@ -86,14 +96,13 @@ impl LotParser {
                 group
                     .iter()
                     .map(|slice| {
-                        let start = slice[0] as usize;
-                        let end = if slice.len() > 1 {
-                            (slice[1] + 1) as usize
-                        } else {
-                            start + 1
-                        };
+                        let start = index(slice.first()).unwrap_or(0).min(chars.len());
+                        let end = index(slice.get(1))
+                            .unwrap_or(start)
+                            .saturating_add(1)
+                            .clamp(start, chars.len());
                         chars
-                            .get(start..end.min(chars.len()))
+                            .get(start..end)
                             .map(|s| s.iter().collect::<String>())
                             .unwrap_or_default()
                     })
@@ -113,92 +122,134 @@ impl LotParser {
         let key_str = Self::build_string(&self.lot, lot_number);
         let value_str = Self::build_string(&self.lot_res, lot_number);
 
-        let parts: Vec<&str> = key_str.split('.').collect();
-
-        // Build nested structure
-        let mut result = Value::Object(Map::new());
-
-        if parts.is_empty() {
-            return result;
-        }
-
-        // Navigate to create nested structure
-        let mut current = &mut result;
-        for (idx, part) in parts.iter().enumerate() {
-            if idx == parts.len() - 1 {
-                // Last part gets the value
-                if let Value::Object(map) = current {
-                    map.insert((*part).to_string(), Value::String(value_str.clone()));
-                }
-            } else {
-                // Create nested object
-                if let Value::Object(map) = current {
-                    map.entry((*part).to_string())
-                        .or_insert(Value::Object(Map::new()));
-                    current = map.get_mut(*part).unwrap();
-                }
-            }
-        }
-
-        result
+        // Nest from the innermost part, which holds the value
+        key_str
+            .split('.')
+            .rev()
+            .fold(Value::String(value_str), |inner, part| {
+                let mut map = Map::new();
+                map.insert(part.to_string(), inner);
+                Value::Object(map)
+            })
     }
 }
 
+/// Client-dependent parts of the `w` payload.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadOptions {
+    /// Page flavor; the h5 script reports touch input
+    pub client_type: ClientType,
+    /// Device whose environment checks the payload reports
+    pub fingerprint: FingerprintProfile,
+}
+
 /// Generate the W parameter for captcha verification.
 pub fn generate_w_parameter(
     data: &LoadResponse,
     captcha_id: &str,
-    _risk_type: RiskType,
+    risk_type: &RiskType,
+    constants: &Constants,
+    solver_result: Option<SolverResult>,
+) -> Result<String> {
+    generate_w_parameter_with(
+        data,
+        captcha_id,
+        risk_type,
+        constants,
+        solver_result,
+        &PayloadOptions::default(),
+    )
+}
+
+/// Generate the W parameter, with the client-dependent fields of `options`.
+pub fn generate_w_parameter_with(
+    data: &LoadResponse,
+    captcha_id: &str,
+    risk_type: &RiskType,
     constants: &Constants,
     solver_result: Option<SolverResult>,
+    options: &PayloadOptions,
 ) -> Result<String> {
+    let payload = build_w_payload(
+        data,
+        captcha_id,
+        risk_type,
+        constants,
+        solver_result,
+        options,
+    )?;
+    encrypt_w(&serde_json::to_string(&payload)?, &data.pt)
+}
+
+/// Build the plaintext payload that [`generate_w_parameter_with`] encrypts.
+pub fn build_w_payload(
+    data: &LoadResponse,
+    captcha_id: &str,
+    _risk_type: &RiskType,
+    constants: &Constants,
+    solver_result: Option<SolverResult>,
+    options: &PayloadOptions,
+) -> Result<Value> {
     let lot_number = &data.lot_number;
 
     // Parse the mapping to create LotParser
     let lot_parser = LotParser::new(&constants.mapping)?;
 
     // Generate PoW
-    let pow_result = generate_pow(
-        lot_number,
-        captcha_id,
-        &data.pow_detail.hashfunc,
-        &data.pow_detail.version,
-        data.pow_detail.bits,
-        &data.pow_detail.datetime,
-    );
-
-    // Build base payload
+    #[cfg(feature = "metrics")]
+    let pow_started = std::time::Instant::now();
+    let pow_result = match data.pow_detail.as_ref().filter(|pow| pow.is_required()) {
+        Some(pow) => tracing::debug_span!("pow", bits = pow.bits).in_scope(|| {
+            generate_pow(
+                lot_number,
+                captcha_id,
+                &pow.hashfunc,
+                &pow.version,
+                pow.bits,
+                &pow.datetime,
+            )
+        })?,
+        None => PowResult {
+            pow_msg: String::new(),
+            pow_sign: String::new(),
+        },
+    };
+    #[cfg(feature = "metrics")]
+    crate::metrics::metrics()
+        .pow_duration
+        .observe(pow_started.elapsed().as_secs_f64());
+
+    // Build base payload; used whole when the version's template is unknown
+    let fingerprint = &options.fingerprint;
+    // The script's own device ID, if it still has one, beats the profile's
+    let device_id = match constants.device_id.as_str() {
+        "" => fingerprint.device_id.as_str(),
+        device_id => device_id,
+    };
     let mut payload = json!({
         "geetest": "captcha",
-        "lang": "zh",
-        "ep": "123",
-        "biht": "1426265548",
-        "device_id": "",  // Go version uses empty string
+        "lang": fingerprint.payload_lang(),
+        "ep": constants.protocol.ep,
+        "biht": constants.protocol.biht,
+        "device_id": device_id,
         "lot_number": lot_number,
         "pow_msg": pow_result.pow_msg,
         "pow_sign": pow_result.pow_sign,
-        "em": {
-            "cp": 0,
-            "ek": "11",
-            "nt": 0,
-            "ph": 0,
-            "sc": 0,
-            "si": 0,
-            "wd": 1
-        },
-        "gee_guard": {
-            "roe": {
-                "auh": "3",
-                "aup": "3",
-                "cdc": "3",
-                "egp": "3",
-                "res": "3",
-                "rew": "3",
-                "sep": "3",
-                "snh": "3"
-            }
-        }
+        "em": fingerprint.em,
+        "gee_guard": fingerprint.gee_guard,
     });
+    // Fields of the script's payload literal; the rest is added after it
+    let literal: Vec<String> = payload
+        .as_object()
+        .map(|map| map.keys().cloned().collect())
+        .unwrap_or_default();
+
+    // The mobile script flags touch input in the environment checks
+    if options.client_type == ClientType::H5 {
+        if let Some(em) = payload.get_mut("em").and_then(Value::as_object_mut) {
+            em.insert("tc".to_string(), json!(1));
+        }
+    }
 
     // Merge abo constants
     if let Value::Object(ref mut map) = payload {
@@ -215,26 +266,41 @@ pub fn generate_w_parameter(
         }
     }
 
-    // Add solver-specific fields
+    // Add solver-specific fields, and count the input events behind them
+    let mut events = EventCounts::default();
     if let Some(result) = solver_result {
         match result {
-            SolverResult::Slide { left } => {
-                let passtime = rand::random::<u32>() % 600 + 600; // 600-1200ms
+            SolverResult::Slide { left, top, .. } => {
+                // The drag that placed the piece, taking passtime
+                let track = Trajectory::slide(left);
+                events = EventCounts::from_trajectory(&track);
+                let passtime = track.duration();
                 let userresponse = left / 1.0059466666666665 + 2.0;
 
                 if let Value::Object(ref mut map) = payload {
                     map.insert("passtime".to_string(), json!(passtime));
                     map.insert("setLeft".to_string(), json!(left));
                     map.insert("userresponse".to_string(), json!(userresponse));
+                    // 2D variants answer with both coordinates, scaled alike
+                    if let Some(top) = top {
+                        let userresponse_y = top / 1.0059466666666665 + 2.0;
+                        map.insert("setTop".to_string(), json!(top));
+                        map.insert(
+                            "userresponse".to_string(),
+                            json!([userresponse, userresponse_y]),
+                        );
+                    }
                 }
             }
             SolverResult::Gobang { response } => {
+                events = EventCounts::clicks(response.len() as u32);
                 if let Value::Object(ref mut map) = payload {
                     map.insert("userresponse".to_string(), json!(response));
                 }
             }
-            SolverResult::Icon { positions } => {
-                let passtime = rand::random::<u32>() % 600 + 600;
+            SolverResult::Icon { positions, .. } => {
+                events = EventCounts::clicks(positions.len() as u32);
+                let passtime = crate::entropy::rng().next_u32() % 600 + 600;
 
                 if let Value::Object(ref mut map) = payload {
                     map.insert("passtime".to_string(), json!(passtime));
@@ -242,9 +308,17 @@ pub fn generate_w_parameter(
                 }
             }
             SolverResult::Ai => {
-                // AI/invisible captcha doesn't need additional fields
+                // Invisible captchas are scored on page behavior instead of an answer
+                let behavior = BehaviorBundle::generate_for_viewport(fingerprint.screen_size());
+                events = EventCounts::from_bundle(&behavior);
+
+                if let Value::Object(ref mut map) = payload {
+                    map.insert("passtime".to_string(), json!(behavior.dwell_ms));
+                    map.insert("behavior".to_string(), behavior.to_value());
+                }
             }
             SolverResult::Svg { userresponse, passtime } => {
+                events = EventCounts::clicks(1);
                 if let Value::Object(ref mut map) = payload {
                     map.insert("passtime".to_string(), json!(passtime));
                     map.insert("userresponse".to_string(), json!(userresponse));
@@ -252,21 +326,66 @@ pub fn generate_w_parameter(
             }
         }
     }
+    if let Some(em) = payload.get_mut("em").and_then(Value::as_object_mut) {
+        events.write_to(em);
+    }
+    if !constants.payload_fields.is_empty() {
+        payload = apply_template(payload, &constants.payload_fields, &literal);
+    }
+
+    Ok(payload)
+}
 
-    // Serialize and encrypt
-    let payload_str = serde_json::to_string(&payload)?;
-    encrypt_w(&payload_str, &data.pt)
+/// Keep the fields of `payload` that the script's payload template names,
+/// and those added after the `literal` fields (abo, lot and solver values)
+/// that the template does not mention.
+fn apply_template(payload: Value, template: &[String], literal: &[String]) -> Value {
+    let Value::Object(mut payload) = payload else {
+        return payload;
+    };
+    let mut kept = Map::new();
+    for field in template {
+        match payload.remove(field) {
+            Some(value) => {
+                kept.insert(field.clone(), value);
+            }
+            None => tracing::debug!("No value for payload template field `{}`", field),
+        }
+    }
+    for (field, value) in payload {
+        if !literal.contains(&field) {
+            kept.insert(field, value);
+        }
+    }
+    Value::Object(kept)
 }
 
 /// Result from a captcha solver.
-#[derive(Debug, Clone)]
+///
+/// Serializes with its captcha type as `type`, e.g.
+/// `{"type": "slide", "left": 120.5, "top": null}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum SolverResult {
-    /// Slide captcha result with X position.
-    Slide { left: f64 },
+    /// Slide captcha result with X position, and Y position for the 2D
+    /// variant, as sent to the API.
+    Slide {
+        left: f64,
+        top: Option<f64>,
+        /// Top-left corner of the piece in background pixels, for driving
+        /// a real slider
+        #[serde(default)]
+        raw: [f64; 2],
+    },
     /// Gobang result with move positions.
     Gobang { response: Vec<Vec<i32>> },
-    /// Icon result with click positions.
-    Icon { positions: Vec<Vec<f64>> },
+    /// Icon result with click positions, as sent to the API.
+    Icon {
+        positions: Vec<Vec<f64>>,
+        /// Click positions in image pixels, for clicking a real page
+        #[serde(default)]
+        raw: Vec<[f64; 2]>,
+    },
     /// AI/invisible captcha (no user interaction).
     Ai,
     /// SVG icon captcha result with grid position and timing.
@@ -307,4 +426,204 @@ mod tests {
         // First group should have 2 slices (concatenated with +)
         assert_eq!(result[0].len(), 2);
     }
+
+    /// `get_dict` as the Python reference computes it, where `n[a:b]`
+    /// is `lot_number[a:b + 1]` and slicing past the end is clamped.
+    fn python_get_dict(key: &[Vec<(u32, u32)>], value: &[(u32, u32)], lot_number: &str) -> Value {
+        let chars: Vec<char> = lot_number.chars().collect();
+        let slice = |&(a, b): &(u32, u32)| -> String {
+            let start = (a as usize).min(chars.len());
+            let end = (b as usize + 1).min(chars.len()).max(start);
+            chars[start..end].iter().collect()
+        };
+        let value: String = value.iter().map(slice).collect();
+        let key: Vec<String> = key
+            .iter()
+            .map(|group| group.iter().map(slice).collect())
+            .collect();
+        key.join(".")
+            .split('.')
+            .rev()
+            .fold(Value::String(value), |inner, part| {
+                let mut map = Map::new();
+                map.insert(part.to_string(), inner);
+                Value::Object(map)
+            })
+    }
+
+    fn mapping_part(group: &[(u32, u32)]) -> String {
+        let slices: Vec<String> = group
+            .iter()
+            .map(|(a, b)| format!("n[{}:{}]", a, b))
+            .collect();
+        format!("({})", slices.join("+"))
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_get_dict_matches_python(
+            key in proptest::collection::vec(
+                proptest::collection::vec((0u32..40, 0u32..8), 1..4), 1..4),
+            value in proptest::collection::vec((0u32..40, 0u32..8), 1..3),
+            lot_number in "\\PC{0,40}",
+        ) {
+            // Slices are given as (start, length)
+            let key: Vec<Vec<(u32, u32)>> = key
+                .iter()
+                .map(|group| group.iter().map(|&(a, len)| (a, a + len)).collect())
+                .collect();
+            let value: Vec<(u32, u32)> = value.iter().map(|&(a, len)| (a, a + len)).collect();
+            let key_pattern: Vec<String> = key.iter().map(|group| mapping_part(group)).collect();
+            let mapping = format!(r#"{{"{}":"{}"}}"#, key_pattern.join("+.+"), mapping_part(&value));
+
+            let parser = LotParser::new(&mapping).unwrap();
+            proptest::prop_assert_eq!(
+                parser.get_dict(&lot_number),
+                python_get_dict(&key, &value, &lot_number)
+            );
+        }
+    }
+
+    #[test]
+    fn test_adversarial_mapping() {
+        for mapping in [r#"{"n[5:2]":"n[0:1]"}"#, r#"{"n[0:99999999999]":"n[0:1]"}"#] {
+            assert!(LotParser::new(mapping).is_err(), "{}", mapping);
+        }
+
+        // Slices beyond the lot number are clamped
+        let parser = LotParser::new(r#"{"n[1:2]+.+n[30:2147483647]":"n[3:2147483647]"}"#).unwrap();
+        assert_eq!(parser.get_dict("abcdé"), json!({"bc": {"": "dé"}}));
+        assert_eq!(parser.get_dict(""), json!({"": {"": ""}}));
+    }
+
+    #[test]
+    fn test_slide_payload_with_top() {
+        let data: LoadResponse = serde_json::from_value(json!({
+            "lot_number": "f4744c44df4541b3be48c5c270ced20b",
+            "payload": "payload",
+            "process_token": "token",
+            "pt": "1",
+        }))
+        .unwrap();
+        let constants = Constants {
+            mapping: r#"{"(n[13:15]+n[3:5])+.+(n[1:3]+n[26:28])":"n[13:18]"}"#.into(),
+            ..Default::default()
+        };
+        let payload = |top| {
+            let result = Some(SolverResult::Slide {
+                left: 100.0,
+                top,
+                raw: [0.0, 0.0],
+            });
+            let options = PayloadOptions::default();
+            build_w_payload(&data, "id", &RiskType::Slide, &constants, result, &options).unwrap()
+        };
+
+        let flat = payload(None);
+        assert!(flat.get("setTop").is_none());
+        assert!(flat["userresponse"].is_f64());
+        // em counts the events of the drag
+        assert_eq!(flat["em"]["md"], 1);
+        assert_eq!(flat["em"]["cl"], 0);
+        assert!(flat["em"]["mm"].as_u64().unwrap() > 10);
+        assert_eq!(flat["em"]["wd"], 1);
+
+        let placed = payload(Some(48.0));
+        assert_eq!(placed["setTop"], json!(48.0));
+        assert_eq!(placed["userresponse"][0], flat["userresponse"]);
+        assert!(placed["userresponse"][1].as_f64().unwrap() > 48.0);
+    }
+
+    #[test]
+    fn test_payload_template() {
+        let data: LoadResponse = serde_json::from_value(json!({
+            "lot_number": "f4744c44df4541b3be48c5c270ced20b",
+            "payload": "payload",
+            "process_token": "token",
+            "pt": "1",
+        }))
+        .unwrap();
+        let mut constants = Constants {
+            mapping: r#"{"(n[13:15]+n[3:5])+.+(n[1:3]+n[26:28])":"n[13:18]"}"#.into(),
+            abo: [("abo".to_string(), "1".to_string())].into(),
+            ..Default::default()
+        };
+        let options = PayloadOptions::default();
+        let payload = |constants: &Constants| {
+            build_w_payload(&data, "id", &RiskType::Ai, constants, None, &options).unwrap()
+        };
+        assert_eq!(payload(&constants)["biht"], "1426265548");
+
+        // Fields outside the template are dropped, unknown ones skipped
+        constants.payload_fields = ["geetest", "lot_number", "em", "newfield"]
+            .map(String::from)
+            .to_vec();
+        let payload = payload(&constants);
+        assert!(payload.get("biht").is_none());
+        assert!(payload.get("newfield").is_none());
+        assert_eq!(payload["geetest"], "captcha");
+        assert_eq!(payload["em"]["wd"], 1);
+        // abo and lot values are still merged
+        assert_eq!(payload["abo"], "1");
+
+        // A template without em leaves it out, event counts included
+        constants.payload_fields = ["geetest", "lot_number"].map(String::from).to_vec();
+        let result = Some(SolverResult::Gobang {
+            response: vec![vec![0, 0], vec![1, 1]],
+        });
+        let payload =
+            build_w_payload(&data, "id", &RiskType::Gobang, &constants, result, &options).unwrap();
+        assert!(payload.get("em").is_none());
+        assert!(payload["userresponse"].is_array());
+        assert_eq!(payload["lot_number"], "f4744c44df4541b3be48c5c270ced20b");
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_w_is_reproducible() {
+        let data: LoadResponse = serde_json::from_value(json!({
+            "lot_number": "f4744c44df4541b3be48c5c270ced20b",
+            "payload": "payload",
+            "process_token": "token",
+            "pt": "1",
+            "pow_detail": {"hashfunc": "md5", "version": "1", "bits": 0, "datetime": "now"},
+        }))
+        .unwrap();
+        let constants = Constants {
+            mapping: r#"{"(n[13:15]+n[3:5])+.+(n[1:3]+n[26:28])":"n[13:18]"}"#.into(),
+            ..Default::default()
+        };
+        let w = || {
+            let _guard = crate::test_util::deterministic(42);
+            let result = Some(SolverResult::Slide {
+                left: 120.0,
+                top: None,
+                raw: [140.0, 0.0],
+            });
+            generate_w_parameter(&data, "id", &RiskType::Slide, &constants, result).unwrap()
+        };
+        assert_eq!(w(), w());
+    }
+
+    #[test]
+    fn test_solver_result_json() {
+        let result = SolverResult::Icon {
+            positions: vec![vec![33.0, 49.0]],
+            raw: vec![[100.0, 100.0]],
+        };
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            value,
+            json!({"type": "icon", "positions": [[33.0, 49.0]], "raw": [[100.0, 100.0]]})
+        );
+        assert_eq!(
+            serde_json::from_value::<SolverResult>(value).unwrap(),
+            result
+        );
+
+        // Answers saved before raw coordinates existed still load
+        let old: SolverResult =
+            serde_json::from_value(json!({"type": "slide", "left": 120.5, "top": null})).unwrap();
+        assert!(matches!(old, SolverResult::Slide { raw, .. } if raw == [0.0; 2]));
+    }
 }
@@ -0,0 +1,89 @@
+//! Bounded image decoding.
+//!
+//! Captcha images come from Geetest's CDN, but a compromised CDN or a
+//! hijacked proxy could serve a few kilobytes that decode to gigabytes.
+//! Every solver decodes through [`decode_image`], which refuses oversized
+//! inputs and dimensions before allocating the pixels.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageReader, Limits};
+
+use crate::error::{GeekedError, Result};
+
+/// Largest encoded image accepted, in bytes.
+pub(crate) const MAX_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Largest width or height accepted, in pixels. Captcha images are a few
+/// hundred pixels wide.
+pub(crate) const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// Most memory a decoder may allocate, in bytes.
+const MAX_DECODE_ALLOC: u64 = 128 * 1024 * 1024;
+
+/// Decode PNG, JPEG, WebP, GIF or BMP bytes (and AVIF with the `avif`
+/// feature), naming the image as `what` in errors.
+pub(crate) fn decode_image(bytes: &[u8], what: &str) -> Result<DynamicImage> {
+    let failed = |problem: String| {
+        GeekedError::ImageProcessing(format!("Failed to load {}: {}", what, problem))
+    };
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(failed(format!(
+            "{} bytes exceeds the {} byte limit",
+            bytes.len(),
+            MAX_IMAGE_BYTES
+        )));
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| failed(e.to_string()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+    reader.decode().map_err(|e| failed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageFormat;
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_formats() {
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP] {
+            let bytes = encode(DynamicImage::new_rgb8(30, 20), format);
+            let image = decode_image(&bytes, "background").unwrap();
+            assert_eq!((image.width(), image.height()), (30, 20), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        // A tiny PNG claiming a huge canvas
+        let bomb = encode(
+            DynamicImage::new_luma8(MAX_IMAGE_DIMENSION + 1, 1),
+            ImageFormat::Png,
+        );
+        let error = decode_image(&bomb, "background").unwrap_err().to_string();
+        assert!(error.contains("Failed to load background"), "{}", error);
+
+        let oversized = vec![0u8; MAX_IMAGE_BYTES + 1];
+        let error = decode_image(&oversized, "puzzle piece")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("byte limit"), "{}", error);
+
+        assert!(decode_image(b"not an image", "image").is_err());
+    }
+}
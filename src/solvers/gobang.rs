@@ -4,7 +4,34 @@
 //! with n-1 matching elements and one empty cell (0), then move a matching piece
 //! from elsewhere to fill the gap.
 
+use std::cmp::Reverse;
 use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+/// A move: `[[remove_row, remove_col], [fill_row, fill_col]]`.
+type Move = [[i32; 2]; 2];
+
+/// An incomplete line and the move toward filling it.
+struct Partial {
+    /// Cells holding the most common piece
+    count: usize,
+    piece: i32,
+    fill_pos: (usize, usize),
+    line: Vec<(usize, usize)>,
+}
+
+/// Outcome of [`GobangSolver::solve_within`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GobangSearch {
+    /// A move completing a line
+    Solved(Move),
+    /// The time budget ran out first; holds the move toward the fullest
+    /// line seen until then, if any
+    TimedOut(Option<Move>),
+    /// No line can be completed
+    NoSolution,
+}
 
 /// Solver for Gobang/Five-in-a-row captcha puzzles.
 pub struct GobangSolver {
@@ -27,7 +54,37 @@ impl GobangSolver {
     /// # Returns
     /// `Some([[remove_row, remove_col], [fill_row, fill_col]])` if found, `None` otherwise.
     pub fn find_four_in_line(&self) -> Option<[[i32; 2]; 2]> {
+        match self.search(|| false) {
+            GobangSearch::Solved(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Like [`find_four_in_line`](Self::find_four_in_line), but gives up
+    /// after `budget` so large or adversarial boards cannot stall the caller.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn solve_within(&self, budget: Duration) -> GobangSearch {
+        let deadline = Instant::now() + budget;
+        self.search(|| Instant::now() >= deadline)
+    }
+
+    /// Scan the lines until one can be completed or `expired` returns true.
+    fn search(&self, mut expired: impl FnMut() -> bool) -> GobangSearch {
+        // Fullest incomplete line so far
+        let mut best: Option<Partial> = None;
+
         for line in self.iterate_lines() {
+            if expired() {
+                let partial = best.and_then(|best| {
+                    let fill_pos = best.fill_pos;
+                    let remove_pos = self.find_remove_candidate(best.piece, &best.line)?;
+                    Some([
+                        [remove_pos.0 as i32, remove_pos.1 as i32],
+                        [fill_pos.0 as i32, fill_pos.1 as i32],
+                    ])
+                });
+                return GobangSearch::TimedOut(partial);
+            }
             if line.len() < self.n {
                 continue;
             }
@@ -35,6 +92,23 @@ impl GobangSolver {
             let elements: Vec<i32> = line.iter().map(|&(r, c)| self.board[r][c]).collect();
             let freq = Self::count_freq(&elements);
 
+            if let Some(zero_idx) = elements.iter().position(|&x| x == 0) {
+                let fullest = freq
+                    .iter()
+                    .filter(|(&num, _)| num != 0)
+                    .max_by_key(|(&num, &count)| (count, Reverse(num)));
+                if let Some((&num, &count)) = fullest {
+                    if best.as_ref().is_none_or(|best| count > best.count) {
+                        best = Some(Partial {
+                            count,
+                            piece: num,
+                            fill_pos: line[zero_idx],
+                            line: line.clone(),
+                        });
+                    }
+                }
+            }
+
             // Look for a line with n-1 matching elements and one 0
             if !freq.values().any(|&count| count == self.n - 1) {
                 continue;
@@ -60,7 +134,7 @@ impl GobangSolver {
 
                     // Find a piece to remove (same value, not in this line)
                     if let Some(remove_pos) = self.find_remove_candidate(correct_num, &line) {
-                        return Some([
+                        return GobangSearch::Solved([
                             [remove_pos.0 as i32, remove_pos.1 as i32],
                             [fill_pos.0 as i32, fill_pos.1 as i32],
                         ]);
@@ -69,7 +143,7 @@ impl GobangSolver {
             }
         }
 
-        None
+        GobangSearch::NoSolution
     }
 
     /// Iterate over all possible lines (rows, columns, diagonals).
@@ -190,4 +264,32 @@ mod tests {
         // All zeros, can't solve
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_solve_within() {
+        let board = vec![
+            vec![1, 1, 0, 0, 1],
+            vec![2, 2, 2, 2, 1],
+            vec![3, 3, 3, 3, 3],
+            vec![4, 4, 4, 4, 4],
+            vec![5, 5, 5, 5, 5],
+        ];
+        let solver = GobangSolver::new(board);
+        assert_eq!(
+            solver.solve_within(Duration::from_secs(1)),
+            GobangSearch::NoSolution
+        );
+        assert_eq!(
+            solver.solve_within(Duration::ZERO),
+            GobangSearch::TimedOut(None)
+        );
+
+        // Out of time after the first row: move a 1 toward filling it
+        let mut lines = 0;
+        let search = solver.search(|| {
+            lines += 1;
+            lines > 1
+        });
+        assert_eq!(search, GobangSearch::TimedOut(Some([[1, 4], [0, 2]])));
+    }
 }
@@ -3,11 +3,18 @@
 //! This solver identifies arrows/icons in an image and matches them
 //! to the required directions using a custom ONNX classification model.
 
+use super::decode::decode_image;
+use super::regions::{find_connected_components, otsu_threshold, threshold_image};
 use crate::error::{GeekedError, Result};
-use image::{DynamicImage, GrayImage, Luma};
+use image::DynamicImage;
 use ndarray::Array4;
+use ort::execution_providers::CPUExecutionProvider;
 use ort::session::{builder::GraphOptimizationLevel, Session};
 use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+
+pub use super::regions::BoundingBox;
 
 /// Direction labels for icon classification.
 /// Maps question icon filenames to direction codes.
@@ -73,31 +80,145 @@ const MODEL_INPUT_HEIGHT: u32 = 64;
 /// Embedded ONNX model for icon classification.
 static ICON_MODEL: &[u8] = include_bytes!("../../models/geetest_v4_icon.onnx");
 
-/// Bounding box for detected icon region.
-#[derive(Debug, Clone, Copy)]
-pub struct BoundingBox {
-    pub x1: u32,
-    pub y1: u32,
-    pub x2: u32,
-    pub y2: u32,
+/// Process-wide solvers, so models are loaded once and reused.
+static SHARED: OnceLock<SolverPool> = OnceLock::new();
+
+/// ONNX Runtime settings for the icon model.
+///
+/// The defaults let ONNX Runtime use every core for one inference; when
+/// many workers share a machine, cap the threads to keep solves from
+/// competing for CPU.
+#[derive(Debug, Clone)]
+pub struct IconSolverConfig {
+    /// Threads used within an operator; one per core if unset
+    pub intra_threads: Option<usize>,
+    /// Threads running independent operators in parallel; operators run
+    /// sequentially if unset
+    pub inter_threads: Option<usize>,
+    /// Plan allocations from the first inference's memory use
+    pub memory_pattern: bool,
+    /// Keep freed memory in an arena for reuse, trading memory for speed
+    pub arena_allocator: bool,
+    /// Graph optimizations, from 0 (none) to 3 (all)
+    pub optimization_level: u8,
+    /// Shared solvers for concurrent solves, each holding its own copy of
+    /// the model; at least one
+    pub pool_size: usize,
+}
+
+impl Default for IconSolverConfig {
+    fn default() -> Self {
+        Self {
+            intra_threads: None,
+            inter_threads: None,
+            memory_pattern: true,
+            arena_allocator: true,
+            optimization_level: 3,
+            pool_size: 2,
+        }
+    }
+}
+
+/// Solvers shared across threads, loaded on demand up to the pool size.
+struct SolverPool {
+    config: IconSolverConfig,
+    state: Mutex<PoolState>,
+    returned: Condvar,
 }
 
-impl BoundingBox {
-    /// Get the center point of the bounding box.
-    pub fn center(&self) -> (f64, f64) {
-        let cx = self.x1 as f64 + (self.x2 - self.x1) as f64 / 2.0;
-        let cy = self.y1 as f64 + (self.y2 - self.y1) as f64 / 2.0;
-        (cx, cy)
+struct PoolState {
+    idle: Vec<IconSolver>,
+    /// Solvers loaded, idle or checked out
+    loaded: usize,
+}
+
+impl SolverPool {
+    fn new(config: IconSolverConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                loaded: 0,
+            }),
+            returned: Condvar::new(),
+        }
     }
 
-    /// Get width of bounding box.
-    pub fn width(&self) -> u32 {
-        self.x2 - self.x1
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    /// Get height of bounding box.
-    pub fn height(&self) -> u32 {
-        self.y2 - self.y1
+    /// Take an idle solver, load a new one if under the limit, or wait for
+    /// one to be returned.
+    fn checkout(&'static self) -> Result<PooledIconSolver> {
+        let mut state = self.lock();
+        loop {
+            if let Some(solver) = state.idle.pop() {
+                return Ok(PooledIconSolver {
+                    pool: self,
+                    solver: Some(solver),
+                });
+            }
+            if state.loaded < self.config.pool_size.max(1) {
+                state.loaded += 1;
+                drop(state);
+                // Load outside the lock so returns are not held up
+                return match IconSolver::with_config(&self.config) {
+                    Ok(solver) => Ok(PooledIconSolver {
+                        pool: self,
+                        solver: Some(solver),
+                    }),
+                    Err(e) => {
+                        self.lock().loaded -= 1;
+                        self.returned.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            state = self
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Return `solver`, counting it as newly loaded if `new`.
+    fn checkin(&self, solver: IconSolver, new: bool) {
+        let mut state = self.lock();
+        if new {
+            state.loaded += 1;
+        }
+        state.idle.push(solver);
+        drop(state);
+        self.returned.notify_one();
+    }
+}
+
+/// A solver checked out with [`IconSolver::shared`], returned on drop.
+pub struct PooledIconSolver {
+    pool: &'static SolverPool,
+    solver: Option<IconSolver>,
+}
+
+impl Deref for PooledIconSolver {
+    type Target = IconSolver;
+
+    fn deref(&self) -> &IconSolver {
+        self.solver.as_ref().expect("held until dropped")
+    }
+}
+
+impl DerefMut for PooledIconSolver {
+    fn deref_mut(&mut self) -> &mut IconSolver {
+        self.solver.as_mut().expect("held until dropped")
+    }
+}
+
+impl Drop for PooledIconSolver {
+    fn drop(&mut self) {
+        if let Some(solver) = self.solver.take() {
+            self.pool.checkin(solver, false);
+        }
     }
 }
 
@@ -110,21 +231,52 @@ pub struct IconSolver {
 impl IconSolver {
     /// Create a new IconSolver, loading the ONNX model.
     pub fn new() -> Result<Self> {
-        let session = Session::builder()
-            .map_err(|e| {
-                GeekedError::ImageProcessing(format!(
-                    "Failed to create ONNX session builder: {}",
-                    e
-                ))
-            })?
-            .with_optimization_level(GraphOptimizationLevel::Level3)
-            .map_err(|e| {
-                GeekedError::ImageProcessing(format!("Failed to set optimization level: {}", e))
-            })?
+        Self::with_config(&IconSolverConfig::default())
+    }
+
+    /// Create a new IconSolver, loading the ONNX model with `config`.
+    pub fn with_config(config: &IconSolverConfig) -> Result<Self> {
+        let optimization_level = match config.optimization_level {
+            0 => GraphOptimizationLevel::Disable,
+            1 => GraphOptimizationLevel::Level1,
+            2 => GraphOptimizationLevel::Level2,
+            3 => GraphOptimizationLevel::Level3,
+            level => {
+                return Err(GeekedError::Config(format!(
+                    "icon optimization level {} is not between 0 and 3",
+                    level
+                )))
+            }
+        };
+        let failed = |what: &str| {
+            let what = what.to_string();
+            move |e: ort::Error| GeekedError::ImageProcessing(format!("Failed to {}: {}", what, e))
+        };
+
+        let mut builder = Session::builder()
+            .map_err(failed("create ONNX session builder"))?
+            .with_optimization_level(optimization_level)
+            .map_err(failed("set optimization level"))?
+            .with_memory_pattern(config.memory_pattern)
+            .map_err(failed("set memory pattern"))?
+            .with_execution_providers([CPUExecutionProvider::default()
+                .with_arena_allocator(config.arena_allocator)
+                .build()])
+            .map_err(failed("configure the CPU allocator"))?;
+        if let Some(threads) = config.intra_threads {
+            builder = builder
+                .with_intra_threads(threads)
+                .map_err(failed("set intra-op threads"))?;
+        }
+        if let Some(threads) = config.inter_threads {
+            builder = builder
+                .with_parallel_execution(true)
+                .and_then(|builder| builder.with_inter_threads(threads))
+                .map_err(failed("set inter-op threads"))?;
+        }
+        let session = builder
             .commit_from_memory(ICON_MODEL)
-            .map_err(|e| {
-                GeekedError::ImageProcessing(format!("Failed to load ONNX model: {}", e))
-            })?;
+            .map_err(failed("load ONNX model"))?;
 
         let icon_map = ICON_MAPPING
             .iter()
@@ -134,6 +286,43 @@ impl IconSolver {
         Ok(Self { session, icon_map })
     }
 
+    /// Load the process-wide solvers with `config` instead of the defaults.
+    ///
+    /// Call before the first icon solve; fails with
+    /// [`GeekedError::Config`] once the shared solvers are loaded.
+    pub fn init_shared(config: &IconSolverConfig) -> Result<()> {
+        // Load one solver now so a bad config fails here, not mid-solve
+        let pool = SolverPool::new(config.clone());
+        pool.checkin(Self::with_config(config)?, true);
+        SHARED
+            .set(pool)
+            .map_err(|_| GeekedError::Config("the icon solver is already loaded".to_string()))
+    }
+
+    /// Check out a process-wide solver, loading a model on first use.
+    ///
+    /// Blocks while [`pool_size`](IconSolverConfig::pool_size) solvers are
+    /// checked out; async code should use
+    /// [`find_icon_pixels_shared`](Self::find_icon_pixels_shared) instead.
+    pub fn shared() -> Result<PooledIconSolver> {
+        SHARED
+            .get_or_init(|| SolverPool::new(IconSolverConfig::default()))
+            .checkout()
+    }
+
+    /// [`find_icon_pixels`](Self::find_icon_pixels) with a shared solver,
+    /// run on Tokio's blocking pool so inference does not stall the runtime.
+    pub async fn find_icon_pixels_shared(
+        img_bytes: Vec<u8>,
+        questions: Vec<String>,
+    ) -> Result<Vec<[f64; 2]>> {
+        tokio::task::spawn_blocking(move || {
+            Self::shared()?.find_icon_pixels(&img_bytes, &questions)
+        })
+        .await
+        .map_err(|e| GeekedError::ImageProcessing(format!("Icon inference failed: {}", e)))?
+    }
+
     /// Get the required direction for a question icon URL.
     fn get_direction(&self, url: &str) -> Option<&str> {
         let filename = url.split('/').last()?;
@@ -272,10 +461,27 @@ impl IconSolver {
         &mut self,
         img_bytes: &[u8],
         questions: &[String],
+    ) -> Result<Vec<[f64; 2]>> {
+        let pixels = self.find_icon_pixels(img_bytes, questions)?;
+        Ok(pixels.into_iter().map(Self::scale_to_api).collect())
+    }
+
+    /// Convert a position in image pixels to API click coordinates.
+    pub fn scale_to_api([x, y]: [f64; 2]) -> [f64; 2] {
+        // Scale coordinates as per Python: x * 33, y * 49
+        [x * 33.0 / 100.0, y * 49.0 / 100.0]
+    }
+
+    /// Find the icons to click like
+    /// [`find_icon_positions`](Self::find_icon_positions), as centers in
+    /// image pixels.
+    pub fn find_icon_pixels(
+        &mut self,
+        img_bytes: &[u8],
+        questions: &[String],
     ) -> Result<Vec<[f64; 2]>> {
         // Load the image
-        let img = image::load_from_memory(img_bytes)
-            .map_err(|e| GeekedError::ImageProcessing(format!("Failed to load image: {}", e)))?;
+        let img = decode_image(img_bytes, "image")?;
 
         // Get required directions from questions (convert to owned Strings to avoid borrow issues)
         let required_directions: Vec<Option<String>> = questions
@@ -309,9 +515,7 @@ impl IconSolver {
                 for (i_idx, (bbox, detected_dir)) in detected_icons.iter().enumerate() {
                     if !used_icons[i_idx] && detected_dir == req_dir {
                         let (cx, cy) = bbox.center();
-                        // Scale coordinates as per Python: x * 33, y * 49
-                        // These scaling factors convert from image coordinates to API coordinates
-                        results[q_idx] = Some([cx * 33.0 / 100.0, cy * 49.0 / 100.0]);
+                        results[q_idx] = Some([cx, cy]);
                         used_icons[i_idx] = true;
                         break;
                     }
@@ -323,12 +527,12 @@ impl IconSolver {
         for (i_idx, (bbox, _)) in detected_icons.iter().enumerate() {
             if !used_icons[i_idx] {
                 let (cx, cy) = bbox.center();
-                unused_positions.push([cx * 33.0 / 100.0, cy * 49.0 / 100.0]);
+                unused_positions.push([cx, cy]);
             }
         }
 
         // Second pass: fill in missing with unused positions
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::entropy::rng();
         for result in results.iter_mut() {
             if result.is_none() && !unused_positions.is_empty() {
                 let idx = rand::Rng::gen_range(&mut rng, 0..unused_positions.len());
@@ -340,154 +544,14 @@ impl IconSolver {
         let final_results: Vec<[f64; 2]> = results
             .into_iter()
             .enumerate()
-            .map(|(idx, opt)| {
-                opt.unwrap_or_else(|| {
-                    // Fallback: generate a reasonable position based on index
-                    let x = 50.0 + (idx as f64 * 80.0);
-                    let y = 100.0;
-                    [x * 33.0 / 100.0, y * 49.0 / 100.0]
-                })
-            })
+            // Fallback: generate a reasonable position based on index
+            .map(|(idx, opt)| opt.unwrap_or([50.0 + (idx as f64 * 80.0), 100.0]))
             .collect();
 
         Ok(final_results)
     }
 }
 
-/// Calculate Otsu's threshold for binarization.
-fn otsu_threshold(img: &GrayImage) -> u8 {
-    let mut histogram = [0u64; 256];
-    let total_pixels = (img.width() * img.height()) as u64;
-
-    // Build histogram
-    for pixel in img.pixels() {
-        histogram[pixel[0] as usize] += 1;
-    }
-
-    let mut sum = 0u64;
-    for (i, &count) in histogram.iter().enumerate() {
-        sum += i as u64 * count;
-    }
-
-    let mut sum_b = 0u64;
-    let mut w_b = 0u64;
-    let mut max_variance = 0.0f64;
-    let mut threshold = 0u8;
-
-    for (i, &count) in histogram.iter().enumerate() {
-        w_b += count;
-        if w_b == 0 {
-            continue;
-        }
-
-        let w_f = total_pixels - w_b;
-        if w_f == 0 {
-            break;
-        }
-
-        sum_b += i as u64 * count;
-
-        let m_b = sum_b as f64 / w_b as f64;
-        let m_f = (sum - sum_b) as f64 / w_f as f64;
-
-        let variance = w_b as f64 * w_f as f64 * (m_b - m_f).powi(2);
-
-        if variance > max_variance {
-            max_variance = variance;
-            threshold = i as u8;
-        }
-    }
-
-    threshold
-}
-
-/// Apply threshold to create binary image.
-fn threshold_image(img: &GrayImage, threshold: u8) -> GrayImage {
-    let (width, height) = img.dimensions();
-    let mut binary = GrayImage::new(width, height);
-
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = img.get_pixel(x, y)[0];
-            // Invert so foreground (icons) are white
-            let val = if pixel < threshold { 255 } else { 0 };
-            binary.put_pixel(x, y, Luma([val]));
-        }
-    }
-
-    binary
-}
-
-/// Find connected components in a binary image and return bounding boxes.
-fn find_connected_components(binary: &GrayImage) -> Vec<BoundingBox> {
-    let (width, height) = binary.dimensions();
-    let mut labels: Vec<i32> = vec![0; (width * height) as usize];
-    let mut current_label = 1i32;
-
-    // First pass: assign preliminary labels
-    for y in 0..height {
-        for x in 0..width {
-            let idx = (y * width + x) as usize;
-            if binary.get_pixel(x, y)[0] == 0 {
-                continue; // Background
-            }
-
-            let mut neighbors = Vec::new();
-
-            // Check left neighbor
-            if x > 0 {
-                let left_idx = (y * width + x - 1) as usize;
-                if labels[left_idx] > 0 {
-                    neighbors.push(labels[left_idx]);
-                }
-            }
-
-            // Check top neighbor
-            if y > 0 {
-                let top_idx = ((y - 1) * width + x) as usize;
-                if labels[top_idx] > 0 {
-                    neighbors.push(labels[top_idx]);
-                }
-            }
-
-            if neighbors.is_empty() {
-                labels[idx] = current_label;
-                current_label += 1;
-            } else {
-                let min_label = *neighbors.iter().min().unwrap();
-                labels[idx] = min_label;
-            }
-        }
-    }
-
-    // Second pass: find bounding boxes for each label
-    let mut bboxes: HashMap<i32, (u32, u32, u32, u32)> = HashMap::new();
-
-    for y in 0..height {
-        for x in 0..width {
-            let idx = (y * width + x) as usize;
-            let label = labels[idx];
-            if label > 0 {
-                let entry = bboxes.entry(label).or_insert((x, y, x, y));
-                entry.0 = entry.0.min(x);
-                entry.1 = entry.1.min(y);
-                entry.2 = entry.2.max(x);
-                entry.3 = entry.3.max(y);
-            }
-        }
-    }
-
-    bboxes
-        .into_values()
-        .map(|(x1, y1, x2, y2)| BoundingBox {
-            x1,
-            y1,
-            x2: x2 + 1,
-            y2: y2 + 1,
-        })
-        .collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -519,34 +583,42 @@ mod tests {
     }
 
     #[test]
-    fn test_otsu_threshold() {
-        // Create a simple test image with some gradation
-        let mut img = GrayImage::new(10, 10);
-        // Create some variation in pixel values
-        for y in 0..10 {
-            for x in 0..10 {
-                // Create a gradient pattern
-                let val = ((x + y) * 25).min(255) as u8;
-                img.put_pixel(x, y, Luma([val]));
-            }
-        }
+    fn test_with_config() {
+        let config = IconSolverConfig {
+            intra_threads: Some(1),
+            inter_threads: Some(1),
+            arena_allocator: false,
+            optimization_level: 1,
+            ..Default::default()
+        };
+        let solver = IconSolver::with_config(&config).unwrap();
+        assert!(solver.get_direction("unknown.png").is_none());
 
-        let threshold = otsu_threshold(&img);
-        // Threshold should be a valid value (0-255)
-        // For this gradient, should be somewhere reasonable
-        assert!(threshold <= 255, "Threshold should be valid: {}", threshold);
+        let config = IconSolverConfig {
+            optimization_level: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            IconSolver::with_config(&config),
+            Err(GeekedError::Config(_))
+        ));
     }
 
     #[test]
-    fn test_bounding_box_center() {
-        let bbox = BoundingBox {
-            x1: 10,
-            y1: 20,
-            x2: 30,
-            y2: 40,
-        };
-        let (cx, cy) = bbox.center();
-        assert!((cx - 20.0).abs() < 0.01);
-        assert!((cy - 30.0).abs() < 0.01);
+    fn test_pool_waits_for_returned_solver() {
+        let pool: &'static SolverPool = Box::leak(Box::new(SolverPool::new(IconSolverConfig {
+            pool_size: 1,
+            ..Default::default()
+        })));
+        let first = pool.checkout().unwrap();
+        let waiter = std::thread::spawn(move || pool.checkout().map(|_| ()));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        waiter.join().unwrap().unwrap();
+        let state = pool.lock();
+        assert_eq!(state.loaded, 1);
+        assert_eq!(state.idle.len(), 1);
     }
 }
@@ -1,6 +1,8 @@
 //! Captcha solvers for different Geetest risk types.
 
+pub(crate) mod decode;
 pub mod gobang;
+pub(crate) mod regions;
 pub mod slide;
 
 #[cfg(feature = "icon")]
@@ -9,11 +11,12 @@ pub mod icon;
 #[cfg(feature = "svg")]
 pub mod svg;
 
-pub use gobang::GobangSolver;
+pub use gobang::{GobangSearch, GobangSolver};
+pub use regions::BoundingBox;
 pub use slide::SlideSolver;
 
 #[cfg(feature = "icon")]
-pub use icon::{BoundingBox, IconSolver};
+pub use icon::{IconSolver, IconSolverConfig};
 
 #[cfg(feature = "svg")]
 pub use svg::SvgSolver;
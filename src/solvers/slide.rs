@@ -1,11 +1,13 @@
 //! Slide puzzle solver using edge detection and template matching.
 //!
-//! This solver finds the correct X position for a puzzle piece by:
+//! This solver finds the correct X position (and, for 2D variants, the Y
+//! position) for a puzzle piece by:
 //! 1. Converting images to grayscale
 //! 2. Applying Canny edge detection
 //! 3. Using template matching to find the best position
 
-use crate::error::{GeekedError, Result};
+use super::decode::decode_image;
+use crate::error::Result;
 use image::{DynamicImage, GrayImage, Luma};
 use imageproc::template_matching::{find_extremes, match_template, MatchTemplateMethod};
 
@@ -22,12 +24,8 @@ impl SlideSolver {
     /// * `puzzle_piece` - Bytes of the puzzle piece image
     /// * `background` - Bytes of the background image
     pub fn from_bytes(puzzle_piece: &[u8], background: &[u8]) -> Result<Self> {
-        let puzzle_piece = image::load_from_memory(puzzle_piece).map_err(|e| {
-            GeekedError::ImageProcessing(format!("Failed to load puzzle piece: {}", e))
-        })?;
-        let background = image::load_from_memory(background).map_err(|e| {
-            GeekedError::ImageProcessing(format!("Failed to load background: {}", e))
-        })?;
+        let puzzle_piece = decode_image(puzzle_piece, "puzzle piece")?;
+        let background = decode_image(background, "background")?;
 
         Ok(Self {
             puzzle_piece,
@@ -43,11 +41,53 @@ impl SlideSolver {
         }
     }
 
+    /// Width of the background image, in pixels.
+    pub fn background_width(&self) -> u32 {
+        self.background.width()
+    }
+
+    /// Height of the background image, in pixels.
+    pub fn background_height(&self) -> u32 {
+        self.background.height()
+    }
+
     /// Find the X position where the puzzle piece should be placed.
     ///
     /// # Returns
     /// The X coordinate (left edge) of the puzzle piece position.
     pub fn find_position(&self) -> f64 {
+        let (max_x, _max_y) = self.find_match_location();
+        self.left_from_match(max_x)
+    }
+
+    /// Find where the puzzle piece should be placed on both axes, for
+    /// variants where the piece moves freely.
+    ///
+    /// # Returns
+    /// The X coordinate as [`find_position`](Self::find_position) returns
+    /// it, and the Y coordinate of the piece's top edge.
+    pub fn find_placement(&self) -> (f64, f64) {
+        let (max_x, max_y) = self.find_match_location();
+        (self.left_from_match(max_x), f64::from(max_y))
+    }
+
+    /// X coordinate for the protocol from the matched top-left corner, as
+    /// returned by [`find_match_location`](Self::find_match_location).
+    pub fn left_from_match(&self, max_x: u32) -> f64 {
+        let piece_width = self.puzzle_piece.width() as f64;
+
+        // Calculate center X and subtract offset
+        // The -41 offset accounts for the transparent padding on the puzzle piece
+        let center_x = max_x as f64 + piece_width / 2.0;
+        center_x - 41.0
+    }
+
+    /// Find the best template match of the puzzle piece in the background.
+    ///
+    /// # Returns
+    /// The raw `(x, y)` pixel location of the piece's top-left corner, before
+    /// any protocol-specific offset is applied.
+    pub fn find_match_location(&self) -> (u32, u32) {
         // Convert to grayscale
         let piece_gray = self.puzzle_piece.to_luma8();
         let bg_gray = self.background.to_luma8();
@@ -65,13 +105,7 @@ impl SlideSolver {
         let extremes = find_extremes(&result);
 
         // Get the position of maximum correlation
-        let (max_x, _max_y) = extremes.max_value_location;
-        let piece_width = self.puzzle_piece.width() as f64;
-
-        // Calculate center X and subtract offset
-        // The -41 offset accounts for the transparent padding on the puzzle piece
-        let center_x = max_x as f64 + piece_width / 2.0;
-        center_x - 41.0
+        extremes.max_value_location
     }
 }
 
@@ -294,4 +328,23 @@ mod tests {
         // Should return some position
         assert!(position >= -50.0 && position <= 300.0);
     }
+
+    #[test]
+    fn test_find_placement() {
+        let square = |width, height, x0, y0| {
+            let mut image = GrayImage::new(width, height);
+            for x in x0..x0 + 40 {
+                for y in y0..y0 + 40 {
+                    image.put_pixel(x, y, Luma([255]));
+                }
+            }
+            DynamicImage::ImageLuma8(image)
+        };
+        let solver = SlideSolver::new(square(60, 60, 10, 10), square(300, 200, 150, 80));
+
+        let (left, top) = solver.find_placement();
+        assert_eq!(top, 70.0);
+        assert_eq!(left, solver.find_position());
+        assert_eq!(left, 140.0 + 30.0 - 41.0);
+    }
 }
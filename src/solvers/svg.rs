@@ -11,8 +11,10 @@
 //! 4. Compares rendered icons against the prompt using binarized IoU + correlation
 //! 5. Returns the best-matching cell coordinates and a realistic passtime
 
+use super::decode::decode_image;
 use crate::error::{GeekedError, Result};
 use image::{DynamicImage, GrayImage, RgbaImage};
+use rand::RngCore;
 use regex::Regex;
 
 /// Grid cell [row, col] (1-based) matching GeeTest's JS click handler.
@@ -284,8 +286,7 @@ fn render_icon_svg(svg_str: &str) -> Result<GrayImage> {
 
 /// Prepare prompt image bytes as a grayscale image at ICON_SIZE.
 fn prepare_prompt(bytes: &[u8]) -> Result<GrayImage> {
-    let img = image::load_from_memory(bytes)
-        .map_err(|e| GeekedError::ImageProcessing(format!("Failed to load prompt: {e}")))?;
+    let img = decode_image(bytes, "prompt")?;
 
     let resized = img.resize_exact(ICON_SIZE, ICON_SIZE, image::imageops::CatmullRom);
     Ok(resized.to_luma8())
@@ -375,7 +376,7 @@ fn calculate_passtime(
     let mut visible_end_ms = (duration_ms * timing.end_pct / 100.0) as u32;
 
     // Human reaction time: 300-800ms after frame appears
-    let reaction_ms = rand::random::<u32>() % 500 + 300;
+    let reaction_ms = crate::entropy::rng().next_u32() % 500 + 300;
     let mut earliest = visible_start_ms + reaction_ms;
 
     if earliest < 500 {
@@ -385,7 +386,7 @@ fn calculate_passtime(
         visible_end_ms = earliest + 500;
     }
 
-    earliest + rand::random::<u32>() % (visible_end_ms - earliest + 1)
+    earliest + crate::entropy::rng().next_u32() % (visible_end_ms - earliest + 1)
 }
 
 #[cfg(test)]
@@ -0,0 +1,350 @@
+//! Persistent record of solve outcomes in sqlite.
+//!
+//! Every solve of a client built with
+//! [`GeekedBuilder::stats`](crate::GeekedBuilder::stats) is stored with its
+//! type, proxy, duration, outcome, score and constants version. The query
+//! helpers show how the success rate moves over time and across Geetest
+//! script versions, which is how a drop in accuracy after an update is
+//! spotted.
+//!
+//! The server also keeps its per-API-key daily usage counters here.
+//!
+//! ```ignore
+//! let stats = Arc::new(StatsStore::open("solves.db")?);
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .stats(stats.clone())
+//!     .build()
+//!     .await?;
+//! solver.solve().await?;
+//!
+//! let day_ago = SystemTime::now() - Duration::from_secs(86400);
+//! for bucket in stats.success_rates(day_ago, Duration::from_secs(3600), None)? {
+//!     println!("{}: {:.0}%", bucket.start, bucket.rate() * 100.0);
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, Row};
+
+use crate::error::{GeekedError, Result};
+use crate::models::RiskType;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS solves (
+        id INTEGER PRIMARY KEY,
+        recorded_at INTEGER NOT NULL,
+        captcha_id TEXT NOT NULL,
+        risk_type TEXT NOT NULL,
+        proxy TEXT,
+        duration_ms INTEGER NOT NULL,
+        error_code INTEGER,
+        score INTEGER,
+        constants_version TEXT
+    );
+    CREATE INDEX IF NOT EXISTS solves_recorded_at ON solves (recorded_at);
+    CREATE TABLE IF NOT EXISTS api_usage (
+        key_name TEXT NOT NULL,
+        day INTEGER NOT NULL,
+        solves INTEGER NOT NULL,
+        PRIMARY KEY (key_name, day)
+    );
+";
+
+/// Aggregate columns shared by the success-rate queries.
+const AGGREGATES: &str = "COUNT(*), COALESCE(SUM(error_code IS NULL), 0), AVG(score)";
+
+/// One finished solve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveRecord {
+    /// Unix time in seconds
+    pub recorded_at: u64,
+    pub captcha_id: String,
+    pub risk_type: String,
+    /// Proxy URL with credentials masked
+    pub proxy: Option<String>,
+    pub duration_ms: u64,
+    /// [`GeekedError::error_code`] of a failed solve, `None` on success
+    pub error_code: Option<i32>,
+    /// Verify score, if Geetest reported one
+    pub score: Option<u32>,
+    /// Version of the Geetest script the constants came from, if known
+    pub constants_version: Option<String>,
+}
+
+impl SolveRecord {
+    pub fn succeeded(&self) -> bool {
+        self.error_code.is_none()
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            recorded_at: row.get(0)?,
+            captcha_id: row.get(1)?,
+            risk_type: row.get(2)?,
+            proxy: row.get(3)?,
+            duration_ms: row.get(4)?,
+            error_code: row.get(5)?,
+            score: row.get(6)?,
+            constants_version: row.get(7)?,
+        })
+    }
+}
+
+/// Success rate over a group of solves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuccessRate {
+    /// Unix time in seconds of the group's start (bucket start or first solve)
+    pub start: u64,
+    pub solves: u64,
+    pub succeeded: u64,
+    /// Mean verify score of the solves that reported one
+    pub average_score: Option<f64>,
+}
+
+impl SuccessRate {
+    /// Share of solves that succeeded, 0 if there were none.
+    pub fn rate(&self) -> f64 {
+        if self.solves == 0 {
+            0.0
+        } else {
+            self.succeeded as f64 / self.solves as f64
+        }
+    }
+
+    fn from_row(row: &Row<'_>, start: u64) -> rusqlite::Result<Self> {
+        Ok(Self {
+            start,
+            solves: row.get(0)?,
+            succeeded: row.get(1)?,
+            average_score: row.get(2)?,
+        })
+    }
+}
+
+/// Sqlite-backed solve outcome store.
+///
+/// Writes are small and synchronous; share one store between clients with
+/// an `Arc`.
+pub struct StatsStore {
+    conn: Mutex<Connection>,
+}
+
+impl StatsStore {
+    /// Open (or create) the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(conn)
+    }
+
+    /// Store that lives only as long as the process.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Store one solve.
+    pub fn record(&self, record: &SolveRecord) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO solves (recorded_at, captcha_id, risk_type, proxy, duration_ms,
+                                 error_code, score, constants_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.recorded_at,
+                record.captcha_id,
+                record.risk_type,
+                record.proxy,
+                record.duration_ms,
+                record.error_code,
+                record.score,
+                record.constants_version,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The latest `limit` solves, newest first.
+    pub fn recent(&self, limit: usize) -> Result<Vec<SolveRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT recorded_at, captcha_id, risk_type, proxy, duration_ms, error_code, score,
+                    constants_version
+             FROM solves ORDER BY recorded_at DESC, id DESC LIMIT ?1",
+        )?;
+        let records = stmt
+            .query_map([limit as i64], SolveRecord::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(records)
+    }
+
+    /// Success rate of all solves since `since`, optionally of one type.
+    pub fn success_rate(
+        &self,
+        since: SystemTime,
+        risk_type: Option<&RiskType>,
+    ) -> Result<SuccessRate> {
+        let since = unix_secs(since);
+        let rate = self.conn().query_row(
+            &format!(
+                "SELECT {AGGREGATES} FROM solves
+                 WHERE recorded_at >= ?1 AND (?2 IS NULL OR risk_type = ?2)"
+            ),
+            params![since, risk_type.map(RiskType::as_str)],
+            |row| SuccessRate::from_row(row, since),
+        )?;
+        Ok(rate)
+    }
+
+    /// Success rate per `bucket` of time since `since`, oldest first.
+    ///
+    /// Buckets without solves are left out.
+    pub fn success_rates(
+        &self,
+        since: SystemTime,
+        bucket: Duration,
+        risk_type: Option<&RiskType>,
+    ) -> Result<Vec<SuccessRate>> {
+        let bucket = bucket.as_secs().max(1);
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {AGGREGATES}, (recorded_at / ?3) * ?3 AS bucket FROM solves
+             WHERE recorded_at >= ?1 AND (?2 IS NULL OR risk_type = ?2)
+             GROUP BY bucket ORDER BY bucket"
+        ))?;
+        let rates = stmt
+            .query_map(
+                params![unix_secs(since), risk_type.map(RiskType::as_str), bucket],
+                |row| SuccessRate::from_row(row, row.get(3)?),
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rates)
+    }
+
+    /// Success rate per constants version since `since`, in the order the
+    /// versions first appeared.
+    pub fn success_rates_by_version(
+        &self,
+        since: SystemTime,
+    ) -> Result<Vec<(Option<String>, SuccessRate)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {AGGREGATES}, MIN(recorded_at), constants_version FROM solves
+             WHERE recorded_at >= ?1
+             GROUP BY constants_version ORDER BY MIN(recorded_at)"
+        ))?;
+        let rates = stmt
+            .query_map([unix_secs(since)], |row| {
+                Ok((row.get(4)?, SuccessRate::from_row(row, row.get(3)?)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rates)
+    }
+
+    /// Count one solve for API key `name` on `day` (days since the epoch).
+    pub fn record_usage(&self, name: &str, day: u64) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO api_usage (key_name, day, solves) VALUES (?1, ?2, 1)
+             ON CONFLICT (key_name, day) DO UPDATE SET solves = solves + 1",
+            params![name, day],
+        )?;
+        Ok(())
+    }
+
+    /// Solves counted for API key `name` on `day`.
+    pub fn usage(&self, name: &str, day: u64) -> Result<u64> {
+        let solves = self.conn().query_row(
+            "SELECT COALESCE(SUM(solves), 0) FROM api_usage WHERE key_name = ?1 AND day = ?2",
+            params![name, day],
+            |row| row.get(0),
+        )?;
+        Ok(solves)
+    }
+}
+
+impl From<rusqlite::Error> for GeekedError {
+    fn from(err: rusqlite::Error) -> Self {
+        GeekedError::Stats(err.to_string())
+    }
+}
+
+/// Seconds since the Unix epoch, 0 for earlier times.
+pub(crate) fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(recorded_at: u64, version: &str, error_code: Option<i32>) -> SolveRecord {
+        SolveRecord {
+            recorded_at,
+            captcha_id: "id".into(),
+            risk_type: "slide".into(),
+            proxy: None,
+            duration_ms: 900,
+            error_code,
+            score: error_code.is_none().then_some(8),
+            constants_version: Some(version.into()),
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_success_rates_over_time_and_versions() {
+        let store = StatsStore::open_in_memory().unwrap();
+        store.record(&record(1000, "v1", None)).unwrap();
+        store.record(&record(1010, "v1", None)).unwrap();
+        store.record(&record(4000, "v2", Some(201))).unwrap();
+        store.record(&record(4100, "v2", None)).unwrap();
+
+        let total = store.success_rate(at(0), None).unwrap();
+        assert_eq!((total.solves, total.succeeded), (4, 3));
+        assert_eq!(total.average_score, Some(8.0));
+
+        let hourly = store
+            .success_rates(at(0), Duration::from_secs(3600), Some(&RiskType::Slide))
+            .unwrap();
+        assert_eq!(hourly.len(), 2);
+        assert_eq!((hourly[0].start, hourly[0].rate()), (0, 1.0));
+        assert_eq!((hourly[1].start, hourly[1].rate()), (3600, 0.5));
+
+        let by_version = store.success_rates_by_version(at(2000)).unwrap();
+        assert_eq!(by_version.len(), 1);
+        assert_eq!(by_version[0].0.as_deref(), Some("v2"));
+        assert_eq!(by_version[0].1.start, 4000);
+    }
+
+    #[test]
+    fn test_recent_and_empty_rate() {
+        let store = StatsStore::open_in_memory().unwrap();
+        assert_eq!(store.success_rate(at(0), None).unwrap().rate(), 0.0);
+
+        store.record(&record(1, "v1", Some(202))).unwrap();
+        store.record(&record(2, "v1", None)).unwrap();
+        let recent = store.recent(1).unwrap();
+        assert_eq!(recent, vec![record(2, "v1", None)]);
+        assert!(recent[0].succeeded());
+
+        let ai = store.success_rate(at(0), Some(&RiskType::Ai)).unwrap();
+        assert_eq!(ai.solves, 0);
+    }
+}
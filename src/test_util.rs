@@ -0,0 +1,88 @@
+//! Deterministic solves for tests (`test-util` feature).
+//!
+//! Replaces the randomness, clock and UUIDs of the v4 solve path for the
+//! current thread, so callback names, challenges, PoW nonces, passtime and
+//! the encrypted `w` come out the same on every run. Golden files of full
+//! payloads can then be compared with the Python and Go implementations:
+//!
+//! ```ignore
+//! let _guard = chaser_gt::test_util::deterministic(42)
+//!     .clock(1_700_000_000_000)
+//!     .uuids([uuid::Uuid::nil()]);
+//! let w = generate_w_parameter(&load, captcha_id, &risk_type, &constants, result)?;
+//! assert_eq!(w, include_str!("golden/slide_w.txt"));
+//! ```
+//!
+//! The overrides are thread-local: run async tests on a current-thread
+//! runtime (the `#[tokio::test]` default). Never enable this feature in
+//! production builds, since a seeded RNG also drives the encryption keys.
+
+use rand::SeedableRng;
+
+use crate::entropy::{Overrides, OVERRIDES};
+
+/// Active overrides; the real sources come back when dropped.
+#[must_use = "the overrides are removed when the guard is dropped"]
+pub struct Deterministic {
+    _private: (),
+}
+
+/// Use an RNG seeded with `seed` on this thread. UUIDs are drawn from it
+/// too, unless fixed with [`Deterministic::uuids`].
+pub fn deterministic(seed: u64) -> Deterministic {
+    OVERRIDES.with(|overrides| {
+        *overrides.borrow_mut() = Overrides {
+            rng: Some(rand::rngs::StdRng::seed_from_u64(seed)),
+            ..Default::default()
+        }
+    });
+    Deterministic { _private: () }
+}
+
+impl Deterministic {
+    /// Freeze the clock at `unix_millis`.
+    pub fn clock(self, unix_millis: u64) -> Self {
+        OVERRIDES.with(|overrides| overrides.borrow_mut().clock_millis = Some(unix_millis));
+        self
+    }
+
+    /// Hand out `uuids` in order before falling back to the seeded RNG.
+    pub fn uuids(self, uuids: impl IntoIterator<Item = uuid::Uuid>) -> Self {
+        OVERRIDES.with(|overrides| overrides.borrow_mut().uuids.extend(uuids));
+        self
+    }
+}
+
+impl Drop for Deterministic {
+    fn drop(&mut self) {
+        OVERRIDES.with(|overrides| *overrides.borrow_mut() = Overrides::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy;
+    use rand::RngCore;
+
+    #[test]
+    fn test_same_seed_same_values() {
+        let draw = || {
+            let _guard = deterministic(7).clock(1_000).uuids([uuid::Uuid::nil()]);
+            (
+                entropy::rng().next_u64(),
+                entropy::now_millis(),
+                entropy::uuid_v4(),
+                entropy::uuid_v4(),
+                crate::crypto::rand_uid(),
+            )
+        };
+        let first = draw();
+        assert_eq!(first, draw());
+        assert_eq!(first.1, 1_000);
+        assert_eq!(first.2, uuid::Uuid::nil());
+
+        // Real sources are back once the guard is gone
+        assert_ne!(entropy::now_millis(), 1_000);
+    }
+}
@@ -0,0 +1,369 @@
+//! Pre-solved tokens for latency-sensitive consumers.
+//!
+//! A [`TokenPool`] keeps up to [`depth`](TokenPoolConfig::depth) solved
+//! [`SecCode`]s per captcha ID, solving in the background as tokens are
+//! taken or grow too old to be accepted. [`TokenPool::get_token`] returns a
+//! pooled token immediately and only solves inline when the pool has run dry.
+//!
+//! ```ignore
+//! let pool = TokenPool::new(TokenPoolConfig::default());
+//! pool.add(Geeked::builder("captcha_id", RiskType::Slide).proxy_pool(proxies));
+//! let seccode = pool.get_token("captcha_id").await?;
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::client::GeekedBuilder;
+use crate::error::{GeekedError, Result};
+use crate::models::{RiskType, SecCode};
+
+/// Pool sizing and token lifetime.
+#[derive(Debug, Clone)]
+pub struct TokenPoolConfig {
+    /// Tokens kept ready per captcha ID
+    pub depth: usize,
+    /// Background solves running at once per captcha ID
+    pub concurrency: usize,
+    /// Age after which a token is discarded instead of handed out
+    /// (see [`SecCode::is_expired`])
+    pub max_age: Duration,
+    /// Pause after a failed background solve
+    pub retry_delay: Duration,
+}
+
+impl Default for TokenPoolConfig {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            concurrency: 1,
+            max_age: SecCode::MAX_AGE,
+            retry_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// What a refill worker should do next.
+enum Claim {
+    /// Solve one token; the slot is reserved
+    Solve,
+    /// The pool is full; wait for a token to be taken or, at most this
+    /// long, for the oldest one to expire
+    Wait(Option<Duration>),
+}
+
+#[derive(Default)]
+struct Tokens {
+    /// Ready tokens, oldest first
+    ready: VecDeque<SecCode>,
+    in_flight: usize,
+}
+
+/// Tokens for one captcha ID.
+struct Entry {
+    builder: GeekedBuilder,
+    tokens: Mutex<Tokens>,
+    /// Wakes refill workers when a token is taken
+    refill: Notify,
+}
+
+impl Entry {
+    fn tokens(&self) -> std::sync::MutexGuard<'_, Tokens> {
+        self.tokens.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Drop tokens older than `max_age`.
+    fn prune(tokens: &mut Tokens, max_age: Duration) {
+        while tokens
+            .ready
+            .front()
+            .is_some_and(|seccode| seccode.is_expired(max_age))
+        {
+            tokens.ready.pop_front();
+            tracing::debug!("Discarding expired pooled token");
+        }
+    }
+
+    /// Newest token that is still fresh.
+    fn take(&self, max_age: Duration) -> Option<SecCode> {
+        let token = {
+            let mut tokens = self.tokens();
+            Self::prune(&mut tokens, max_age);
+            tokens.ready.pop_back()
+        };
+        self.refill.notify_waiters();
+        token
+    }
+
+    fn claim(&self, config: &TokenPoolConfig) -> Claim {
+        let mut tokens = self.tokens();
+        Self::prune(&mut tokens, config.max_age);
+        if tokens.ready.len() + tokens.in_flight < config.depth {
+            tokens.in_flight += 1;
+            Claim::Solve
+        } else {
+            let next_expiry = tokens
+                .ready
+                .front()
+                .map(|seccode| config.max_age.saturating_sub(seccode.age()));
+            Claim::Wait(next_expiry)
+        }
+    }
+
+    fn finish(&self, seccode: Option<SecCode>) {
+        let mut tokens = self.tokens();
+        tokens.in_flight -= 1;
+        if let Some(seccode) = seccode {
+            tokens.ready.push_back(seccode);
+        }
+    }
+}
+
+/// An entry and the workers refilling it, stopped when dropped.
+struct Pooled {
+    entry: Arc<Entry>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Pooled {
+    fn drop(&mut self) {
+        self.workers.iter().for_each(JoinHandle::abort);
+    }
+}
+
+/// Keeps solved tokens ready for a set of captcha IDs.
+///
+/// Background solving stops when the pool is dropped.
+pub struct TokenPool {
+    config: TokenPoolConfig,
+    entries: Mutex<HashMap<String, Pooled>>,
+}
+
+impl TokenPool {
+    pub fn new(config: TokenPoolConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start keeping tokens for the builder's captcha ID.
+    ///
+    /// Every background solve builds a fresh client from a clone of
+    /// `builder`. Replaces an earlier builder for the same captcha ID.
+    /// Must be called within a Tokio runtime.
+    pub fn add(&self, builder: GeekedBuilder) {
+        let captcha_id = builder.captcha_id().to_string();
+        let entry = Arc::new(Entry {
+            builder,
+            tokens: Mutex::new(Tokens::default()),
+            refill: Notify::new(),
+        });
+        let workers = (0..self.config.concurrency.max(1))
+            .map(|_| tokio::spawn(refill(entry.clone(), self.config.clone())))
+            .collect();
+
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(captcha_id, Pooled { entry, workers });
+    }
+
+    /// Stop keeping tokens for `captcha_id`, discarding the pooled ones.
+    pub fn remove(&self, captcha_id: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(captcha_id);
+    }
+
+    fn entry(&self, captcha_id: &str) -> Option<Arc<Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(captcha_id)
+            .map(|pooled| pooled.entry.clone())
+    }
+
+    /// A pooled token, if a fresh one is ready.
+    pub fn try_get_token(&self, captcha_id: &str) -> Option<SecCode> {
+        self.entry(captcha_id)?.take(self.config.max_age)
+    }
+
+    /// A pooled token, if a fresh one is ready and the pool solves
+    /// `captcha_id` as `risk_type`.
+    pub fn try_get_token_for(&self, captcha_id: &str, risk_type: &RiskType) -> Option<SecCode> {
+        let entry = self.entry(captcha_id)?;
+        if entry.builder.risk_type() != risk_type {
+            return None;
+        }
+        entry.take(self.config.max_age)
+    }
+
+    /// A pooled token, or a newly solved one if the pool is empty.
+    ///
+    /// Fails with [`GeekedError::UnsupportedType`] if `captcha_id` was never
+    /// [`add`](Self::add)ed.
+    pub async fn get_token(&self, captcha_id: &str) -> Result<SecCode> {
+        let entry = self.entry(captcha_id).ok_or_else(|| {
+            GeekedError::UnsupportedType(format!("captcha_id {} is not pooled", captcha_id))
+        })?;
+        if let Some(seccode) = entry.take(self.config.max_age) {
+            return Ok(seccode);
+        }
+
+        tracing::debug!("Token pool for {} is empty, solving inline", captcha_id);
+        entry.builder.clone().build().await?.solve().await
+    }
+
+    /// Discard every pooled token, e.g. after the site started rejecting
+    /// them. Returns the number discarded; the pool refills in the background.
+    pub fn flush(&self) -> usize {
+        let entries: Vec<_> = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|pooled| pooled.entry.clone())
+            .collect();
+        entries
+            .iter()
+            .map(|entry| {
+                let flushed = std::mem::take(&mut entry.tokens().ready).len();
+                entry.refill.notify_waiters();
+                flushed
+            })
+            .sum()
+    }
+
+    /// Number of fresh tokens ready for `captcha_id`.
+    pub fn ready(&self, captcha_id: &str) -> usize {
+        self.entry(captcha_id).map_or(0, |entry| {
+            let mut tokens = entry.tokens();
+            Entry::prune(&mut tokens, self.config.max_age);
+            tokens.ready.len()
+        })
+    }
+}
+
+/// Keep `entry` topped up until aborted.
+async fn refill(entry: Arc<Entry>, config: TokenPoolConfig) {
+    loop {
+        // Register for wakeups before checking, so a token taken in
+        // between is not missed
+        let taken = entry.refill.notified();
+        tokio::pin!(taken);
+        taken.as_mut().enable();
+
+        match entry.claim(&config) {
+            Claim::Solve => {
+                let result = match entry.builder.clone().build().await {
+                    Ok(solver) => solver.solve().await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(seccode) => entry.finish(Some(seccode)),
+                    Err(e) => {
+                        entry.finish(None);
+                        tracing::warn!("Background solve for the token pool failed: {}", e);
+                        tokio::time::sleep(config.retry_delay).await;
+                    }
+                }
+            }
+            Claim::Wait(Some(expiry)) => {
+                tokio::select! {
+                    _ = taken => {}
+                    _ = tokio::time::sleep(expiry) => {}
+                }
+            }
+            Claim::Wait(None) => taken.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seccode(lot_number: &str, age_secs: i64) -> SecCode {
+        SecCode {
+            captcha_id: "id".into(),
+            lot_number: lot_number.into(),
+            pass_token: String::new(),
+            gen_time: String::new(),
+            captcha_output: String::new(),
+            solved_at: chrono::Utc::now() - chrono::Duration::seconds(age_secs),
+        }
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            builder: GeekedBuilder::new("id", RiskType::Slide),
+            tokens: Mutex::new(Tokens::default()),
+            refill: Notify::new(),
+        }
+    }
+
+    #[test]
+    fn test_expired_tokens_are_discarded() {
+        let config = TokenPoolConfig {
+            depth: 2,
+            ..Default::default()
+        };
+        let entry = entry();
+        entry
+            .tokens()
+            .ready
+            .extend([seccode("old", 30), seccode("new", 10)]);
+
+        // Full: wait until the oldest token expires
+        match entry.claim(&config) {
+            Claim::Wait(Some(expiry)) => assert!(expiry <= Duration::from_secs(30)),
+            _ => panic!("expected the pool to be full"),
+        }
+
+        let config = TokenPoolConfig {
+            max_age: Duration::from_secs(20),
+            ..config
+        };
+        assert!(matches!(entry.claim(&config), Claim::Solve));
+        assert_eq!(entry.tokens().in_flight, 1);
+        assert_eq!(entry.take(config.max_age).unwrap().lot_number, "new");
+        assert!(entry.take(config.max_age).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_captcha_id() {
+        let pool = TokenPool::new(TokenPoolConfig::default());
+        assert!(pool.try_get_token("missing").is_none());
+        assert_eq!(pool.ready("missing"), 0);
+        assert!(matches!(
+            pool.get_token("missing").await,
+            Err(GeekedError::UnsupportedType(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flush() {
+        let pool = TokenPool::new(TokenPoolConfig {
+            depth: 0,
+            ..Default::default()
+        });
+        pool.add(GeekedBuilder::new("id", RiskType::Slide));
+        pool.entry("id")
+            .unwrap()
+            .tokens()
+            .ready
+            .extend([seccode("a", 0), seccode("b", 0)]);
+
+        assert_eq!(pool.ready("id"), 2);
+        assert!(pool.try_get_token_for("id", &RiskType::Icon).is_none());
+        assert_eq!(pool.ready("id"), 2);
+        assert_eq!(pool.flush(), 2);
+        assert_eq!(pool.ready("id"), 0);
+    }
+}
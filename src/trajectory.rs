@@ -0,0 +1,117 @@
+//! Synthetic pointer trajectories for drag-style captchas.
+//!
+//! Geetest scores the mouse movement that accompanies a slide answer, so a
+//! solve has to submit a plausible drag instead of a single jump to the target.
+
+use rand::Rng;
+
+/// A single pointer sample, relative to where the drag started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackPoint {
+    /// Horizontal offset in pixels
+    pub x: i32,
+    /// Vertical offset in pixels
+    pub y: i32,
+    /// Milliseconds since the pointer was pressed
+    pub t: u32,
+}
+
+/// A synthetic drag trajectory.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    points: Vec<TrackPoint>,
+}
+
+impl Trajectory {
+    /// Generate a human-like horizontal drag covering `distance` pixels.
+    ///
+    /// The first sample is the press offset inside the slider knob, followed by
+    /// an ease-out motion with small vertical jitter and a short hold at the
+    /// target before release.
+    pub fn slide(distance: f64) -> Self {
+        let mut rng = crate::entropy::rng();
+        let target = distance.round() as i32;
+
+        let mut points = vec![
+            TrackPoint {
+                x: -rng.gen_range(18..=38),
+                y: -rng.gen_range(18..=38),
+                t: 0,
+            },
+            TrackPoint { x: 0, y: 0, t: 0 },
+        ];
+
+        // Longer drags take longer, but not linearly
+        let duration = 400.0 + distance.abs().sqrt() * 60.0 + rng.gen_range(0.0..250.0);
+        let mut t = 0u32;
+        let mut y = 0i32;
+
+        loop {
+            t += rng.gen_range(8..=20);
+            let progress = (t as f64 / duration).min(1.0);
+            // Ease-out quart: fast start, slow approach
+            let eased = 1.0 - (1.0 - progress).powi(4);
+            let x = (distance * eased).round() as i32;
+
+            if rng.gen_bool(0.15) {
+                y = (y + rng.gen_range(-1..=1)).clamp(-3, 3);
+            }
+
+            points.push(TrackPoint { x, y, t });
+
+            if progress >= 1.0 {
+                break;
+            }
+        }
+
+        // Hold at the target for a moment before releasing
+        for _ in 0..rng.gen_range(1..=3) {
+            t += rng.gen_range(40..=160);
+            points.push(TrackPoint { x: target, y, t });
+        }
+
+        Self { points }
+    }
+
+    /// Create a trajectory from recorded points.
+    pub fn from_points(points: Vec<TrackPoint>) -> Self {
+        Self { points }
+    }
+
+    /// The samples making up this trajectory.
+    pub fn points(&self) -> &[TrackPoint] {
+        &self.points
+    }
+
+    /// Total time from press to release in milliseconds.
+    pub fn duration(&self) -> u32 {
+        self.points.last().map(|p| p.t).unwrap_or(0)
+    }
+
+    /// Final horizontal offset of the trajectory.
+    pub fn end_x(&self) -> i32 {
+        self.points.last().map(|p| p.x).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slide_reaches_target() {
+        let track = Trajectory::slide(123.4);
+
+        assert_eq!(track.end_x(), 123);
+        assert!(track.duration() > 0);
+    }
+
+    #[test]
+    fn test_slide_time_is_monotonic() {
+        let track = Trajectory::slide(200.0);
+
+        for pair in track.points().windows(2) {
+            assert!(pair[1].t >= pair[0].t);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! HTTP layer used by [`Geeked`](crate::Geeked) and the
+//! [`Deobfuscator`](crate::deobfuscate::Deobfuscator).
+//!
+//! Production uses [`rquest::Client`]; tests plug in a [`MockTransport`]
+//! serving recorded responses, so the client logic runs without touching
+//! Geetest:
+//!
+//! ```ignore
+//! let transport = MockTransport::new()
+//!     .fixture("https://gcaptcha4.geetest.com/load", 200, include_str!("load.jsonp"));
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .transport(Arc::new(transport))
+//!     .build()
+//!     .await?;
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, PoisonError};
+
+use rquest::header::SET_COOKIE;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{GeekedError, Result};
+
+/// Future returned by [`Transport::send`].
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<TransportResponse>> + Send + 'a>>;
+
+/// A GET request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportRequest {
+    pub url: String,
+    /// Query parameters, in order
+    pub query: Vec<(String, String)>,
+    /// Largest response body accepted, in bytes; unlimited if unset
+    pub max_body: Option<usize>,
+}
+
+impl TransportRequest {
+    /// GET `url`.
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            query: Vec::new(),
+            max_body: None,
+        }
+    }
+
+    /// Fail instead of reading a response body over `bytes` long.
+    pub fn max_body(mut self, bytes: usize) -> Self {
+        self.max_body = Some(bytes);
+        self
+    }
+
+    /// Append query parameters.
+    pub fn query(mut self, params: &[(&str, &str)]) -> Self {
+        self.query.extend(
+            params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        self
+    }
+
+    /// Value of the query parameter `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Status, cookies and body of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportResponse {
+    pub status: u16,
+    /// `Set-Cookie` header values, in order
+    pub set_cookies: Vec<String>,
+    pub body: Vec<u8>,
+}
+
+impl TransportResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Body as text, with invalid UTF-8 replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Largest response bodies the client reads, so a misbehaving proxy or CDN
+/// cannot feed it hundreds of megabytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BodyLimits {
+    /// `/load` and `/verify` replies, in bytes
+    pub jsonp: usize,
+    /// Captcha images, in bytes
+    pub image: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            jsonp: 1024 * 1024,
+            image: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Error for a response body over the request's `max_body`.
+fn body_too_large(request: &TransportRequest, status: u16, limit: usize) -> GeekedError {
+    let reason = format!("body exceeds the {}-byte limit", limit);
+    GeekedError::unexpected_response(&request.url, status, &reason, None)
+}
+
+/// Sends requests on behalf of the client.
+///
+/// Implementations fail requests whose response body is over
+/// [`TransportRequest::max_body`].
+pub trait Transport: Send + Sync {
+    fn send(&self, request: TransportRequest) -> TransportFuture<'_>;
+}
+
+impl Transport for rquest::Client {
+    fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let mut response = self.get(&request.url).query(&request.query).send().await?;
+            let status = response.status().as_u16();
+            let set_cookies = response
+                .headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .map(str::to_string)
+                .collect();
+            let Some(limit) = request.max_body else {
+                let body = response.bytes().await?.to_vec();
+                return Ok(TransportResponse {
+                    status,
+                    set_cookies,
+                    body,
+                });
+            };
+            if response
+                .content_length()
+                .is_some_and(|length| length > limit as u64)
+            {
+                return Err(body_too_large(&request, status, limit));
+            }
+            // Stop reading as soon as the body outgrows the limit
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                if body.len() + chunk.len() > limit {
+                    return Err(body_too_large(&request, status, limit));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(TransportResponse {
+                status,
+                set_cookies,
+                body,
+            })
+        })
+    }
+}
+
+/// Transport answering from recorded responses.
+///
+/// Responses are matched by URL without the query string and served in
+/// the order they were added; the last one keeps being served. JSONP bodies
+/// are rewrapped in the callback the request asks for, so fixtures can be
+/// recorded with any callback name. Unmatched URLs fail with
+/// [`GeekedError::UnexpectedResponse`] (status 404).
+#[derive(Default)]
+pub struct MockTransport {
+    fixtures: Mutex<HashMap<String, Vec<TransportResponse>>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `body` with `status` for `url`.
+    pub fn fixture(self, url: impl Into<String>, status: u16, body: impl Into<Vec<u8>>) -> Self {
+        let response = TransportResponse {
+            status,
+            set_cookies: Vec::new(),
+            body: body.into(),
+        };
+        self.fixtures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(url.into())
+            .or_default()
+            .push(response);
+        self
+    }
+
+    /// Add a `Set-Cookie` header to the last fixture for `url`.
+    pub fn set_cookie(self, url: &str, cookie: impl Into<String>) -> Self {
+        if let Some(response) = self
+            .fixtures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(url)
+            .and_then(|queue| queue.last_mut())
+        {
+            response.set_cookies.push(cookie.into());
+        }
+        self
+    }
+
+    /// Requests sent so far, oldest first.
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn respond(&self, request: &TransportRequest) -> Option<TransportResponse> {
+        let mut fixtures = self.fixtures.lock().unwrap_or_else(PoisonError::into_inner);
+        let queue = fixtures.get_mut(&request.url)?;
+        let mut response = match queue.len() {
+            0 => return None,
+            1 => queue[0].clone(),
+            _ => queue.remove(0),
+        };
+        if let Some(callback) = request.param("callback") {
+            response.body = rewrap_jsonp(&response.body, callback);
+        }
+        Some(response)
+    }
+}
+
+/// Replace the callback name of a `name({...})` body.
+fn rewrap_jsonp(body: &[u8], callback: &str) -> Vec<u8> {
+    let text = String::from_utf8_lossy(body);
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    };
+    match text.split_once('(') {
+        Some((name, rest)) if is_name(name.trim()) => format!("{}({}", callback, rest).into_bytes(),
+        _ => body.to_vec(),
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: TransportRequest) -> TransportFuture<'_> {
+        let response = match self.respond(&request) {
+            Some(response) => match request.max_body {
+                Some(limit) if response.body.len() > limit => {
+                    Err(body_too_large(&request, response.status, limit))
+                }
+                _ => Ok(response),
+            },
+            None => Err(GeekedError::unexpected_response(
+                &request.url,
+                404,
+                "no fixture for this URL",
+                None,
+            )),
+        };
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request);
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_transport() {
+        let url = "https://gcaptcha4.geetest.com/load";
+        let mock = MockTransport::new()
+            .fixture(url, 200, r#"geetest_1({"status": "success"})"#)
+            .fixture(url, 500, "oops");
+
+        let request = TransportRequest::get(url).query(&[("callback", "geetest_42")]);
+        let first = mock.send(request.clone()).await.unwrap();
+        assert_eq!(first.text(), r#"geetest_42({"status": "success"})"#);
+        assert_eq!(mock.send(request.clone()).await.unwrap().status, 500);
+        assert_eq!(mock.send(request).await.unwrap().status, 500);
+
+        let missing = mock
+            .send(TransportRequest::get("https://example.com/"))
+            .await;
+        assert!(matches!(
+            missing,
+            Err(GeekedError::UnexpectedResponse { status: 404, .. })
+        ));
+        assert_eq!(mock.requests()[0].param("callback"), Some("geetest_42"));
+    }
+
+    #[tokio::test]
+    async fn test_max_body() {
+        let url = "https://static.geetest.com/bg.png";
+        let mock = MockTransport::new().fixture(url, 200, vec![0; 100]);
+
+        let fits = TransportRequest::get(url).max_body(100);
+        assert_eq!(mock.send(fits).await.unwrap().body.len(), 100);
+        let too_large = mock.send(TransportRequest::get(url).max_body(99)).await;
+        assert!(matches!(
+            too_large,
+            Err(GeekedError::UnexpectedResponse { status: 200, ref body, .. })
+                if body.contains("99-byte limit")
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+//! Typed `user_info` values.
+//!
+//! Sites that bind captchas to an account pass `user_info` either as
+//! `key=value` pairs or as JSON. [`UserInfo`] builds both, percent-encoding
+//! the pairs so values containing `&` or `=` survive:
+//!
+//! ```ignore
+//! let solver = Geeked::builder("captcha_id", RiskType::Slide)
+//!     .user_info(UserInfo::new().pair("account_id", "12345").pair("name", "a&b"))
+//!     .build()
+//!     .await?;
+//! // user_info = "account_id=12345&name=a%26b"
+//! ```
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::Result;
+
+/// A `user_info` value, passed to
+/// [`GeekedBuilder::user_info`](crate::GeekedBuilder::user_info) in place of
+/// a raw string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserInfo {
+    encoded: String,
+}
+
+impl UserInfo {
+    /// Empty `key=value` pairs; add them with [`pair`](Self::pair).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `key=value` pairs, in order.
+    pub fn from_pairs<I, K, V>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        pairs
+            .into_iter()
+            .fold(Self::new(), |info, (key, value)| info.pair(key, value))
+    }
+
+    /// `value` serialized as JSON, for sites that expect an object.
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Result<Self> {
+        Ok(Self {
+            encoded: serde_json::to_string(value)?,
+        })
+    }
+
+    /// Append `key=value`, percent-encoding both.
+    pub fn pair(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        if !self.encoded.is_empty() {
+            self.encoded.push('&');
+        }
+        self.encoded.push_str(&urlencoding::encode(key.as_ref()));
+        self.encoded.push('=');
+        self.encoded.push_str(&urlencoding::encode(value.as_ref()));
+        self
+    }
+
+    /// The string sent to Geetest.
+    pub fn as_str(&self) -> &str {
+        &self.encoded
+    }
+}
+
+impl fmt::Display for UserInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encoded)
+    }
+}
+
+impl From<UserInfo> for String {
+    fn from(info: UserInfo) -> Self {
+        info.encoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairs() {
+        let info = UserInfo::new()
+            .pair("account_id", "12345")
+            .pair("name", "a&b=c d");
+        assert_eq!(info.as_str(), "account_id=12345&name=a%26b%3Dc%20d");
+        assert_eq!(
+            UserInfo::from_pairs([("account_id", "12345"), ("name", "a&b=c d")]),
+            info
+        );
+        assert_eq!(UserInfo::new().as_str(), "");
+    }
+
+    #[test]
+    fn test_json() {
+        #[derive(Serialize)]
+        struct Session<'a> {
+            uid: u64,
+            sid: &'a str,
+        }
+
+        let info = UserInfo::json(&Session {
+            uid: 42,
+            sid: "x\"y",
+        })
+        .unwrap();
+        assert_eq!(String::from(info), r#"{"uid":42,"sid":"x\"y"}"#);
+    }
+}
@@ -0,0 +1,424 @@
+//! Client for solving Geetest v3 captchas.
+
+use super::crypto::{encode_track, encrypt_w, mix_track, rp, user_response};
+use super::models::{
+    AjaxResponse, ClickChallenge, SlideChallenge, TypeResponse, V3CaptchaType, V3SecCode,
+};
+use super::solvers::{encode_clicks, find_slide_distance, ClickSolver};
+use crate::error::{GeekedError, Result};
+use crate::trajectory::{TrackPoint, Trajectory};
+use rand::Rng;
+use rquest::{Client, Proxy};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// API server for v3 requests.
+const API_SERVER: &str = "https://api.geetest.com";
+
+/// Static server used when the challenge does not name one.
+const DEFAULT_STATIC_SERVER: &str = "static.geetest.com";
+
+/// `h9s9` field of the `w` payload, a literal in Geetest's v3 `slide.js`
+/// and `click.js` rather than anything derived from the challenge.
+const H9S9: &str = "1816378497";
+
+/// Builder for creating a Geetest v3 client.
+pub struct GeekedV3Builder {
+    gt: String,
+    challenge: String,
+    captcha_type: V3CaptchaType,
+    proxy: Option<String>,
+    local_address: Option<IpAddr>,
+}
+
+impl GeekedV3Builder {
+    /// Create a new builder from the site's `gt` and a fresh `challenge`.
+    pub fn new(gt: impl Into<String>, challenge: impl Into<String>) -> Self {
+        Self {
+            gt: gt.into(),
+            challenge: challenge.into(),
+            captcha_type: V3CaptchaType::default(),
+            proxy: None,
+            local_address: None,
+        }
+    }
+
+    /// Set the captcha type to request (defaults to slide).
+    pub fn captcha_type(mut self, captcha_type: V3CaptchaType) -> Self {
+        self.captcha_type = captcha_type;
+        self
+    }
+
+    /// Set HTTP/SOCKS5 proxy.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Set local address to bind outgoing connections to.
+    pub fn local_address(mut self, addr: IpAddr) -> Self {
+        self.local_address = Some(addr);
+        self
+    }
+
+    /// Build the v3 client.
+    pub fn build(self) -> Result<GeekedV3> {
+        let mut builder = Client::builder();
+
+        if let Some(addr) = self.local_address {
+            builder = builder.local_address(addr);
+        }
+
+        if let Some(proxy_url) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy_url)?);
+        }
+
+        Ok(GeekedV3 {
+            client: builder.build()?,
+            gt: self.gt,
+            challenge: self.challenge,
+            captcha_type: self.captcha_type,
+            proxy: self.proxy,
+        })
+    }
+}
+
+/// Geetest v3 captcha solver client.
+///
+/// # Example
+/// ```ignore
+/// use chaser_gt::Geeked;
+///
+/// // gt and challenge come from the target site's register endpoint
+/// let solver = Geeked::builder_v3(gt, challenge).build()?;
+/// let result = solver.solve().await?;
+/// println!("validate: {}", result.validate);
+/// ```
+pub struct GeekedV3 {
+    client: Client,
+    gt: String,
+    challenge: String,
+    captcha_type: V3CaptchaType,
+    proxy: Option<String>,
+}
+
+impl GeekedV3 {
+    /// Create a builder for the v3 client.
+    pub fn builder(gt: impl Into<String>, challenge: impl Into<String>) -> GeekedV3Builder {
+        GeekedV3Builder::new(gt, challenge)
+    }
+
+    /// Generate a random callback string.
+    fn random_callback() -> String {
+        format!("geetest_{}", now_millis())
+    }
+
+    /// Parse a JSONP response from the v3 API.
+    ///
+    /// `gettype.php` wraps its data in `{"status", "data"}`, while `get.php`
+    /// and `ajax.php` return the object directly.
+    fn parse_jsonp<T: serde::de::DeserializeOwned>(response: &str) -> Result<T> {
+        let start = response.find('(');
+        let end = response.rfind(')');
+        let json_str = match (start, end) {
+            (Some(start), Some(end)) if start < end => &response[start + 1..end],
+            _ => return Err(GeekedError::InvalidResponse("Invalid JSONP format".into())),
+        };
+
+        let mut value: Value = serde_json::from_str(json_str)?;
+
+        if let Some(status) = value.get("status").and_then(Value::as_str) {
+            if status != "success" {
+                let field = |keys: &[&str]| {
+                    keys.iter()
+                        .find_map(|key| match value.get(key)? {
+                            Value::String(s) => Some(s.clone()),
+                            Value::Number(n) => Some(n.to_string()),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| status.to_string())
+                };
+                return Err(GeekedError::Geetest {
+                    code: field(&["error_code", "code"]),
+                    message: field(&["error", "msg"]),
+                });
+            }
+            if let Some(data) = value.get_mut("data") {
+                value = data.take();
+            }
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Perform a JSONP GET request against the v3 API.
+    async fn get_jsonp<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T> {
+        let callback = Self::random_callback();
+        let mut query = params.to_vec();
+        query.push(("callback", callback.as_str()));
+
+        let url = format!("{}/{}", API_SERVER, path);
+        let response = self.client.get(&url).query(&query).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() || !body.contains(&format!("{}(", callback)) {
+            return Err(GeekedError::unexpected_response(
+                &url,
+                status.as_u16(),
+                &body,
+                self.proxy.as_deref(),
+            ));
+        }
+
+        Self::parse_jsonp(&body)
+    }
+
+    /// Register the gt with the API and fetch its configuration.
+    async fn get_type(&self) -> Result<TypeResponse> {
+        self.get_jsonp("gettype.php", &[("gt", self.gt.as_str())])
+            .await
+    }
+
+    /// Fetch the challenge data for the configured captcha type.
+    async fn get_challenge<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        self.get_jsonp(
+            "get.php",
+            &[
+                ("is_next", "true"),
+                ("type", self.captcha_type.as_str()),
+                ("gt", self.gt.as_str()),
+                ("challenge", self.challenge.as_str()),
+                ("lang", "zh-cn"),
+                ("https", "true"),
+                ("protocol", "https://"),
+                ("offline", "false"),
+                ("product", "embed"),
+                ("api_server", "api.geetest.com"),
+                ("isPC", "true"),
+                ("autoReset", "true"),
+                ("width", "100%"),
+            ],
+        )
+        .await
+    }
+
+    /// Download an image from the static server.
+    async fn download_image(&self, static_servers: &[String], path: &str) -> Result<Vec<u8>> {
+        let server = static_servers
+            .first()
+            .map(|s| s.trim_end_matches('/'))
+            .unwrap_or(DEFAULT_STATIC_SERVER);
+        let url = format!("https://{}/{}", server, path.trim_start_matches('/'));
+        let bytes = self.client.get(&url).send().await?.bytes().await?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Submit the encrypted answer.
+    async fn submit(&self, challenge: &str, w: &str) -> Result<AjaxResponse> {
+        self.get_jsonp(
+            "ajax.php",
+            &[
+                ("gt", self.gt.as_str()),
+                ("challenge", challenge),
+                ("lang", "zh-cn"),
+                ("$_BCN", "0"),
+                ("client_type", "web"),
+                ("w", w),
+            ],
+        )
+        .await
+    }
+
+    /// Build the `ep` environment block shared by all answer payloads.
+    fn environment() -> Value {
+        let start = now_millis();
+        let mut rng = rand::thread_rng();
+        let fetch = start + rng.gen_range(1..5);
+        let dns = fetch + rng.gen_range(0..3);
+        let connect = dns + rng.gen_range(10..60);
+        let response = connect + rng.gen_range(20..120);
+
+        json!({
+            "v": "7.9.2",
+            "te": false,
+            "me": true,
+            "tm": {
+                "a": start,
+                "b": fetch,
+                "c": fetch,
+                "d": 0,
+                "e": 0,
+                "f": fetch,
+                "g": dns,
+                "h": dns,
+                "i": dns,
+                "j": connect,
+                "k": connect,
+                "l": response,
+                "m": response + rng.gen_range(1..10),
+                "n": response + rng.gen_range(10..40),
+                "o": response + rng.gen_range(40..120),
+                "p": response + rng.gen_range(200..600),
+                "q": response + rng.gen_range(200..600),
+                "r": response + rng.gen_range(600..900),
+                "s": response + rng.gen_range(900..1200),
+                "t": response + rng.gen_range(900..1200),
+                "u": response + rng.gen_range(900..1200)
+            },
+            "td": -1
+        })
+    }
+
+    /// Solve a slide challenge and build its `w` parameter.
+    async fn solve_slide(&self) -> Result<(String, String)> {
+        let data: SlideChallenge = self.get_challenge().await?;
+
+        let (bg, slice) = tokio::try_join!(
+            self.download_image(&data.static_servers, &data.bg),
+            self.download_image(&data.static_servers, &data.slice)
+        )?;
+
+        let distance = find_slide_distance(&bg, &slice)?;
+        let track = Trajectory::slide(distance);
+        let passtime = track.duration();
+
+        let payload = json!({
+            "lang": "zh-cn",
+            "userresponse": user_response(track.end_x() as f64, &data.challenge),
+            "passtime": passtime,
+            "imgload": rand::thread_rng().gen_range(50..150),
+            "aa": mix_track(&encode_track(&track), &data.c, &data.s),
+            "ep": Self::environment(),
+            "h9s9": H9S9,
+            "rp": rp(&self.gt, &data.challenge, passtime),
+        });
+
+        let w = encrypt_w(&serde_json::to_string(&payload)?)?;
+        Ok((data.challenge, w))
+    }
+
+    /// Solve a click challenge and build its `w` parameter.
+    async fn solve_click(&self) -> Result<(String, String)> {
+        let data: ClickChallenge = self.get_challenge().await?;
+
+        let pic = self.download_image(&data.static_servers, &data.pic).await?;
+        let solver = ClickSolver::from_bytes(&pic)?;
+        let targets = solver.find_targets();
+        if targets.is_empty() {
+            return Err(GeekedError::VerificationFailed {
+                message: "No click targets found".into(),
+            });
+        }
+
+        // Pointer path visiting each target with a human-ish pause in between
+        let mut rng = rand::thread_rng();
+        let mut t = 0;
+        let points = targets
+            .iter()
+            .map(|&(x, y)| {
+                t += rng.gen_range(400..900);
+                TrackPoint {
+                    x: x.round() as i32,
+                    y: y.round() as i32,
+                    t,
+                }
+            })
+            .collect();
+        let track = Trajectory::from_points(points);
+        let passtime = track.duration() + rng.gen_range(200..600);
+
+        let payload = json!({
+            "lang": "zh-cn",
+            "passtime": passtime,
+            "a": encode_clicks(&targets, solver.click_area()),
+            "pic": data.pic,
+            "tt": mix_track(&encode_track(&track), &data.c, &data.s),
+            "ep": Self::environment(),
+            "h9s9": H9S9,
+            "rp": rp(&self.gt, &data.challenge, passtime),
+        });
+
+        let w = encrypt_w(&serde_json::to_string(&payload)?)?;
+        Ok((data.challenge, w))
+    }
+
+    /// Solve the captcha and return the validation triple.
+    pub async fn solve(&self) -> Result<V3SecCode> {
+        let gettype = self.get_type().await?;
+        tracing::debug!("v3 gettype: type={}", gettype.captcha_type);
+
+        let (challenge, w) = match self.captcha_type {
+            V3CaptchaType::Slide => self.solve_slide().await?,
+            V3CaptchaType::Click => self.solve_click().await?,
+        };
+
+        let response = self.submit(&challenge, &w).await?;
+        tracing::debug!(
+            "v3 ajax: success={}, message={}, score={:?}",
+            response.success,
+            response.message,
+            response.score
+        );
+
+        match response.validate {
+            Some(validate) if response.success == 1 && !validate.is_empty() => {
+                Ok(V3SecCode::new(challenge, validate))
+            }
+            _ => Err(GeekedError::VerificationFailed {
+                message: response.message,
+            }),
+        }
+    }
+
+    /// Get the gt value.
+    pub fn gt(&self) -> &str {
+        &self.gt
+    }
+
+    /// Get the captcha type.
+    pub fn captcha_type(&self) -> V3CaptchaType {
+        self.captcha_type
+    }
+}
+
+/// Current Unix time in milliseconds.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_jsonp_wrapped() {
+        let response = r#"geetest_1({"status": "success", "data": {"type": "fullpage"}})"#;
+        let data: TypeResponse = GeekedV3::parse_jsonp(response).unwrap();
+        assert_eq!(data.captcha_type, "fullpage");
+    }
+
+    #[test]
+    fn test_parse_jsonp_plain() {
+        let response = r#"geetest_1({"success": 1, "message": "success", "validate": "abc"})"#;
+        let data: AjaxResponse = GeekedV3::parse_jsonp(response).unwrap();
+        assert_eq!(data.success, 1);
+        assert_eq!(data.validate.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_parse_jsonp_error() {
+        let response =
+            r#"geetest_1({"status": "error", "error": "illegal gt", "error_code": "error_02"})"#;
+        let result: Result<TypeResponse> = GeekedV3::parse_jsonp(response);
+        assert!(result.unwrap_err().is_captcha_id_invalid());
+    }
+}
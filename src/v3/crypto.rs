@@ -0,0 +1,299 @@
+//! Encoding and encryption for the Geetest v3 `w` parameter.
+//!
+//! v3 uses the same RSA key and AES-CBC scheme as v4, but encodes the AES
+//! output with a custom base64 alphabet and obfuscates the drag track.
+
+use crate::crypto::{encrypt_aes_cbc, encrypt_rsa, rand_uid};
+use crate::error::Result;
+use crate::trajectory::Trajectory;
+use md5::{Digest, Md5};
+use rand::Rng;
+
+/// Alphabet of Geetest's custom base64 encoding.
+const BASE64_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789()";
+
+/// Padding character of Geetest's custom base64 encoding.
+const BASE64_PAD: char = '.';
+
+/// Bit masks selecting the four 6-bit groups out of each 24-bit chunk.
+const BASE64_MASKS: [u32; 4] = [7274496, 9483264, 19220, 235];
+
+/// Alphabet used to encode individual track deltas.
+const TRACK_TABLE: &[u8] = b"()*,-./0123456789:?@ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqr";
+
+/// Small (dx, dy) steps that get a single-character encoding.
+const TRACK_STEPS: [(i32, i32); 9] = [
+    (1, 0),
+    (2, 0),
+    (1, -1),
+    (1, 1),
+    (0, 1),
+    (0, -1),
+    (3, 0),
+    (2, -1),
+    (2, 1),
+];
+
+/// Characters for the entries of [`TRACK_STEPS`].
+const TRACK_STEP_CHARS: &[u8] = b"stuvwxyz~";
+
+/// Encrypt a v3 `w` payload.
+///
+/// The payload is AES-encrypted with a random key, encoded with Geetest's
+/// base64 variant, and followed by the RSA-encrypted key.
+pub fn encrypt_w(payload: &str) -> Result<String> {
+    let key = rand_uid();
+    let encrypted = encrypt_aes_cbc(payload, &key)?;
+    Ok(encode_base64(&encrypted) + &encrypt_rsa(&key)?)
+}
+
+/// Encode bytes with Geetest's base64 variant.
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len() * 4 / 3 + 4);
+
+    for chunk in data.chunks(3) {
+        let value = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+
+        // n input bytes produce n + 1 output characters
+        for mask in &BASE64_MASKS[..chunk.len() + 1] {
+            result.push(BASE64_TABLE[extract_bits(value, *mask) as usize] as char);
+        }
+        for _ in chunk.len()..3 {
+            result.push(BASE64_PAD);
+        }
+    }
+
+    result
+}
+
+/// Collect the bits of `value` selected by `mask` into a compact integer.
+fn extract_bits(value: u32, mask: u32) -> u32 {
+    (0..24)
+        .rev()
+        .filter(|bit| (mask >> bit) & 1 == 1)
+        .fold(0, |acc, bit| (acc << 1) | ((value >> bit) & 1))
+}
+
+/// Compute the `userresponse` field for a slide distance.
+///
+/// The distance is offset by a value derived from the last two characters of
+/// the challenge and then expressed as a sum of challenge characters.
+pub fn user_response(distance: f64, challenge: &str) -> String {
+    let chars: Vec<char> = challenge.chars().collect();
+    let digit = |c: char| {
+        let code = c as i64;
+        if code > 57 {
+            code - 87
+        } else {
+            code - 48
+        }
+    };
+
+    let offset = match chars.get(32..34) {
+        Some([a, b]) => 36 * digit(*a) + digit(*b),
+        _ => 0,
+    };
+    let mut remaining = distance.round() as i64 + offset;
+
+    // Distribute the first 32 characters round-robin into 5 buckets of unique chars
+    let mut buckets: Vec<Vec<char>> = vec![Vec::new(); 5];
+    let mut seen = std::collections::HashSet::new();
+    let mut bucket = 0;
+    for &c in chars.iter().take(32) {
+        if seen.insert(c) {
+            buckets[bucket].push(c);
+            bucket = (bucket + 1) % 5;
+        }
+    }
+
+    let mut weights = vec![1i64, 2, 5, 10, 50];
+    let mut rng = rand::thread_rng();
+    let mut result = String::new();
+
+    while remaining > 0 && !weights.is_empty() {
+        let idx = weights.len() - 1;
+        if remaining >= weights[idx] && !buckets[idx].is_empty() {
+            let pick = rng.gen_range(0..buckets[idx].len());
+            result.push(buckets[idx][pick]);
+            remaining -= weights[idx];
+        } else {
+            buckets.pop();
+            weights.pop();
+        }
+    }
+
+    result
+}
+
+/// Encode a drag trajectory into Geetest's compact track format.
+pub fn encode_track(track: &Trajectory) -> String {
+    let mut xs = String::new();
+    let mut ys = String::new();
+    let mut ts = String::new();
+
+    for (dx, dy, dt) in track_deltas(track) {
+        match TRACK_STEPS.iter().position(|&step| step == (dx, dy)) {
+            Some(idx) => ys.push(TRACK_STEP_CHARS[idx] as char),
+            None => {
+                xs.push_str(&encode_track_value(dx));
+                ys.push_str(&encode_track_value(dy));
+            }
+        }
+        ts.push_str(&encode_track_value(dt));
+    }
+
+    format!("{}!!{}!!{}", xs, ys, ts)
+}
+
+/// Convert absolute track points into merged deltas.
+///
+/// Samples where the pointer did not move are folded into the time of the
+/// next movement, matching the reference script.
+fn track_deltas(track: &Trajectory) -> Vec<(i32, i32, i32)> {
+    let mut deltas = Vec::new();
+    let mut idle = 0;
+    let mut last = (0, 0);
+
+    for pair in track.points().windows(2) {
+        let dx = pair[1].x - pair[0].x;
+        let dy = pair[1].y - pair[0].y;
+        let dt = pair[1].t as i32 - pair[0].t as i32;
+
+        if dx == 0 && dy == 0 && dt == 0 {
+            continue;
+        }
+        last = (dx, dy);
+        if dx == 0 && dy == 0 {
+            idle += dt;
+        } else {
+            deltas.push((dx, dy, dt + idle));
+            idle = 0;
+        }
+    }
+
+    if idle != 0 {
+        deltas.push((last.0, last.1, idle));
+    }
+
+    deltas
+}
+
+/// Encode a single track value.
+fn encode_track_value(value: i32) -> String {
+    let len = TRACK_TABLE.len() as i32;
+    let abs = value.abs();
+    let high = (abs / len).min(len - 1);
+
+    let mut result = String::new();
+    if value < 0 {
+        result.push('!');
+    }
+    if high > 0 {
+        result.push('$');
+        result.push(TRACK_TABLE[high as usize] as char);
+    }
+    result.push(TRACK_TABLE[(abs % len) as usize] as char);
+    result
+}
+
+/// Mix the encoded track with the `c` and `s` values from the challenge.
+///
+/// Every byte of the hex string `s` is inserted into the track at a position
+/// derived from the quadratic coefficients in `c`.
+pub fn mix_track(encoded: &str, c: &[i64], s: &str) -> String {
+    if c.len() < 5 || s.is_empty() || encoded.is_empty() {
+        return encoded.to_string();
+    }
+
+    let base_len = encoded.chars().count() as i64;
+    let mut result: Vec<char> = encoded.chars().collect();
+
+    let bytes = s.as_bytes();
+    for pair in bytes.chunks(2) {
+        let Ok(hex) = std::str::from_utf8(pair) else {
+            continue;
+        };
+        let Ok(value) = i64::from_str_radix(hex, 16) else {
+            continue;
+        };
+        let pos = ((c[0] * value * value + c[2] * value + c[4]) % base_len) as usize;
+        result.insert(pos.min(result.len()), value as u8 as char);
+    }
+
+    result.into_iter().collect()
+}
+
+/// Compute the `rp` checksum over gt, challenge and passtime.
+pub fn rp(gt: &str, challenge: &str, passtime: u32) -> String {
+    let challenge_prefix: String = challenge.chars().take(32).collect();
+    let mut hasher = Md5::new();
+    hasher.update(format!("{}{}{}", gt, challenge_prefix, passtime).as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trajectory::TrackPoint;
+
+    #[test]
+    fn test_encode_base64_lengths() {
+        assert_eq!(encode_base64(b"abc").len(), 4);
+        assert!(encode_base64(b"ab").ends_with('.'));
+        assert!(encode_base64(b"a").ends_with(".."));
+        assert!(encode_base64(b"abcdef")
+            .bytes()
+            .all(|b| BASE64_TABLE.contains(&b)));
+    }
+
+    #[test]
+    fn test_user_response_sums_to_distance() {
+        let challenge = "0123456789abcdef0123456789abcdefa0";
+        let response = user_response(10.0, challenge);
+
+        // Recompute the sum from the bucket weights
+        let chars: Vec<char> = challenge.chars().take(32).collect();
+        let mut buckets: Vec<Vec<char>> = vec![Vec::new(); 5];
+        let mut seen = std::collections::HashSet::new();
+        let mut bucket = 0;
+        for c in chars {
+            if seen.insert(c) {
+                buckets[bucket].push(c);
+                bucket = (bucket + 1) % 5;
+            }
+        }
+        let weights = [1, 2, 5, 10, 50];
+        let sum: i64 = response
+            .chars()
+            .map(|c| {
+                let idx = buckets.iter().position(|b| b.contains(&c)).unwrap();
+                weights[idx]
+            })
+            .sum();
+
+        // "a0" -> 36 * 10 + 0
+        assert_eq!(sum, 10 + 360);
+    }
+
+    #[test]
+    fn test_encode_track() {
+        let track = Trajectory::from_points(vec![
+            TrackPoint { x: 0, y: 0, t: 0 },
+            TrackPoint { x: 1, y: 0, t: 10 },
+            TrackPoint { x: 11, y: 2, t: 30 },
+        ]);
+
+        // (1, 0) is a short step, (10, 2) is spelled out
+        assert_eq!(encode_track(&track), "3!!s*!!3A");
+    }
+
+    #[test]
+    fn test_mix_track_inserts_bytes() {
+        let mixed = mix_track("abcdef", &[1, 0, 1, 0, 1], "41");
+        assert_eq!(mixed.len(), 7);
+        assert!(mixed.contains('A'));
+    }
+}
@@ -0,0 +1,27 @@
+//! Geetest v3 support.
+//!
+//! v3 uses a `gt`/`challenge` pair obtained from the target site instead of a
+//! captcha ID, different endpoints (`gettype.php`, `get.php`, `ajax.php`) and
+//! its own `w` format. The RSA/AES primitives and image processing are shared
+//! with the v4 implementation.
+//!
+//! ```ignore
+//! use chaser_gt::Geeked;
+//!
+//! let solver = Geeked::builder_v3("gt_from_site", "challenge_from_site")
+//!     .proxy("http://127.0.0.1:8080")
+//!     .build()?;
+//!
+//! let result = solver.solve().await?;
+//! println!("geetest_validate: {}", result.validate);
+//! ```
+
+#[cfg(not(target_arch = "wasm32"))]
+mod client;
+pub mod crypto;
+pub mod models;
+pub mod solvers;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use client::{GeekedV3, GeekedV3Builder};
+pub use models::{V3CaptchaType, V3SecCode};
@@ -0,0 +1,131 @@
+//! Data models for Geetest v3 captcha.
+
+use serde::{Deserialize, Serialize};
+
+/// Captcha types supported by the v3 client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum V3CaptchaType {
+    /// Slide puzzle captcha
+    #[default]
+    Slide,
+    /// Click-in-order captcha (characters or icons)
+    Click,
+}
+
+impl V3CaptchaType {
+    /// Returns the `type` value used by `get.php`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            V3CaptchaType::Slide => "slide3",
+            V3CaptchaType::Click => "click",
+        }
+    }
+}
+
+impl std::fmt::Display for V3CaptchaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Validation result of a solved v3 captcha.
+///
+/// Serializes with the `geetest_*` field names sites expect in their forms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct V3SecCode {
+    #[serde(rename = "geetest_challenge")]
+    pub challenge: String,
+    #[serde(rename = "geetest_validate")]
+    pub validate: String,
+    #[serde(rename = "geetest_seccode")]
+    pub seccode: String,
+}
+
+impl V3SecCode {
+    /// Build the seccode triple from a challenge and validate token.
+    pub fn new(challenge: impl Into<String>, validate: impl Into<String>) -> Self {
+        let validate = validate.into();
+        Self {
+            challenge: challenge.into(),
+            seccode: format!("{}|jordan", validate),
+            validate,
+        }
+    }
+}
+
+/// Response data from `gettype.php`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TypeResponse {
+    #[serde(rename = "type")]
+    pub captcha_type: String,
+    #[serde(default)]
+    pub static_servers: Vec<String>,
+}
+
+/// Slide challenge returned by `get.php`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlideChallenge {
+    /// Extended challenge (original challenge plus two characters)
+    pub challenge: String,
+    /// Shuffled background with the gap
+    pub bg: String,
+    /// Shuffled background without the gap
+    pub fullbg: String,
+    /// Puzzle piece
+    pub slice: String,
+    /// Track mixing coefficients
+    #[serde(default)]
+    pub c: Vec<i64>,
+    /// Track mixing bytes (hex)
+    #[serde(default)]
+    pub s: String,
+    #[serde(default)]
+    pub static_servers: Vec<String>,
+}
+
+/// Click challenge returned by `get.php`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClickChallenge {
+    /// Extended challenge (original challenge plus two characters)
+    pub challenge: String,
+    /// Image with the click targets and the prompt strip
+    pub pic: String,
+    /// Track mixing coefficients
+    #[serde(default)]
+    pub c: Vec<i64>,
+    /// Track mixing bytes (hex)
+    #[serde(default)]
+    pub s: String,
+    #[serde(default)]
+    pub pic_type: Option<String>,
+    #[serde(default)]
+    pub static_servers: Vec<String>,
+}
+
+/// Response from `ajax.php`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AjaxResponse {
+    #[serde(default)]
+    pub success: i32,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub validate: Option<String>,
+    #[serde(default)]
+    pub score: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v3_seccode_fields() {
+        let seccode = V3SecCode::new("abc", "def");
+        assert_eq!(seccode.seccode, "def|jordan");
+
+        let json = serde_json::to_value(&seccode).unwrap();
+        assert_eq!(json["geetest_challenge"], "abc");
+        assert_eq!(json["geetest_validate"], "def");
+    }
+}
@@ -0,0 +1,174 @@
+//! Solvers for Geetest v3 challenges.
+//!
+//! The slide solver restores the shuffled background and reuses the v4
+//! template matcher. The click solver locates candidate targets with the
+//! shared region detector.
+
+use crate::error::Result;
+use crate::solvers::decode::decode_image;
+use crate::solvers::regions::{find_connected_components, otsu_threshold, threshold_image};
+use crate::solvers::{BoundingBox, SlideSolver};
+use image::{imageops, DynamicImage, RgbaImage};
+
+/// Order in which the 52 background strips were shuffled.
+const STRIP_ORDER: [u32; 52] = [
+    39, 38, 48, 49, 41, 40, 46, 47, 35, 34, 50, 51, 33, 32, 28, 29, 27, 26, 36, 37, 31, 30, 44, 45,
+    43, 42, 12, 13, 23, 22, 14, 15, 21, 20, 8, 9, 25, 24, 6, 7, 3, 2, 0, 1, 11, 10, 4, 5, 19, 18,
+    16, 17,
+];
+
+/// Width and height of a restored background.
+const RESTORED_WIDTH: u32 = 260;
+const RESTORED_HEIGHT: u32 = 160;
+
+/// Height of the click target area; the prompt strip sits below it.
+const CLICK_AREA_HEIGHT: u32 = 344;
+
+/// Restore a shuffled v3 background image.
+///
+/// The server delivers the background cut into 52 strips (26 per half) in a
+/// fixed permuted order; this puts them back into place.
+pub fn restore_background(img: &DynamicImage) -> RgbaImage {
+    let src = img.to_rgba8();
+    let mut restored = RgbaImage::new(RESTORED_WIDTH, RESTORED_HEIGHT);
+
+    for (idx, &strip) in STRIP_ORDER.iter().enumerate() {
+        let src_x = strip % 26 * 12 + 1;
+        let src_y = if strip > 25 { 80 } else { 0 };
+        let dst_x = idx as u32 % 26 * 10;
+        let dst_y = if idx > 25 { 80 } else { 0 };
+
+        if src_x + 10 > src.width() || src_y + 80 > src.height() {
+            continue;
+        }
+
+        let piece = imageops::crop_imm(&src, src_x, src_y, 10, 80).to_image();
+        imageops::replace(&mut restored, &piece, dst_x as i64, dst_y as i64);
+    }
+
+    restored
+}
+
+/// Find how far the slider has to travel for a v3 slide challenge.
+///
+/// # Arguments
+/// * `bg` - Bytes of the shuffled background with the gap
+/// * `slice` - Bytes of the puzzle piece
+pub fn find_slide_distance(bg: &[u8], slice: &[u8]) -> Result<f64> {
+    let bg = decode_image(bg, "background")?;
+    let slice = decode_image(slice, "puzzle piece")?;
+
+    let restored = DynamicImage::ImageRgba8(restore_background(&bg));
+    let solver = SlideSolver::new(slice, restored);
+    let (x, _y) = solver.find_match_location();
+
+    Ok(x as f64)
+}
+
+/// Encode click positions as `x_y` pairs in units of 1/10000 of the click area.
+pub fn encode_clicks(targets: &[(f64, f64)], area: (u32, u32)) -> String {
+    targets
+        .iter()
+        .map(|(x, y)| {
+            format!(
+                "{}_{}",
+                (x / area.0 as f64 * 10000.0).round() as u32,
+                (y / area.1 as f64 * 10000.0).round() as u32
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Best-effort solver for v3 click challenges.
+///
+/// Without a recognition model the solver cannot read the prompt, so it picks
+/// the most prominent candidate regions and clicks them left to right. The
+/// number of targets is taken from the prompt strip below the click area.
+pub struct ClickSolver {
+    image: DynamicImage,
+}
+
+impl ClickSolver {
+    /// Create a new click solver from image bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let image = decode_image(bytes, "image")?;
+        Ok(Self { image })
+    }
+
+    /// Size of the click area (the image without the prompt strip).
+    pub fn click_area(&self) -> (u32, u32) {
+        (
+            self.image.width(),
+            CLICK_AREA_HEIGHT.min(self.image.height()),
+        )
+    }
+
+    /// Locate the click targets.
+    ///
+    /// # Returns
+    /// Pixel centers of the targets inside the click area, in click order.
+    pub fn find_targets(&self) -> Vec<(f64, f64)> {
+        let (width, area_height) = self.click_area();
+        let height = self.image.height();
+
+        let prompt_count = if height > area_height {
+            let prompt = self
+                .image
+                .crop_imm(0, area_height, width, height - area_height);
+            Self::regions(&prompt, 4).len().clamp(1, 5)
+        } else {
+            3
+        };
+
+        let area = self.image.crop_imm(0, 0, width, area_height);
+        let mut candidates = Self::regions(&area, 16);
+        candidates.sort_by_key(|bbox| std::cmp::Reverse(bbox.width() * bbox.height()));
+        candidates.truncate(prompt_count);
+        candidates.sort_by_key(|bbox| bbox.x1);
+
+        candidates.iter().map(BoundingBox::center).collect()
+    }
+
+    /// Find foreground regions with a minimum side length.
+    fn regions(img: &DynamicImage, min_dim: u32) -> Vec<BoundingBox> {
+        let gray = img.to_luma8();
+        let binary = threshold_image(&gray, otsu_threshold(&gray));
+        find_connected_components(&binary)
+            .into_iter()
+            .filter(|bbox| bbox.width() >= min_dim && bbox.height() >= min_dim)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_restore_background_dimensions() {
+        let img = DynamicImage::new_rgba8(312, 160);
+        let restored = restore_background(&img);
+        assert_eq!(restored.dimensions(), (RESTORED_WIDTH, RESTORED_HEIGHT));
+    }
+
+    #[test]
+    fn test_restore_background_moves_strips() {
+        // Mark the source strip that ends up first in the restored image
+        let mut src = RgbaImage::new(312, 160);
+        let strip = STRIP_ORDER[0];
+        let x = strip % 26 * 12 + 1;
+        let y = if strip > 25 { 80 } else { 0 };
+        src.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+
+        let restored = restore_background(&DynamicImage::ImageRgba8(src));
+        assert_eq!(restored.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_encode_clicks() {
+        let encoded = encode_clicks(&[(172.0, 86.0), (34.4, 344.0)], (344, 344));
+        assert_eq!(encoded, "5000_2500,1000_10000");
+    }
+}
@@ -0,0 +1,310 @@
+//! WebAssembly bindings for chaser-gt.
+//!
+//! A fetch-based solver for browsers and edge runtimes (Cloudflare Workers,
+//! Deno). Requests go through the host's global `fetch`, so there is no TLS
+//! fingerprinting or proxy support, and constants are supplied by the host
+//! (e.g. the `constants.json` written by a native run, kept in KV) instead of
+//! being deobfuscated and cached on disk.
+//!
+//! Slide, gobang and AI captchas are supported.
+//!
+//! # Example (JavaScript)
+//!
+//! ```js
+//! import init, { WasmSolver } from './chaser_gt.js';
+//!
+//! await init();
+//! const solver = new WasmSolver('captcha_id', 'slide', await env.KV.get('constants'));
+//! const result = await solver.solve();
+//! console.log(result.pass_token);
+//! ```
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::error::{GeekedError, Result};
+use crate::models::{
+    jsonp_body, CachedConstants, Constants, GeetestResponse, LoadResponse, RiskType, SecCode,
+    VerifyOutcome, VerifyResponse,
+};
+use crate::sign::{generate_w_parameter, LotParser, SolverResult};
+use crate::solvers::{GobangSolver, SlideSolver};
+
+/// Cap on verify rounds for multi-round ("continue") captchas.
+const MAX_ROUNDS: u32 = 10;
+
+#[wasm_bindgen]
+extern "C" {
+    /// The host's global `fetch`.
+    #[wasm_bindgen(js_name = fetch)]
+    fn global_fetch(url: &str) -> js_sys::Promise;
+}
+
+/// Fetch a URL and return the response, failing on non-2xx statuses.
+async fn fetch(url: &str) -> Result<web_sys::Response> {
+    let response = JsFuture::from(global_fetch(url))
+        .await
+        .map_err(|e| js_error("Fetch failed", e))?;
+    let response: web_sys::Response = response
+        .dyn_into()
+        .map_err(|e| js_error("Fetch returned a non-Response", e))?;
+
+    if !response.ok() {
+        let body = text(&response).await.unwrap_or_default();
+        return Err(GeekedError::unexpected_response(
+            url,
+            response.status(),
+            &body,
+            None,
+        ));
+    }
+    Ok(response)
+}
+
+/// Read a response body as text.
+async fn text(response: &web_sys::Response) -> Result<String> {
+    let promise = response
+        .text()
+        .map_err(|e| js_error("Failed to read body", e))?;
+    let value = JsFuture::from(promise)
+        .await
+        .map_err(|e| js_error("Failed to read body", e))?;
+    Ok(value.as_string().unwrap_or_default())
+}
+
+/// Read a response body as bytes.
+async fn bytes(response: &web_sys::Response) -> Result<Vec<u8>> {
+    let promise = response
+        .array_buffer()
+        .map_err(|e| js_error("Failed to read body", e))?;
+    let value = JsFuture::from(promise)
+        .await
+        .map_err(|e| js_error("Failed to read body", e))?;
+    Ok(js_sys::Uint8Array::new(&value).to_vec())
+}
+
+fn js_error(context: &str, value: JsValue) -> GeekedError {
+    GeekedError::InvalidResponse(format!("{}: {:?}", context, value))
+}
+
+/// Build a URL with an encoded query string.
+fn url_with_query(base: &str, params: &[(&str, &str)]) -> String {
+    let query: Vec<String> = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
+        .collect();
+    format!("{}?{}", base, query.join("&"))
+}
+
+/// Fetch-based solver for wasm32 targets.
+#[wasm_bindgen]
+pub struct WasmSolver {
+    captcha_id: String,
+    risk_type: RiskType,
+    constants: Constants,
+    challenge: String,
+    lang: String,
+}
+
+#[wasm_bindgen]
+impl WasmSolver {
+    /// Create a solver.
+    ///
+    /// `constants` is the JSON of a constants cache file (`version`,
+    /// `fetched_at`, `mapping`, `abo`, `device_id`).
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        captcha_id: String,
+        risk_type: &str,
+        constants: &str,
+    ) -> std::result::Result<WasmSolver, JsError> {
+        let risk_type = risk_type.parse::<RiskType>()?;
+        let constants: CachedConstants = serde_json::from_str(constants)?;
+        // Reject mappings the signer could not use
+        LotParser::new(&constants.mapping)?;
+
+        Ok(Self {
+            captcha_id,
+            risk_type,
+            constants: constants.into(),
+            challenge: uuid::Uuid::new_v4().to_string(),
+            lang: "eng".to_string(),
+        })
+    }
+
+    /// Language requested from Geetest (default: "eng").
+    #[wasm_bindgen(setter)]
+    pub fn set_lang(&mut self, lang: String) {
+        self.lang = lang;
+    }
+
+    /// Solve the captcha.
+    ///
+    /// Resolves to an object with `captcha_id`, `lot_number`, `pass_token`,
+    /// `gen_time` and `captcha_output`.
+    pub async fn solve(&self) -> std::result::Result<JsValue, JsError> {
+        let seccode = self.run().await?;
+        let json = serde_json::to_string(&seccode)?;
+        js_sys::JSON::parse(&json).map_err(|_| JsError::new("Failed to convert result"))
+    }
+}
+
+impl WasmSolver {
+    fn callback() -> String {
+        let random = (rand::random::<f64>() * 10000.0) as u64;
+        format!("geetest_{}", random + js_sys::Date::now() as u64)
+    }
+
+    async fn get_jsonp<T: serde::de::DeserializeOwned>(
+        &self,
+        base: &str,
+        params: &[(&str, &str)],
+        callback: &str,
+    ) -> Result<T> {
+        let url = url_with_query(base, params);
+        let body = text(&fetch(&url).await?).await?;
+        if jsonp_body(&body, callback).is_none() {
+            return Err(GeekedError::unexpected_response(&url, 200, &body, None));
+        }
+        GeetestResponse::parse_jsonp(&body, callback, false)
+    }
+
+    async fn load(&self) -> Result<LoadResponse> {
+        let callback = Self::callback();
+        let params = [
+            ("captcha_id", self.captcha_id.as_str()),
+            ("challenge", self.challenge.as_str()),
+            ("client_type", "web"),
+            ("risk_type", self.risk_type.as_str()),
+            ("lang", self.lang.as_str()),
+            ("callback", callback.as_str()),
+        ];
+        self.get_jsonp("https://gcaptcha4.geetest.com/load", &params, &callback)
+            .await
+    }
+
+    async fn download_image(&self, path: &str) -> Result<Vec<u8>> {
+        let url = format!("https://static.geetest.com/{}", path);
+        bytes(&fetch(&url).await?).await
+    }
+
+    async fn solve_captcha(&self, data: &LoadResponse) -> Result<SolverResult> {
+        match &self.risk_type {
+            RiskType::Slide => {
+                let slice_path = data.slice.as_ref().ok_or_else(|| {
+                    GeekedError::InvalidResponse("Missing slice path for slide captcha".into())
+                })?;
+                let bg_path = data.bg.as_ref().ok_or_else(|| {
+                    GeekedError::InvalidResponse("Missing bg path for slide captcha".into())
+                })?;
+
+                let slice_bytes = self.download_image(slice_path).await?;
+                let bg_bytes = self.download_image(bg_path).await?;
+                let solver = SlideSolver::from_bytes(&slice_bytes, &bg_bytes)?;
+                let variation: f64 = rand::random::<f64>() * 0.5;
+                let (raw_x, raw_y) = solver.find_match_location();
+                Ok(SolverResult::Slide {
+                    left: solver.left_from_match(raw_x) + variation,
+                    top: data.slide_needs_y().then_some(f64::from(raw_y)),
+                    raw: [f64::from(raw_x), f64::from(raw_y)],
+                })
+            }
+            RiskType::Gobang => {
+                let ques = data.ques.as_ref().ok_or_else(|| {
+                    GeekedError::InvalidResponse("Missing ques for gobang captcha".into())
+                })?;
+                let board: Vec<Vec<i32>> = serde_json::from_value(ques.clone())?;
+                let result = GobangSolver::new(board)
+                    .find_four_in_line()
+                    .ok_or_else(|| GeekedError::VerificationFailed {
+                        message: "Could not solve gobang puzzle".into(),
+                    })?;
+                Ok(SolverResult::Gobang {
+                    response: vec![
+                        vec![result[0][0], result[0][1]],
+                        vec![result[1][0], result[1][1]],
+                    ],
+                })
+            }
+            RiskType::Ai => Ok(SolverResult::Ai),
+            other => Err(GeekedError::UnsupportedType(format!(
+                "'{}' captchas are not supported on wasm",
+                other.as_str()
+            ))),
+        }
+    }
+
+    async fn submit(&self, round: &LoadResponse, w: &str) -> Result<VerifyOutcome> {
+        let callback = Self::callback();
+        let protocol = round.payload_protocol()?;
+        let params = [
+            ("callback", callback.as_str()),
+            ("captcha_id", self.captcha_id.as_str()),
+            ("client_type", "web"),
+            ("lot_number", round.lot_number.as_str()),
+            ("risk_type", self.risk_type.as_str()),
+            ("payload", round.payload.as_str()),
+            ("process_token", round.process_token.as_str()),
+            ("payload_protocol", protocol.as_str()),
+            ("pt", self.constants.protocol.pt.as_str()),
+            ("w", w),
+        ];
+
+        let response: VerifyResponse = self
+            .get_jsonp("https://gcaptcha4.geetest.com/verify", &params, &callback)
+            .await?;
+        Ok(response.into())
+    }
+
+    async fn run(&self) -> Result<SecCode> {
+        let mut round = self.load().await?;
+        let mut solver_result = Some(self.solve_captcha(&round).await?);
+
+        for _ in 0..MAX_ROUNDS {
+            let w = generate_w_parameter(
+                &round,
+                &self.captcha_id,
+                &self.risk_type,
+                &self.constants,
+                solver_result,
+            )?;
+
+            solver_result = match self.submit(&round, &w).await? {
+                VerifyOutcome::Success(seccode) => return Ok(seccode),
+                VerifyOutcome::Continue(update) => {
+                    if round.apply_continue(update) {
+                        Some(self.solve_captcha(&round).await?)
+                    } else {
+                        None
+                    }
+                }
+                VerifyOutcome::Retry => {
+                    round = self.load().await?;
+                    Some(self.solve_captcha(&round).await?)
+                }
+                VerifyOutcome::Fail { reason, code } => {
+                    let message = match code {
+                        Some(code) => format!("{} (code {})", reason, code),
+                        None => reason,
+                    };
+                    return Err(GeekedError::VerificationFailed { message });
+                }
+            };
+        }
+
+        Err(GeekedError::VerificationFailed {
+            message: format!("Max retries ({}) exceeded", MAX_ROUNDS),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_with_query_encodes_values() {
+        let url = url_with_query("https://example.com/load", &[("a", "x y"), ("w", "a+b")]);
+        assert_eq!(url, "https://example.com/load?a=x%20y&w=a%2Bb");
+    }
+}
@@ -0,0 +1,234 @@
+//! Result callbacks for server tasks and worker jobs.
+//!
+//! When a task or job carries a callback URL, its outcome is POSTed there as
+//! JSON. With a webhook secret configured, the request carries
+//! `X-Chaser-Timestamp` (Unix seconds) and `X-Chaser-Signature:
+//! sha256=<hex>`, the HMAC-SHA256 of `<timestamp>.<body>` under the secret.
+//! Failed deliveries (network errors, 429 and 5xx) are retried with
+//! exponential backoff.
+//!
+//! Callback URLs come from clients, so they must use `https` and, when
+//! callback hosts are configured, name one of those hosts. Hosts resolving to loopback, private,
+//! link-local or other internal addresses are refused, and redirects are not
+//! followed.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use rquest::redirect::Policy;
+use rquest::Url;
+use sha2::Sha256;
+
+use crate::error::{GeekedError, Result};
+
+/// Timeout for one delivery attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before the first retry; doubled for each further one.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Where callbacks may be sent, and how they are signed and retried.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Webhooks {
+    /// Key for signing callbacks; unsigned if unset
+    pub secret: Option<String>,
+    /// Retries of a failed callback
+    pub retries: u32,
+    /// Hosts callbacks may be sent to; any public host if empty
+    pub hosts: Arc<Vec<String>>,
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>`.
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Whether `ip` is reachable on the public internet, as opposed to
+/// loopback, private, link-local, shared (CGNAT) or reserved ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(ip.into());
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Check a client-supplied callback URL before accepting its task.
+///
+/// Hosts given by name are checked again when resolved for delivery.
+pub(crate) fn check_url(webhooks: &Webhooks, url: &str) -> Result<()> {
+    let invalid = |reason: &str| GeekedError::UnsupportedType(format!("callback URL {}", reason));
+    let url = Url::parse(url).map_err(|_| invalid("is malformed"))?;
+    if url.scheme() != "https" {
+        return Err(invalid("must use https"));
+    }
+    let host = url.host_str().ok_or_else(|| invalid("has no host"))?;
+    if !webhooks.hosts.is_empty()
+        && !webhooks
+            .hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    {
+        return Err(invalid("names a host that is not allowed"));
+    }
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) if !is_public(ip) => Err(invalid("points at an internal address")),
+        _ => Ok(()),
+    }
+}
+
+/// Resolve `host` for a delivery, refusing internal addresses.
+async fn resolve_public(host: &str, port: u16) -> Result<SocketAddr> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(GeekedError::UnsupportedType(format!(
+            "callback host {} resolves to an internal address",
+            host
+        )));
+    }
+    Ok(addrs[0])
+}
+
+/// Client for one delivery attempt to `url`, pinned to the address its host
+/// resolved to so a second lookup cannot redirect it inward.
+async fn client_for(url: &str) -> Result<rquest::Client> {
+    let parsed = Url::parse(url)
+        .map_err(|_| GeekedError::UnsupportedType("callback URL is malformed".to_string()))?;
+    let host = parsed.host_str().unwrap_or_default();
+    let addr = resolve_public(
+        host.trim_matches(['[', ']']),
+        parsed.port_or_known_default().unwrap_or(443),
+    )
+    .await?;
+    Ok(rquest::Client::builder()
+        .redirect(Policy::none())
+        .resolve(host, addr)
+        .build()?)
+}
+
+/// POST the JSON `body` to `url`, retrying failed deliveries. `what` names
+/// the task or job in logs.
+pub(crate) async fn deliver(webhooks: &Webhooks, url: &str, what: &str, body: &str) {
+    for attempt in 0..=webhooks.retries {
+        if attempt > 0 {
+            tokio::time::sleep(RETRY_BACKOFF * 2u32.saturating_pow(attempt - 1)).await;
+        }
+
+        let client = match client_for(url).await {
+            Ok(client) => client,
+            Err(e @ GeekedError::UnsupportedType(_)) => {
+                tracing::warn!("Refusing the callback for {}: {}", what, e);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("Callback for {} failed: {}", what, e);
+                continue;
+            }
+        };
+        let mut request = client
+            .post(url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header("content-type", "application/json");
+        if let Some(secret) = &webhooks.secret {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            request = request
+                .header("x-chaser-timestamp", timestamp.to_string())
+                .header(
+                    "x-chaser-signature",
+                    format!("sha256={}", sign(secret, timestamp, body)),
+                );
+        }
+
+        match request.body(body.to_string()).send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                let status = response.status();
+                tracing::warn!("Callback for {} answered {}", what, status);
+                if status.is_client_error() && status.as_u16() != 429 {
+                    return;
+                }
+            }
+            Err(e) => tracing::warn!("Callback for {} failed: {}", what, e),
+        }
+    }
+    tracing::warn!("Giving up on the callback for {}", what);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // Matches `printf '%s' '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret`
+        assert_eq!(
+            sign("secret", 1_700_000_000, r#"{"a":1}"#),
+            "49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_hosts_are_refused() {
+        assert!(matches!(
+            resolve_public("localhost", 443).await,
+            Err(GeekedError::UnsupportedType(_))
+        ));
+        assert!(matches!(
+            client_for("https://127.0.0.1/hook").await,
+            Err(GeekedError::UnsupportedType(_))
+        ));
+    }
+}
@@ -0,0 +1,636 @@
+//! Queue worker: solves jobs pulled from Redis.
+//!
+//! Producers `LPUSH` JSON jobs onto the job list:
+//!
+//! ```text
+//! {"id": "job-1", "captcha_id": "...", "risk_type": "slide",
+//!  "proxy": null, "user_info": null, "callback_url": "https://..."}
+//! ```
+//!
+//! Each job is atomically moved to a per-worker processing list while it is
+//! solved, and only removed from there once its outcome has been published,
+//! so a crashed worker loses nothing: on restart it re-queues whatever its
+//! processing list still holds (at-least-once delivery).
+//!
+//! Outcomes are `RPUSH`ed to the results list as [`JobResult`] JSON and, if
+//! the job has a `callback_url`, POSTed there under the same rules as server
+//! task callbacks: https only, no internal hosts or redirects, signed with
+//! [`WorkerConfig::webhook_secret`] and retried on failure. Jobs that fail permanently, or
+//! keep failing after `max_attempts`, are also copied to the dead-letter list
+//! together with the error.
+//!
+//! Batch jobs go on a separate list (`chaser-gt:jobs:batch`). Interactive
+//! and batch jobs share `max_concurrency`, but batch jobs only get their
+//! [`PriorityShares`] of it, so a backlog of them cannot hold up interactive
+//! work.
+//!
+//! With [`WorkerConfig::config_file`] set, the concurrency settings are
+//! re-read on SIGHUP or when the file changes (see [`crate::reload`]).
+//!
+//! On SIGTERM the worker stops popping jobs and gives the ones it is solving
+//! [`WorkerConfig::drain_timeout`] to finish; jobs still running after that
+//! stay in the processing list for the next start.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Direction};
+use serde::{Deserialize, Serialize};
+
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+use crate::deobfuscate::Deobfuscator;
+use crate::error::{GeekedError, Result};
+use crate::image_cache::{ImageCache, ImageCacheConfig};
+use crate::models::{Constants, RiskType, SecCode};
+use crate::priority::{Priority, PriorityLimiter, PriorityShares};
+#[cfg(feature = "redis-cache")]
+use crate::redis_cache::RedisCache;
+use crate::reload::{self, RuntimeConfig};
+use crate::shutdown::{self, JobTracker};
+#[cfg(feature = "icon")]
+use crate::solvers::{IconSolver, IconSolverConfig};
+use crate::webhook::{self, Webhooks};
+use crate::Geeked;
+
+/// How long one blocking pop waits before checking again.
+const POP_TIMEOUT_SECS: f64 = 5.0;
+
+/// Pause after a queue error before reconnecting.
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Worker settings.
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Redis connection URL
+    pub redis_url: String,
+    /// List producers push interactive jobs onto
+    pub queue: String,
+    /// List producers push batch jobs onto
+    pub batch_queue: String,
+    /// List outcomes are pushed onto
+    pub results: String,
+    /// List for jobs that could not be solved
+    pub dead_letter: String,
+    /// Stable, unique ID of this worker; names its processing list
+    pub worker_id: String,
+    /// Maximum number of solves running at once
+    pub max_concurrency: usize,
+    /// Share of `max_concurrency` each priority may use
+    pub priority_shares: PriorityShares,
+    /// Runtime config file overriding the concurrency settings; reloaded
+    /// when it changes
+    pub config_file: Option<PathBuf>,
+    /// Attempts per job before it is dead-lettered
+    pub max_attempts: u32,
+    /// Directory for the constants cache (platform default if unset)
+    pub cache_dir: Option<PathBuf>,
+    /// How often to check for new constants
+    pub constants_refresh: Duration,
+    /// Refuse to start with outdated constants that cannot be refreshed
+    pub strict_constants: bool,
+    /// Fail jobs on Geetest responses with unknown fields (canaries)
+    pub strict_responses: bool,
+    /// Upper bound on one solve, continue rounds included; unbounded if unset
+    pub solve_deadline: Option<Duration>,
+    /// Redis URL for sharing constants with other instances
+    #[cfg(feature = "redis-cache")]
+    pub constants_redis_url: Option<String>,
+    /// Session template appended to the proxy user name per solve, for
+    /// rotating gateways (e.g. `-session-{id}`)
+    pub proxy_session_template: Option<String>,
+    /// Cache for captcha images shared by all jobs; off if unset
+    pub image_cache: Option<ImageCacheConfig>,
+    /// Fail jobs fast for captcha IDs that keep failing; off if unset
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// ONNX Runtime settings for the icon model; library defaults if unset
+    #[cfg(feature = "icon")]
+    pub icon: Option<IconSolverConfig>,
+    /// Key for signing job callbacks; unsigned if unset
+    pub webhook_secret: Option<String>,
+    /// Retries of a failed job callback
+    pub webhook_retries: u32,
+    /// Hosts job callbacks may be sent to; any public host if empty
+    pub callback_hosts: Vec<String>,
+    /// How long running jobs may take to finish on shutdown
+    pub drain_timeout: Duration,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            redis_url: "redis://127.0.0.1/".to_string(),
+            queue: "chaser-gt:jobs".to_string(),
+            batch_queue: "chaser-gt:jobs:batch".to_string(),
+            results: "chaser-gt:results".to_string(),
+            dead_letter: "chaser-gt:dead".to_string(),
+            worker_id: std::env::var("HOSTNAME").unwrap_or_else(|_| "default".to_string()),
+            max_concurrency: 16,
+            priority_shares: PriorityShares::default(),
+            config_file: None,
+            max_attempts: 3,
+            cache_dir: None,
+            constants_refresh: Duration::from_secs(600),
+            strict_constants: false,
+            strict_responses: false,
+            solve_deadline: None,
+            #[cfg(feature = "redis-cache")]
+            constants_redis_url: None,
+            proxy_session_template: None,
+            image_cache: None,
+            circuit_breaker: None,
+            #[cfg(feature = "icon")]
+            icon: None,
+            webhook_secret: None,
+            webhook_retries: 3,
+            callback_hosts: Vec::new(),
+            drain_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Concurrency settings, with the runtime config file applied.
+    fn limits(&self) -> Result<(usize, PriorityShares)> {
+        let mut max_concurrency = self.max_concurrency;
+        let mut shares = self.priority_shares.clone();
+        if let Some(path) = &self.config_file {
+            RuntimeConfig::load(path)?.apply(&mut max_concurrency, &mut shares);
+        }
+        Ok((max_concurrency, shares))
+    }
+
+    /// List holding the interactive jobs this worker is currently solving.
+    pub fn processing_list(&self) -> String {
+        self.processing_list_for(Priority::Interactive)
+    }
+
+    /// List holding the jobs of `priority` this worker is currently solving.
+    pub fn processing_list_for(&self, priority: Priority) -> String {
+        format!("{}:processing:{}", self.queue_for(priority), self.worker_id)
+    }
+
+    /// List producers push jobs of `priority` onto.
+    pub fn queue_for(&self, priority: Priority) -> &str {
+        match priority {
+            Priority::Interactive => &self.queue,
+            Priority::Batch => &self.batch_queue,
+        }
+    }
+
+    fn lane(&self, priority: Priority) -> Lane {
+        Lane {
+            queue: self.queue_for(priority).to_string(),
+            processing: self.processing_list_for(priority),
+        }
+    }
+}
+
+/// The job list of one priority and this worker's processing list for it.
+struct Lane {
+    queue: String,
+    processing: String,
+}
+
+/// A solve job as pushed by producers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueJob {
+    /// Producer-chosen ID, echoed in the result
+    pub id: String,
+    pub captcha_id: String,
+    pub risk_type: RiskType,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub user_info: Option<String>,
+    /// URL the [`JobResult`] is POSTed to
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// Failed attempts so far; maintained by the worker
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// Outcome of a job, published to the results list and callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResult {
+    pub id: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seccode: Option<SecCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<i32>,
+}
+
+/// What to do with a job whose solve failed.
+#[derive(Debug, PartialEq, Eq)]
+enum Disposition {
+    /// Put it back on the queue
+    Retry,
+    /// Give up and dead-letter it
+    DeadLetter,
+}
+
+fn disposition(job: &QueueJob, err: &GeekedError, max_attempts: u32) -> Disposition {
+    if err.is_retryable() && job.attempts + 1 < max_attempts {
+        Disposition::Retry
+    } else {
+        Disposition::DeadLetter
+    }
+}
+
+impl From<redis::RedisError> for GeekedError {
+    fn from(err: redis::RedisError) -> Self {
+        GeekedError::Queue(err.to_string())
+    }
+}
+
+/// Shared state of the worker tasks.
+struct Worker {
+    config: WorkerConfig,
+    interactive: Lane,
+    batch: Lane,
+    redis: ConnectionManager,
+    webhooks: Webhooks,
+    constants: RwLock<Arc<Constants>>,
+    image_cache: Option<Arc<ImageCache>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+/// Consume jobs until SIGTERM or Ctrl-C.
+///
+/// Returns early only if Redis or the constants cannot be reached at startup.
+pub async fn run(config: WorkerConfig) -> Result<()> {
+    run_until(config, shutdown::signal()).await
+}
+
+/// Consume jobs until `shutdown` resolves, then wait up to
+/// [`WorkerConfig::drain_timeout`] for running jobs.
+pub async fn run_until(config: WorkerConfig, shutdown: impl Future<Output = ()>) -> Result<()> {
+    #[cfg(feature = "icon")]
+    if let Some(icon) = &config.icon {
+        IconSolver::init_shared(icon)?;
+    }
+    let client = redis::Client::open(config.redis_url.as_str())?;
+    let redis = client.get_connection_manager().await?;
+    // Blocking pops need connections without a response timeout, one per
+    // lane so they do not wait on each other
+    let blocking = ConnectionManagerConfig::new().set_response_timeout(None);
+    let interactive_pops = client
+        .get_connection_manager_with_config(blocking.clone())
+        .await?;
+    let batch_pops = client.get_connection_manager_with_config(blocking).await?;
+
+    let deobfuscator = match &config.cache_dir {
+        Some(dir) => Deobfuscator::with_cache_path(dir.join("constants.json")),
+        None => Deobfuscator::new(),
+    }
+    .with_strict_refresh(config.strict_constants);
+    #[cfg(feature = "redis-cache")]
+    let deobfuscator = match &config.constants_redis_url {
+        Some(url) => deobfuscator.with_redis_cache(RedisCache::connect(url).await?),
+        None => deobfuscator,
+    };
+    let constants = deobfuscator.get_constants().await?;
+
+    let worker = Arc::new(Worker {
+        interactive: config.lane(Priority::Interactive),
+        batch: config.lane(Priority::Batch),
+        redis,
+        webhooks: Webhooks {
+            secret: config.webhook_secret.clone(),
+            retries: config.webhook_retries,
+            hosts: Arc::new(config.callback_hosts.clone()),
+        },
+        constants: RwLock::new(Arc::new(constants)),
+        image_cache: config
+            .image_cache
+            .clone()
+            .map(|config| Arc::new(ImageCache::new(config))),
+        circuit_breaker: config
+            .circuit_breaker
+            .clone()
+            .map(|config| Arc::new(CircuitBreaker::with_config(config))),
+        config,
+    });
+
+    worker.recover().await?;
+
+    let refresh_worker = worker.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(refresh_worker.config.constants_refresh).await;
+            match deobfuscator.get_constants().await {
+                Ok(constants) => {
+                    *refresh_worker
+                        .constants
+                        .write()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner) = Arc::new(constants)
+                }
+                Err(e) => tracing::warn!("Failed to refresh constants: {}", e),
+            }
+        }
+    });
+
+    tracing::info!(
+        "Worker {} consuming {} and {}",
+        worker.config.worker_id,
+        worker.config.queue,
+        worker.config.batch_queue
+    );
+    let limiter = Arc::new(PriorityLimiter::new(
+        worker.config.max_concurrency,
+        &worker.config.priority_shares,
+    ));
+    if let Some(path) = worker.config.config_file.clone() {
+        let (max_concurrency, shares) = worker.config.limits()?;
+        limiter.resize(max_concurrency, &shares);
+
+        let (limiter, worker) = (limiter.clone(), worker.clone());
+        reload::spawn(vec![path], reload::FILE_POLL, move || {
+            match worker.config.limits() {
+                Ok((max_concurrency, shares)) => limiter.resize(max_concurrency, &shares),
+                Err(e) => tracing::warn!("Keeping the current concurrency limits: {}", e),
+            }
+        });
+    }
+    let jobs = JobTracker::default();
+    // A pop interrupted here leaves at most its job in the processing list
+    tokio::select! {
+        _ = consume(&worker, Priority::Interactive, &limiter, &jobs, interactive_pops) => {}
+        _ = consume(&worker, Priority::Batch, &limiter, &jobs, batch_pops) => {}
+        _ = shutdown => {}
+    }
+
+    let unfinished = jobs.drain(worker.config.drain_timeout).await;
+    if unfinished > 0 {
+        tracing::warn!(
+            "Stopping with {} jobs still running; they are re-queued on restart",
+            unfinished
+        );
+    }
+    Ok(())
+}
+
+/// Pop and solve jobs of one priority, within its share of the slots.
+async fn consume(
+    worker: &Arc<Worker>,
+    priority: Priority,
+    limiter: &PriorityLimiter,
+    jobs: &JobTracker,
+    mut blocking: ConnectionManager,
+) {
+    let lane = worker.lane(priority);
+    loop {
+        let permit = limiter.acquire(priority).await;
+
+        let popped: Option<String> = match blocking
+            .blmove(
+                &lane.queue,
+                &lane.processing,
+                Direction::Right,
+                Direction::Left,
+                POP_TIMEOUT_SECS,
+            )
+            .await
+        {
+            Ok(popped) => popped,
+            Err(e) => {
+                tracing::warn!("Failed to pop job: {}", e);
+                tokio::time::sleep(ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+
+        if let Some(raw) = popped {
+            let worker = worker.clone();
+            let job = jobs.start();
+            tokio::spawn(async move {
+                if let Err(e) = worker.handle(worker.lane(priority), &raw).await {
+                    // The job stays in the processing list and is re-queued
+                    // when the worker restarts
+                    tracing::error!("Failed to finish job: {}", e);
+                }
+                drop(permit);
+                drop(job);
+            });
+        }
+    }
+}
+
+impl Worker {
+    fn lane(&self, priority: Priority) -> &Lane {
+        match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Batch => &self.batch,
+        }
+    }
+
+    /// Re-queue jobs left in the processing lists by a previous run.
+    async fn recover(&self) -> Result<()> {
+        let mut redis = self.redis.clone();
+        let mut recovered = 0;
+        for lane in [&self.interactive, &self.batch] {
+            loop {
+                let moved: Option<String> = redis
+                    .lmove(
+                        &lane.processing,
+                        &lane.queue,
+                        Direction::Right,
+                        Direction::Right,
+                    )
+                    .await?;
+                if moved.is_none() {
+                    break;
+                }
+                recovered += 1;
+            }
+        }
+        if recovered > 0 {
+            tracing::info!("Re-queued {} unfinished jobs", recovered);
+        }
+        Ok(())
+    }
+
+    /// Solve one job and publish, retry or dead-letter it.
+    async fn handle(&self, lane: &Lane, raw: &str) -> Result<()> {
+        let mut redis = self.redis.clone();
+
+        let mut job: QueueJob = match serde_json::from_str(raw) {
+            Ok(job) => job,
+            Err(e) => {
+                tracing::warn!("Dead-lettering malformed job: {}", e);
+                let entry = serde_json::json!({
+                    "job": raw,
+                    "error": e.to_string(),
+                    "error_code": GeekedError::from(e).error_code(),
+                });
+                let _: () = redis
+                    .rpush(&self.config.dead_letter, entry.to_string())
+                    .await?;
+                return self.ack(lane, raw).await;
+            }
+        };
+
+        match self.solve(&job).await {
+            Ok(seccode) => {
+                self.publish(
+                    &job,
+                    JobResult {
+                        id: job.id.clone(),
+                        success: true,
+                        seccode: Some(seccode),
+                        error: None,
+                        error_code: None,
+                    },
+                )
+                .await?;
+            }
+            Err(e) => match disposition(&job, &e, self.config.max_attempts) {
+                Disposition::Retry => {
+                    tracing::debug!("Job {} failed, retrying: {}", job.id, e);
+                    job.attempts += 1;
+                    let _: () = redis
+                        .lpush(&lane.queue, serde_json::to_string(&job)?)
+                        .await?;
+                }
+                Disposition::DeadLetter => {
+                    tracing::warn!("Job {} failed: {}", job.id, e);
+                    let entry = serde_json::json!({
+                        "job": job,
+                        "error": e.to_string(),
+                        "error_code": e.error_code(),
+                    });
+                    let _: () = redis
+                        .rpush(&self.config.dead_letter, entry.to_string())
+                        .await?;
+                    self.publish(
+                        &job,
+                        JobResult {
+                            id: job.id.clone(),
+                            success: false,
+                            seccode: None,
+                            error: Some(e.to_string()),
+                            error_code: Some(e.error_code()),
+                        },
+                    )
+                    .await?;
+                }
+            },
+        }
+
+        self.ack(lane, raw).await
+    }
+
+    async fn solve(&self, job: &QueueJob) -> Result<SecCode> {
+        let constants = self
+            .constants
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+
+        let mut builder = Geeked::builder(job.captcha_id.clone(), job.risk_type.clone())
+            .constants(constants)
+            .strict_responses(self.config.strict_responses);
+        if let Some(deadline) = self.config.solve_deadline {
+            builder = builder.solve_deadline(deadline);
+        }
+        if let Some(proxy) = &job.proxy {
+            builder = builder.proxy(proxy.clone());
+            if let Some(template) = &self.config.proxy_session_template {
+                builder = builder.proxy_session_template(template.clone());
+            }
+        }
+        if let Some(user_info) = &job.user_info {
+            builder = builder.user_info(user_info.clone());
+        }
+        if let Some(cache) = &self.image_cache {
+            builder = builder.image_cache(cache.clone());
+        }
+        if let Some(breaker) = &self.circuit_breaker {
+            builder = builder.circuit_breaker(breaker.clone());
+        }
+        builder.build().await?.solve().await
+    }
+
+    /// Push a result to the results list and the job's callback.
+    ///
+    /// A refused or failing callback is logged; the result stays in the
+    /// results list.
+    async fn publish(&self, job: &QueueJob, result: JobResult) -> Result<()> {
+        let body = serde_json::to_string(&result)?;
+        let mut redis = self.redis.clone();
+        let _: () = redis.rpush(&self.config.results, &body).await?;
+
+        if let Some(url) = &job.callback_url {
+            let what = format!("job {}", job.id);
+            match webhook::check_url(&self.webhooks, url) {
+                Ok(()) => webhook::deliver(&self.webhooks, url, &what, &body).await,
+                Err(e) => tracing::warn!("Refusing the callback for {}: {}", what, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a finished job from the processing list.
+    async fn ack(&self, lane: &Lane, raw: &str) -> Result<()> {
+        let mut redis = self.redis.clone();
+        let _: () = redis.lrem(&lane.processing, 1, raw).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(attempts: u32) -> QueueJob {
+        serde_json::from_value(serde_json::json!({
+            "id": "job-1",
+            "captcha_id": "x",
+            "risk_type": "slide",
+            "attempts": attempts,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_job_defaults() {
+        let job: QueueJob =
+            serde_json::from_str(r#"{"id": "1", "captcha_id": "x", "risk_type": "gobang"}"#)
+                .unwrap();
+        assert_eq!(job.risk_type, RiskType::Gobang);
+        assert_eq!(job.attempts, 0);
+        assert!(job.callback_url.is_none());
+    }
+
+    #[test]
+    fn test_disposition() {
+        let transient = GeekedError::VerificationFailed {
+            message: "fail".into(),
+        };
+        assert_eq!(disposition(&job(0), &transient, 3), Disposition::Retry);
+        assert_eq!(disposition(&job(2), &transient, 3), Disposition::DeadLetter);
+
+        let permanent = GeekedError::UnsupportedType("puzzle".into());
+        assert_eq!(disposition(&job(0), &permanent, 3), Disposition::DeadLetter);
+    }
+
+    #[test]
+    fn test_lists_per_priority() {
+        let config = WorkerConfig {
+            worker_id: "w1".into(),
+            ..Default::default()
+        };
+        assert_eq!(config.processing_list(), "chaser-gt:jobs:processing:w1");
+        assert_eq!(config.queue_for(Priority::Batch), "chaser-gt:jobs:batch");
+        assert_eq!(
+            config.processing_list_for(Priority::Batch),
+            "chaser-gt:jobs:batch:processing:w1"
+        );
+    }
+}